@@ -45,20 +45,20 @@ layout(location = 1) out vec4 normal_attachment;
 layout(location = 2) out vec4 light_attachment;
 
 vec4 calc_light() {
-    vec4 light = vec4(0.0, 0.0, 0.0, 0.0);
+    vec3 light = vec3(0.0, 0.0, 0.0);
     for (int i = 0; i < 4 && f_lightmap_anim[i] != LIGHTMAP_ANIM_END; i++) {
-        float map = texture(
+        vec3 map = texture(
             sampler2D(u_lightmap_texture[i], u_lightmap_sampler),
             f_lightmap
-        ).r * 2.0;
+        ).rgb * 2.0;
 
         // range [0, 4]
         float style = frame_uniforms.light_anim_frames[f_lightmap_anim[i]];
-        light[i] = map * style;
+        light += map * style;
     }
 
     // scale by quarter so values don't get clamped
-    return light / 4.0;
+    return vec4(light / 4.0, 1.0);
 }
 
 void main() {
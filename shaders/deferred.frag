@@ -53,7 +53,7 @@ void main() {
 
   vec4 out_color = in_color;
 
-  float light = in_light.x + in_light.y + in_light.z + in_light.w;
+  vec3 light = in_light.rgb;
   for (uint i = 0; i < u_deferred.light_count && i < MAX_LIGHTS; i++) {
     vec4 dlight = u_deferred.lights[i];
     vec3 dir = normalize(position - dlight_origin(dlight));
@@ -67,7 +67,7 @@ void main() {
   }
 
   // allow 200% light saturation
-  light = min(light, 4.0);
+  light = min(light, vec3(4.0));
 
   color_attachment = vec4(out_color.rgb * light, 1.0);
 }
@@ -0,0 +1,98 @@
+extern crate richter;
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write as _},
+    path::PathBuf,
+    process::exit,
+};
+
+use richter::client::demo::convert::{convert_demo, Protocol};
+
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+struct Opt {
+    #[structopt(long)]
+    version: bool,
+
+    /// Protocol version of the output demo (15 or 666).
+    #[structopt(short, long, default_value = "15")]
+    protocol: i32,
+
+    #[structopt(name = "INPUT_DEM", parse(from_os_str))]
+    input_dem: PathBuf,
+
+    #[structopt(name = "OUTPUT_DEM", parse(from_os_str))]
+    output_dem: PathBuf,
+}
+
+const VERSION: &'static str = "
+demconv 0.1
+Copyright © 2020 Cormac O'Brien
+Released under the terms of the MIT License
+";
+
+fn main() {
+    let opt = Opt::from_args();
+
+    if opt.version {
+        println!("{}", VERSION);
+        exit(0);
+    }
+
+    let target = match Protocol::from_version(opt.protocol) {
+        Some(p) => p,
+        None => {
+            println!("Unsupported protocol version: {}", opt.protocol);
+            exit(1);
+        }
+    };
+
+    let mut reader = match File::open(&opt.input_dem) {
+        Ok(f) => BufReader::new(f),
+        Err(why) => {
+            println!("Couldn't open {:#?}: {}", &opt.input_dem, why);
+            exit(1);
+        }
+    };
+
+    let mut writer = match File::create(&opt.output_dem) {
+        Ok(f) => BufWriter::new(f),
+        Err(why) => {
+            println!("Couldn't create {:#?}: {}", &opt.output_dem, why);
+            exit(1);
+        }
+    };
+
+    let report = match convert_demo(&mut reader, &mut writer, target) {
+        Ok(r) => r,
+        Err(why) => {
+            println!("Couldn't convert {:#?}: {}", &opt.input_dem, why);
+            exit(1);
+        }
+    };
+
+    if let Err(why) = writer.flush() {
+        println!("Couldn't write to {:#?}: {}", &opt.output_dem, why);
+        exit(1);
+    }
+
+    let src_version = report
+        .src_protocol()
+        .map(|p| p.version().to_string())
+        .unwrap_or_else(|| "unknown".to_owned());
+    println!(
+        "Converted {} messages from protocol {} to protocol {}",
+        report.message_count(),
+        src_version,
+        target.version()
+    );
+
+    if !report.is_lossless() {
+        println!("The following features could not be represented and were dropped:");
+        for (feature, count) in report.lossy_features() {
+            println!("    {} ({} occurrences)", feature, count);
+        }
+    }
+}
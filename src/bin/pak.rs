@@ -0,0 +1,260 @@
+// Copyright © 2018 Cormac O'Brien
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this software
+// and associated documentation files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all copies or
+// substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING
+// BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+extern crate richter;
+
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::{Component, Path, PathBuf},
+    process::exit,
+};
+
+use richter::common::pak::{Pak, PakWriter};
+
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+struct Opt {
+    #[structopt(long)]
+    version: bool,
+
+    #[structopt(subcommand)]
+    cmd: Option<Cmd>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Cmd {
+    /// Create a PAK archive from the contents of a directory.
+    Create {
+        #[structopt(name = "INPUT_DIR", parse(from_os_str))]
+        input_dir: PathBuf,
+
+        #[structopt(name = "OUTPUT_PAK", parse(from_os_str))]
+        output_pak: PathBuf,
+    },
+
+    /// List the files in a PAK archive with their sizes and offsets.
+    List {
+        #[structopt(name = "PAK", parse(from_os_str))]
+        pak: PathBuf,
+    },
+
+    /// Add files to a PAK archive, replacing any with the same name.
+    Add {
+        /// Directory the files are named relative to.
+        #[structopt(short = "C", long, parse(from_os_str), default_value = ".")]
+        dir: PathBuf,
+
+        #[structopt(name = "PAK", parse(from_os_str))]
+        pak: PathBuf,
+
+        #[structopt(name = "FILES", required = true)]
+        files: Vec<String>,
+    },
+
+    /// Remove files from a PAK archive.
+    Remove {
+        #[structopt(name = "PAK", parse(from_os_str))]
+        pak: PathBuf,
+
+        #[structopt(name = "FILES", required = true)]
+        files: Vec<String>,
+    },
+
+    /// Check a PAK archive for overlapping or out-of-range files.
+    Verify {
+        #[structopt(name = "PAK", parse(from_os_str))]
+        pak: PathBuf,
+    },
+}
+
+const VERSION: &'static str = "
+pak 0.1
+Copyright © 2020 Cormac O'Brien
+Released under the terms of the MIT License
+";
+
+fn open_pak(path: &Path) -> Result<Pak, String> {
+    Pak::new(path).map_err(|why| format!("Couldn't open {}: {}", path.display(), why))
+}
+
+// writes to a temporary file first so a failed write doesn't destroy an existing archive
+fn write_pak(writer: &PakWriter, path: &Path) -> Result<(), String> {
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_name);
+
+    let file = File::create(&tmp_path)
+        .map_err(|why| format!("Couldn't create {}: {}", tmp_path.display(), why))?;
+    if let Err(why) = writer.write_to(BufWriter::new(file)) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(format!("Couldn't write {}: {}", path.display(), why));
+    }
+
+    fs::rename(&tmp_path, path).map_err(|why| format!("Couldn't write {}: {}", path.display(), why))
+}
+
+// returns the archive path of `path` relative to `root`, with forward slashes
+fn pak_path(root: &Path, path: &Path) -> Result<String, String> {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let mut components = Vec::new();
+    for c in relative.components() {
+        match c {
+            Component::Normal(s) => match s.to_str() {
+                Some(s) => components.push(s),
+                None => return Err(format!("Non-UTF-8 file name: {}", path.display())),
+            },
+            Component::CurDir => (),
+            _ => return Err(format!("Path leaves the archive root: {}", path.display())),
+        }
+    }
+
+    Ok(components.join("/"))
+}
+
+// collects the files under `dir`, sorted so archives are reproducible
+fn walk_dir(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let read_dir =
+        fs::read_dir(dir).map_err(|why| format!("Couldn't read {}: {}", dir.display(), why))?;
+
+    let mut paths: Vec<PathBuf> = read_dir.filter_map(Result::ok).map(|e| e.path()).collect();
+    paths.sort();
+
+    for path in paths {
+        if path.is_dir() {
+            walk_dir(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn add_file(writer: &mut PakWriter, root: &Path, path: &Path) -> Result<(), String> {
+    let name = pak_path(root, path)?;
+    let data =
+        fs::read(path).map_err(|why| format!("Couldn't read {}: {}", path.display(), why))?;
+    writer
+        .add(&name, data)
+        .map_err(|why| format!("Couldn't add {}: {}", name, why))
+}
+
+fn create(input_dir: &Path, output_pak: &Path) -> Result<(), String> {
+    let mut files = Vec::new();
+    walk_dir(input_dir, &mut files)?;
+
+    let mut writer = PakWriter::new();
+    for path in files.iter() {
+        add_file(&mut writer, input_dir, path)?;
+    }
+
+    write_pak(&writer, output_pak)?;
+    println!("Wrote {} files to {}", files.len(), output_pak.display());
+    Ok(())
+}
+
+fn list(path: &Path) -> Result<(), String> {
+    let pak = open_pak(path)?;
+    for (name, entry) in pak.iter() {
+        println!("{:>10} {:>10} {}", entry.size(), entry.offset(), name);
+    }
+
+    Ok(())
+}
+
+fn add(dir: &Path, path: &Path, files: &[String]) -> Result<(), String> {
+    let mut writer = if path.exists() {
+        let pak = open_pak(path)?;
+        PakWriter::from_pak(&pak)
+            .map_err(|why| format!("Couldn't read {}: {}", path.display(), why))?
+    } else {
+        PakWriter::new()
+    };
+
+    for file in files {
+        add_file(&mut writer, dir, &dir.join(file))?;
+    }
+
+    write_pak(&writer, path)
+}
+
+fn remove(path: &Path, files: &[String]) -> Result<(), String> {
+    let pak = open_pak(path)?;
+    let mut writer = PakWriter::from_pak(&pak)
+        .map_err(|why| format!("Couldn't read {}: {}", path.display(), why))?;
+    drop(pak);
+
+    for file in files {
+        if !writer.remove(file) {
+            return Err(format!("No such file in {}: {}", path.display(), file));
+        }
+    }
+
+    write_pak(&writer, path)
+}
+
+fn verify(path: &Path) -> Result<(), String> {
+    let problems =
+        Pak::verify_path(path).map_err(|e| format!("Couldn't verify {}: {}", path.display(), e))?;
+    for problem in problems.iter() {
+        println!("{}", problem);
+    }
+
+    if !problems.is_empty() {
+        return Err(format!(
+            "{}: {} problems found",
+            path.display(),
+            problems.len()
+        ));
+    }
+
+    let pak = open_pak(path)?;
+    println!("{}: {} files OK", path.display(), pak.iter().count());
+    Ok(())
+}
+
+fn main() {
+    let opt = Opt::from_args();
+
+    if opt.version {
+        println!("{}", VERSION);
+        exit(0);
+    }
+
+    let result = match opt.cmd {
+        Some(Cmd::Create {
+            input_dir,
+            output_pak,
+        }) => create(&input_dir, &output_pak),
+        Some(Cmd::List { pak }) => list(&pak),
+        Some(Cmd::Add { dir, pak, files }) => add(&dir, &pak, &files),
+        Some(Cmd::Remove { pak, files }) => remove(&pak, &files),
+        Some(Cmd::Verify { pak }) => verify(&pak),
+        None => {
+            Opt::clap().print_help().unwrap();
+            println!();
+            exit(1);
+        }
+    };
+
+    if let Err(why) = result {
+        println!("{}", why);
+        exit(1);
+    }
+}
@@ -6,9 +6,10 @@ use std::{
     rc::Rc,
 };
 
-use richter::client::render::Extent2d;
+use richter::client::{render::Extent2d, sound::capture::SoundCapture};
 
-use chrono::Utc;
+use chrono::{Duration, Utc};
+use failure::Error;
 
 const BYTES_PER_PIXEL: u32 = 4;
 
@@ -16,7 +17,9 @@ const BYTES_PER_PIXEL: u32 = 4;
 ///
 /// This function returns a boxed closure which sets the `screenshot_path`
 /// argument to `Some` when called.
-pub fn cmd_screenshot(screenshot_path: Rc<RefCell<Option<PathBuf>>>) -> Box<dyn Fn(&[&str])> {
+pub fn cmd_screenshot(
+    screenshot_path: Rc<RefCell<Option<PathBuf>>>,
+) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
     Box::new(move |args| {
         let path = match args.len() {
             // TODO: make default path configurable
@@ -24,14 +27,109 @@ pub fn cmd_screenshot(screenshot_path: Rc<RefCell<Option<PathBuf>>>) -> Box<dyn
             1 => PathBuf::from(args[0]),
             _ => {
                 log::error!("Usage: screenshot [PATH]");
-                return;
+                return Ok(());
             }
         };
 
         screenshot_path.replace(Some(PathBuf::from(path)));
+        Ok(())
     })
 }
 
+/// Offline capture of a demo to a sequence of PNG frames and a WAV file.
+///
+/// While a capture is active, the demo is stepped at a fixed timestep rather than in real time so
+/// that every frame is rendered regardless of how long it takes.
+pub struct DemoCapture {
+    dir: PathBuf,
+    frame_duration: Duration,
+    frame_count: usize,
+
+    // frames stepped without being rendered, which are filled in by the next rendered frame
+    skipped_frames: usize,
+
+    sound: Rc<SoundCapture>,
+}
+
+impl DemoCapture {
+    /// Begins a capture to `dir` at `fps` frames per second, creating `dir` if necessary.
+    pub fn new<P>(dir: P, fps: u32) -> std::io::Result<DemoCapture>
+    where
+        P: AsRef<Path>,
+    {
+        std::fs::create_dir_all(dir.as_ref())?;
+
+        Ok(DemoCapture {
+            dir: dir.as_ref().to_owned(),
+            frame_duration: Duration::microseconds(1_000_000 / fps.max(1) as i64),
+            frame_count: 0,
+            skipped_frames: 0,
+            sound: Rc::new(SoundCapture::new()),
+        })
+    }
+
+    /// The simulated duration of each captured frame.
+    pub fn frame_duration(&self) -> Duration {
+        self.frame_duration
+    }
+
+    pub fn sound(&self) -> Rc<SoundCapture> {
+        self.sound.clone()
+    }
+
+    /// Returns the path to which the next frame should be written.
+    pub fn frame_path(&self) -> PathBuf {
+        self.dir.join(format!("frame_{:06}.png", self.frame_count))
+    }
+
+    /// Records that the current frame was written and mixes its audio.
+    ///
+    /// Any frames skipped since the last call are filled in with copies of this one.
+    pub fn advance(&mut self) {
+        let path = self.frame_path();
+        for frame_id in self.frame_count - self.skipped_frames..self.frame_count {
+            let copy_path = self.dir.join(format!("frame_{:06}.png", frame_id));
+            if let Err(e) = std::fs::copy(&path, &copy_path) {
+                log::error!("Couldn't write {}: {}", copy_path.display(), e);
+            }
+        }
+
+        self.skipped_frames = 0;
+        self.sound.mix(self.frame_duration);
+        self.frame_count += 1;
+    }
+
+    /// Records that the current frame couldn't be rendered (e.g. because the level is loading).
+    ///
+    /// The frame's audio is still mixed so that the video and audio stay in step with the demo.
+    pub fn skip(&mut self) {
+        self.skipped_frames += 1;
+        self.sound.mix(self.frame_duration);
+        self.frame_count += 1;
+    }
+
+    /// Ends the capture, writing the mixed audio to `audio.wav` in the capture directory.
+    pub fn finish(self) {
+        if self.skipped_frames > 0 {
+            log::warn!(
+                "Capture ended with {} frames that were never rendered",
+                self.skipped_frames
+            );
+        }
+
+        let wav_path = self.dir.join("audio.wav");
+        if let Err(e) = self.sound.write_wav(&wav_path) {
+            log::error!("Couldn't write {}: {}", wav_path.display(), e);
+        }
+
+        log::info!(
+            "Captured {} frames to {}",
+            self.frame_count - self.skipped_frames,
+            self.dir.display()
+        );
+    }
+}
+
 pub struct Capture {
     // size of the capture image
     capture_size: Extent2d,
@@ -0,0 +1,290 @@
+use std::{
+    cell::{Cell, RefCell},
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write as _},
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+};
+
+use richter::{
+    client::input::Input,
+    common::{
+        self,
+        console::{Console, CvarRegistry},
+        vfs::{Vfs, VfsEntry},
+    },
+};
+
+use failure::{bail, Error};
+
+/// The file to which archived cvars, bindings and aliases are saved.
+pub const CONFIG_FILE: &str = "config.cfg";
+
+// the command history is saved here, in the user's config directory
+const HISTORY_FILE: &str = "richter/history.txt";
+
+// executed at startup if quake.rc is missing
+const STARTUP_SCRIPTS: &[&str] = &["default.cfg", CONFIG_FILE, "autoexec.cfg"];
+
+/// Queues the startup scripts for execution.
+///
+/// If `quake.rc` exists, it is executed (and is expected to exec the other scripts itself).
+/// Otherwise `default.cfg`, `config.cfg` and `autoexec.cfg` are executed in that order.
+pub fn exec_startup_scripts(vfs: &Vfs, console: &Console) {
+    if vfs.open("quake.rc").is_ok() {
+        console.stuff_text("exec quake.rc\n");
+        return;
+    }
+
+    for script in STARTUP_SCRIPTS {
+        if vfs.open(script).is_ok() {
+            console.stuff_text(format!("exec {}\n", script));
+        }
+    }
+}
+
+/// Writes archived cvars, key bindings and aliases to `config.cfg` in `game_dir`.
+pub fn host_writeconfig<P>(
+    game_dir: P,
+    cvars: &CvarRegistry,
+    input: &Input,
+    console: &Console,
+) -> io::Result<()>
+where
+    P: AsRef<Path>,
+{
+    let path = game_dir.as_ref().join(CONFIG_FILE);
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    writeln!(writer, "// generated by richter, do not modify")?;
+    input.write_bindings(&mut writer)?;
+    console.write_aliases(&mut writer)?;
+    cvars.write_archived(&mut writer)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Returns the path of the command history file, if the user has a config directory.
+pub fn history_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(HISTORY_FILE))
+}
+
+/// Loads the command history saved by a previous session.
+pub fn load_history(console: &mut Console) -> io::Result<()> {
+    let path = match history_path() {
+        Some(p) => p,
+        None => return Ok(()),
+    };
+
+    match File::open(path) {
+        Ok(f) => console.read_history(BufReader::new(f)),
+
+        // there's no history on the first run
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Saves the command history for the next session.
+pub fn save_history(console: &Console) -> io::Result<()> {
+    let path = match history_path() {
+        Some(p) => p,
+        None => return Ok(()),
+    };
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    console.write_history(&mut writer)?;
+    writer.flush()
+}
+
+/// Implements the `writeconfig` command.
+pub fn cmd_writeconfig(
+    vfs: Rc<Vfs>,
+    cvars: Rc<RefCell<CvarRegistry>>,
+    input: Rc<RefCell<Input>>,
+    console: Rc<RefCell<Console>>,
+) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |_| {
+        match host_writeconfig(
+            vfs.game_dir(),
+            &cvars.borrow(),
+            &input.borrow(),
+            &console.borrow(),
+        ) {
+            Ok(()) => console.borrow().print(format!("Wrote {}\n", CONFIG_FILE)),
+            Err(e) => bail!("Couldn't write {}: {}", CONFIG_FILE, e),
+        }
+
+        Ok(())
+    })
+}
+
+/// Implements the `condump` command.
+///
+/// Writes the console scrollback to a text file in the game directory.
+pub fn cmd_condump(
+    vfs: Rc<Vfs>,
+    console: Rc<RefCell<Console>>,
+) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |args| {
+        let name = match args.len() {
+            0 => "condump.txt",
+            1 => args[0],
+            _ => bail!("condump [filename] : write the console scrollback to a file"),
+        };
+
+        // don't allow writing outside the game directory
+        if !Path::new(name)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            bail!("condump: invalid filename \"{}\"", name);
+        }
+
+        let path = vfs.game_dir().join(name);
+        let result = File::create(&path).and_then(|f| {
+            let mut writer = BufWriter::new(f);
+            console.borrow().output().write_to(&mut writer)?;
+            writer.flush()
+        });
+
+        match result {
+            Ok(()) => console
+                .borrow()
+                .print(format!("Dumped console text to {}\n", path.display())),
+            Err(e) => bail!("Couldn't write {}: {}", path.display(), e),
+        }
+
+        Ok(())
+    })
+}
+
+/// Implements the `game` command.
+///
+/// Saves the configuration of the current game, layers the given mod over the base game and runs
+/// the new game's startup scripts. With no arguments, prints the current game.
+///
+/// `game_changed` is set when the game is switched so that the program can end the current game
+/// and reload anything it has cached from the previous game's files.
+pub fn cmd_game(
+    vfs: Rc<Vfs>,
+    cvars: Rc<RefCell<CvarRegistry>>,
+    input: Rc<RefCell<Input>>,
+    console: Rc<RefCell<Console>>,
+    game_changed: Rc<Cell<bool>>,
+) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |args| {
+        let game = match args.len() {
+            0 => {
+                let game = vfs.game();
+                let game = game.as_deref().unwrap_or(common::DEFAULT_BASEDIR);
+                console
+                    .borrow()
+                    .print(format!("\"game\" is \"{}\"\n", game));
+                return Ok(());
+            }
+            1 => args[0],
+            _ => bail!("game <directory> : switch to a mod directory"),
+        };
+
+        let current = vfs.game();
+        if current.as_deref().unwrap_or(common::DEFAULT_BASEDIR) == game {
+            return Ok(());
+        }
+
+        if let Err(e) = host_writeconfig(
+            vfs.game_dir(),
+            &cvars.borrow(),
+            &input.borrow(),
+            &console.borrow(),
+        ) {
+            log::error!("Couldn't write {}: {}", CONFIG_FILE, e);
+        }
+
+        if let Err(e) = vfs.set_game(Some(game)) {
+            bail!("game: {}", e);
+        }
+
+        game_changed.set(true);
+        console
+            .borrow()
+            .print(format!("Switched to game \"{}\"\n", game));
+        exec_startup_scripts(&vfs, &console.borrow());
+
+        Ok(())
+    })
+}
+
+/// Implements the `path` command.
+///
+/// Prints the virtual filesystem's search path, with the first component searched first.
+pub fn cmd_path(
+    vfs: Rc<Vfs>,
+    console: Rc<RefCell<Console>>,
+) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |_| {
+        let console = console.borrow();
+        console.print("Current search path:\n");
+        for component in vfs.search_path() {
+            console.print(format!("{}\n", component));
+        }
+
+        Ok(())
+    })
+}
+
+// prints entries found by the `dir` and `find` commands
+fn print_entries(console: &Console, entries: &[VfsEntry]) {
+    for entry in entries {
+        console.print(format!("{:>10} {}\n", entry.size, entry.path));
+    }
+
+    console.print(format!("{} files\n", entries.len()));
+}
+
+/// Implements the `dir` command.
+///
+/// Lists the files in a directory of the virtual filesystem, including its subdirectories.
+pub fn cmd_dir(
+    vfs: Rc<Vfs>,
+    console: Rc<RefCell<Console>>,
+) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |args| {
+        let prefix = match args.len() {
+            0 => String::new(),
+            1 => match args[0].trim_matches('/') {
+                "" => String::new(),
+                dir => format!("{}/", dir),
+            },
+            _ => bail!("dir [directory] : list the files in a directory"),
+        };
+
+        print_entries(&console.borrow(), &vfs.list(prefix));
+
+        Ok(())
+    })
+}
+
+/// Implements the `find` command.
+///
+/// Lists the files in the virtual filesystem matching a glob pattern.
+pub fn cmd_find(
+    vfs: Rc<Vfs>,
+    console: Rc<RefCell<Console>>,
+) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |args| {
+        let pattern = match args.len() {
+            1 => args[0],
+            _ => bail!("find <pattern> : list the files matching a pattern, e.g. maps/*.bsp"),
+        };
+
+        print_entries(&console.borrow(), &vfs.glob(pattern));
+
+        Ok(())
+    })
+}
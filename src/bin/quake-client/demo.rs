@@ -0,0 +1,132 @@
+use std::{cell::RefCell, rc::Rc};
+
+use richter::common::console::Console;
+
+use failure::{bail, Error};
+
+/// The maximum number of demos that can be listed with `startdemos`.
+const MAX_DEMOS: usize = 8;
+
+/// A change in demo playback requested from the console.
+pub enum DemoRequest {
+    /// Play the named demo (`playdemo`).
+    Play(String),
+
+    /// Start the demo loop if nothing else is running (`startdemos`).
+    StartLoop,
+
+    /// Restart the demo loop, interrupting any current game or demo (`demos`).
+    RestartLoop,
+
+    /// Stop demo playback (`stopdemo`).
+    Stop,
+}
+
+/// The attract-mode demo loop.
+pub struct DemoQueue {
+    demos: Vec<String>,
+
+    // index of the next demo to play, or None if the loop is stopped
+    next: Option<usize>,
+
+    request: Option<DemoRequest>,
+}
+
+impl DemoQueue {
+    pub fn new() -> DemoQueue {
+        DemoQueue {
+            demos: Vec::new(),
+            next: None,
+            request: None,
+        }
+    }
+
+    pub fn take_request(&mut self) -> Option<DemoRequest> {
+        self.request.take()
+    }
+
+    /// Moves the loop back to the given demo, or stops it if `next` is `None`.
+    pub fn set_next(&mut self, next: Option<usize>) {
+        self.next = next;
+    }
+
+    pub fn next(&self) -> Option<usize> {
+        self.next
+    }
+
+    /// Returns the path of the next demo in the loop and advances the loop.
+    ///
+    /// Returns `None` if the loop is stopped or no demos were listed.
+    pub fn next_demo(&mut self) -> Option<String> {
+        let next = self.next?;
+
+        if self.demos.is_empty() {
+            log::warn!("No demos listed with startdemos");
+            self.next = None;
+            return None;
+        }
+
+        let id = next % self.demos.len();
+        self.next = Some(id + 1);
+        Some(demo_path(&self.demos[id]))
+    }
+}
+
+/// Appends the `.dem` extension to `name` if it has none.
+fn demo_path(name: &str) -> String {
+    match name.rsplit('/').next() {
+        Some(file) if file.contains('.') => name.to_owned(),
+        _ => format!("{}.dem", name),
+    }
+}
+
+/// Implements the `playdemo` command.
+pub fn cmd_playdemo(queue: Rc<RefCell<DemoQueue>>) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |args| {
+        if args.len() != 1 {
+            bail!("playdemo <demoname> : plays a demo");
+        }
+
+        queue.borrow_mut().request = Some(DemoRequest::Play(demo_path(args[0])));
+        Ok(())
+    })
+}
+
+/// Implements the `startdemos` command.
+pub fn cmd_startdemos(
+    queue: Rc<RefCell<DemoQueue>>,
+    console: Rc<RefCell<Console>>,
+) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |args| {
+        if args.is_empty() {
+            bail!("usage: startdemos <demoname1> <demoname2> ...");
+        }
+
+        let console = console.borrow();
+        if args.len() > MAX_DEMOS {
+            console.print(format!("Max {} demos in demoloop\n", MAX_DEMOS));
+        }
+
+        let mut queue = queue.borrow_mut();
+        queue.demos = args.iter().take(MAX_DEMOS).map(|s| s.to_string()).collect();
+        console.print(format!("{} demo(s) in loop\n", queue.demos.len()));
+        queue.request = Some(DemoRequest::StartLoop);
+        Ok(())
+    })
+}
+
+/// Implements the `demos` command.
+pub fn cmd_demos(queue: Rc<RefCell<DemoQueue>>) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |_| {
+        queue.borrow_mut().request = Some(DemoRequest::RestartLoop);
+        Ok(())
+    })
+}
+
+/// Implements the `stopdemo` command.
+pub fn cmd_stopdemo(queue: Rc<RefCell<DemoQueue>>) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |_| {
+        queue.borrow_mut().request = Some(DemoRequest::Stop);
+        Ok(())
+    })
+}
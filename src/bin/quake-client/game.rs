@@ -39,6 +39,7 @@ use richter::{
             PointLight, PostProcessRenderer, RenderTarget as _, RenderTargetResolve as _,
             SwapChainTarget, UiOverlay, UiRenderer, UiState, WorldRenderer,
         },
+        sound::capture::SoundCapture,
         trace::TraceFrame,
         Client,
     },
@@ -87,18 +88,22 @@ impl InGameState {
 
         cmds.borrow_mut().insert_or_replace(
             "toggleconsole",
-            Box::new(move |_| match toggleconsole_focus.get() {
-                InGameFocus::Game => {
-                    println!("toggleconsole: ON");
-                    toggleconsole_focus.set(InGameFocus::Console);
-                }
+            Box::new(move |_| {
+                match toggleconsole_focus.get() {
+                    InGameFocus::Game => {
+                        println!("toggleconsole: ON");
+                        toggleconsole_focus.set(InGameFocus::Console);
+                    }
+
+                    InGameFocus::Console => {
+                        println!("toggleconsole: OFF");
+                        toggleconsole_focus.set(InGameFocus::Game);
+                    }
 
-                InGameFocus::Console => {
-                    println!("toggleconsole: OFF");
-                    toggleconsole_focus.set(InGameFocus::Game);
+                    InGameFocus::Menu => (),
                 }
 
-                InGameFocus::Menu => (),
+                Ok(())
             }),
         );
 
@@ -106,16 +111,20 @@ impl InGameState {
 
         cmds.borrow_mut().insert_or_replace(
             "togglemenu",
-            Box::new(move |_| match togglemenu_focus.get() {
-                InGameFocus::Game => {
-                    println!("togglemenu: ON");
-                    togglemenu_focus.set(InGameFocus::Menu);
-                }
+            Box::new(move |_| {
+                match togglemenu_focus.get() {
+                    InGameFocus::Game => {
+                        println!("togglemenu: ON");
+                        togglemenu_focus.set(InGameFocus::Menu);
+                    }
 
-                InGameFocus::Menu | InGameFocus::Console => {
-                    println!("togglemenu: OFF");
-                    togglemenu_focus.set(InGameFocus::Game);
+                    InGameFocus::Menu | InGameFocus::Console => {
+                        println!("togglemenu: OFF");
+                        togglemenu_focus.set(InGameFocus::Game);
+                    }
                 }
+
+                Ok(())
             }),
         );
 
@@ -197,6 +206,53 @@ impl Game {
         })
     }
 
+    /// Returns `true` if this game is playing back a demo.
+    pub fn demo_playback(&self) -> bool {
+        self.client.demo_playback()
+    }
+
+    /// Returns `true` if this game is playing back a demo and has reached the end of it.
+    pub fn demo_finished(&self) -> bool {
+        self.client.demo_finished()
+    }
+
+    /// Records all game audio to `capture`, or stops recording if `None`.
+    pub fn set_sound_capture(&mut self, capture: Option<Rc<SoundCapture>>) {
+        self.client.set_sound_capture(capture);
+    }
+
+    /// Saves the next rendered frame to `path`.
+    pub fn screenshot(&self, path: PathBuf) {
+        self.screenshot_path.replace(Some(path));
+    }
+
+    /// Returns `true` if a screenshot has been requested but not yet taken.
+    ///
+    /// Screenshots are not taken while the level is loading.
+    pub fn screenshot_pending(&self) -> bool {
+        self.screenshot_path.borrow().is_some()
+    }
+
+    /// Replaces the UI renderer, e.g. after its textures have been reloaded.
+    pub fn set_ui_renderer(&mut self, ui_renderer: Rc<UiRenderer>) {
+        self.ui_renderer = ui_renderer;
+    }
+
+    /// Reloads models and sounds changed on disk and rebuilds the renderers of reloaded models.
+    pub fn reload_assets(&mut self, gfx_state: &GraphicsState, paths: &[String]) {
+        let model_ids = self.client.reload_assets(paths);
+
+        if let GameState::InGame(ref mut state) = self.state {
+            if let Some(models) = self.client.models() {
+                for id in model_ids {
+                    state
+                        .world_renderer
+                        .reload_model(gfx_state, id, &models[id]);
+                }
+            }
+        }
+    }
+
     // advance the simulation
     pub fn frame(&mut self, gfx_state: &GraphicsState, frame_duration: Duration) {
         self.client.frame(frame_duration).unwrap();
@@ -452,6 +508,7 @@ impl Game {
 
 impl std::ops::Drop for Game {
     fn drop(&mut self) {
+        let _ = self.cmds.borrow_mut().remove("screenshot");
         let _ = self.cmds.borrow_mut().remove("trace_begin");
         let _ = self.cmds.borrow_mut().remove("trace_end");
     }
@@ -19,17 +19,22 @@
 // SOFTWARE.
 
 mod capture;
+mod config;
+mod demo;
 mod game;
 mod menu;
+mod rcon;
 mod trace;
 
 use std::{
     cell::{Cell, Ref, RefCell, RefMut},
     net::{SocketAddr, ToSocketAddrs},
-    path::Path,
+    path::{Path, PathBuf},
     rc::Rc,
 };
 
+use capture::DemoCapture;
+use demo::{DemoQueue, DemoRequest};
 use game::Game;
 
 use chrono::Duration;
@@ -38,14 +43,17 @@ use richter::{
         self,
         input::{Input, InputFocus},
         menu::Menu,
-        render::{self, Extent2d, GraphicsState, UiRenderer, DIFFUSE_ATTACHMENT_FORMAT},
+        render::{
+            self, Extent2d, GraphicsState, RenderTarget as _, SwapChainTarget, UiOverlay,
+            UiRenderer, UiState, DIFFUSE_ATTACHMENT_FORMAT,
+        },
         Client,
     },
     common::{
-        self,
-        console::{CmdRegistry, Console, CvarRegistry},
+        console::{CmdRegistry, Console, ConsoleLog, CvarRegistry},
         host::{Host, Program},
         vfs::Vfs,
+        wad::QPic,
     },
 };
 use structopt::StructOpt;
@@ -55,6 +63,7 @@ use winit::{
     window::{Window, WindowBuilder},
 };
 
+#[derive(Clone, Copy)]
 enum TitleState {
     Menu,
     Console,
@@ -72,9 +81,15 @@ struct ClientProgram {
     console: Rc<RefCell<Console>>,
     menu: Rc<RefCell<Menu>>,
 
+    // log records are printed to the console each frame
+    log: ConsoleLog,
+
     window: Window,
     window_dimensions_changed: Cell<bool>,
 
+    // updated when r_msaa_samples changes
+    msaa_samples: Rc<Cell<u32>>,
+
     instance: wgpu::Instance,
     surface: wgpu::Surface,
     adapter: wgpu::Adapter,
@@ -85,30 +100,34 @@ struct ClientProgram {
     audio_device: Rc<rodio::Device>,
 
     state: RefCell<ProgramState>,
+    title_state: Rc<Cell<TitleState>>,
+    demo_queue: Rc<RefCell<DemoQueue>>,
+
+    // if Some, the current demo is being captured to disk
+    demo_capture: Option<DemoCapture>,
+
     input: Rc<RefCell<Input>>,
+    uptime: Duration,
+
+    // uptime at which the Vfs was last polled for changed assets (see cl_hotreload)
+    asset_poll_time: Duration,
+
+    // changed assets waiting for the next poll, so files still being written aren't loaded
+    pending_reloads: Vec<String>,
+
+    // set by the `game` command when the mod directory changes
+    game_changed: Rc<Cell<bool>>,
 }
 
 impl ClientProgram {
-    pub async fn new(window: Window, audio_device: rodio::Device, trace: bool) -> ClientProgram {
-        let mut vfs = Vfs::new();
-
-        // add basedir first
-        vfs.add_directory(common::DEFAULT_BASEDIR).unwrap();
-
-        // then add PAK archives
-        for vfs_id in 0..common::MAX_PAKFILES {
-            // TODO: check `-basedir` command line argument
-            let basedir = common::DEFAULT_BASEDIR;
-            let path_string = format!("{}/pak{}.pak", basedir, vfs_id);
-            let path = Path::new(&path_string);
-
-            // keep adding PAKs until we don't find one or we hit MAX_PAKFILES
-            if !path.exists() {
-                break;
-            }
-
-            vfs.add_pakfile(path).unwrap();
-        }
+    pub async fn new(
+        window: Window,
+        audio_device: rodio::Device,
+        vfs: Vfs,
+        log: ConsoleLog,
+        trace: bool,
+    ) -> ClientProgram {
+        let vfs = Rc::new(vfs);
 
         let cvars = Rc::new(RefCell::new(CvarRegistry::new()));
         client::register_cvars(&cvars.borrow()).unwrap();
@@ -117,8 +136,30 @@ impl ClientProgram {
         let cmds = Rc::new(RefCell::new(CmdRegistry::new()));
         // TODO: register commands as other subsystems come online
 
-        let console = Rc::new(RefCell::new(Console::new(cmds.clone(), cvars.clone())));
-        let menu = Rc::new(RefCell::new(menu::build_main_menu().unwrap()));
+        let console = Rc::new(RefCell::new(Console::new(
+            cmds.clone(),
+            cvars.clone(),
+            vfs.clone(),
+        )));
+        let menu = Rc::new(RefCell::new(
+            menu::build_main_menu(console.clone(), vfs.clone()).unwrap(),
+        ));
+
+        let demo_queue = Rc::new(RefCell::new(DemoQueue::new()));
+        {
+            let mut cmds = cmds.borrow_mut();
+            cmds.insert("playdemo", demo::cmd_playdemo(demo_queue.clone()))
+                .unwrap();
+            cmds.insert(
+                "startdemos",
+                demo::cmd_startdemos(demo_queue.clone(), console.clone()),
+            )
+            .unwrap();
+            cmds.insert("demos", demo::cmd_demos(demo_queue.clone()))
+                .unwrap();
+            cmds.insert("stopdemo", demo::cmd_stopdemo(demo_queue.clone()))
+                .unwrap();
+        }
 
         let input = Rc::new(RefCell::new(Input::new(
             InputFocus::Game,
@@ -171,28 +212,101 @@ impl ClientProgram {
             },
         ));
 
-        let vfs = Rc::new(vfs);
+        // r_msaa_samples only accepts supported sample counts, so no need to check it here
+        let sample_count = cvars.borrow().get_value("r_msaa_samples").unwrap() as u32;
+        let msaa_samples = Rc::new(Cell::new(sample_count));
+        // the callback can run while the renderer is borrowed, so the pipelines and attachments are
+        // rebuilt by GraphicsState::update at the start of the next frame instead
+        let msaa_samples_cb = msaa_samples.clone();
+        cvars
+            .borrow()
+            .on_change(
+                "r_msaa_samples",
+                Box::new(move |value| msaa_samples_cb.set(value.parse().unwrap())),
+            )
+            .unwrap();
+
+        let developer_log = log.clone();
+        cvars
+            .borrow()
+            .on_change(
+                "developer",
+                Box::new(move |value| developer_log.set_developer(value.parse().unwrap())),
+            )
+            .unwrap();
 
-        // TODO: warn user if r_msaa_samples is invalid
-        let mut sample_count = cvars.borrow().get_value("r_msaa_samples").unwrap_or(2.0) as u32;
-        if !&[2, 4].contains(&sample_count) {
-            sample_count = 2;
+        // complete file arguments from the virtual filesystem
+        {
+            let mut cmds = cmds.borrow_mut();
+            cmds.insert_completer("playdemo", cmd_complete_files(vfs.clone(), "", "dem"));
+            cmds.insert_completer("map", cmd_complete_files(vfs.clone(), "maps/", "bsp"));
+            let exec_vfs = vfs.clone();
+            cmds.insert_completer(
+                "exec",
+                Box::new(move |arg_id| match arg_id {
+                    0 => exec_vfs
+                        .glob("**.cfg")
+                        .into_iter()
+                        .map(|e| e.path)
+                        .collect(),
+                    _ => Vec::new(),
+                }),
+            );
         }
 
         let gfx_state = GraphicsState::new(device, queue, size, sample_count, vfs.clone()).unwrap();
         let ui_renderer = Rc::new(UiRenderer::new(&gfx_state, &menu.borrow()));
+        let game_changed = Rc::new(Cell::new(false));
+
+        // script commands must exist before any scripts are executed
+        {
+            let mut cmds = cmds.borrow_mut();
+            cmds.insert(
+                "writeconfig",
+                config::cmd_writeconfig(vfs.clone(), cvars.clone(), input.clone(), console.clone()),
+            )
+            .unwrap();
+            cmds.insert("condump", config::cmd_condump(vfs.clone(), console.clone()))
+                .unwrap();
+            cmds.insert(
+                "game",
+                config::cmd_game(
+                    vfs.clone(),
+                    cvars.clone(),
+                    input.clone(),
+                    console.clone(),
+                    game_changed.clone(),
+                ),
+            )
+            .unwrap();
+            cmds.insert("path", config::cmd_path(vfs.clone(), console.clone()))
+                .unwrap();
+            cmds.insert("dir", config::cmd_dir(vfs.clone(), console.clone()))
+                .unwrap();
+            cmds.insert("find", config::cmd_find(vfs.clone(), console.clone()))
+                .unwrap();
+            cmds.insert("rcon", rcon::cmd_rcon(cvars.clone(), console.clone()))
+                .unwrap();
+            input.borrow().register_cmds(&mut cmds);
+        }
+
+        if let Err(e) = config::load_history(&mut console.borrow_mut()) {
+            log::warn!("Couldn't load command history: {}", e);
+        }
 
         // this will also execute config.cfg and autoexec.cfg (assuming an unmodified quake.rc)
-        console.borrow().stuff_text("exec quake.rc\n");
+        config::exec_startup_scripts(&vfs, &console.borrow());
 
-        ClientProgram {
+        let program = ClientProgram {
             vfs,
             cvars,
             cmds,
             console,
             menu,
+            log,
             window,
             window_dimensions_changed: Cell::new(false),
+            msaa_samples,
             instance,
             surface,
             adapter,
@@ -201,7 +315,38 @@ impl ClientProgram {
             ui_renderer,
             audio_device: Rc::new(audio_device),
             state: RefCell::new(ProgramState::Title),
+            title_state: Rc::new(Cell::new(TitleState::Console)),
+            demo_queue,
+            demo_capture: None,
             input,
+            uptime: Duration::zero(),
+            asset_poll_time: Duration::zero(),
+            pending_reloads: Vec::new(),
+            game_changed,
+        };
+
+        program.enter_title();
+        program
+    }
+
+    /// Returns to the title screen, ending any game or demo in progress.
+    fn enter_title(&self) {
+        self.state.replace(ProgramState::Title);
+        self.title_state.set(TitleState::Console);
+
+        // the title screen only switches between the console and the menu
+        for name in &["toggleconsole", "togglemenu"] {
+            let title_state = self.title_state.clone();
+            self.cmds.borrow_mut().insert_or_replace(
+                name,
+                Box::new(move |_| {
+                    match title_state.get() {
+                        TitleState::Menu => title_state.set(TitleState::Console),
+                        TitleState::Console => title_state.set(TitleState::Menu),
+                    }
+                    Ok(())
+                }),
+            );
         }
     }
 
@@ -215,7 +360,7 @@ impl ClientProgram {
             self.cvars.clone(),
             self.cmds.clone(),
             self.console.clone(),
-            self.audio_device.clone(),
+            Some(self.audio_device.clone()),
         )
         .unwrap();
 
@@ -237,28 +382,203 @@ impl ClientProgram {
     where
         S: AsRef<str>,
     {
-        let cl = Client::play_demo(
-            demo_path,
+        // shut down the current game first so its commands are unregistered
+        self.state.replace(ProgramState::Title);
+
+        let cl = match Client::play_demo(
+            demo_path.as_ref(),
             self.vfs.clone(),
             self.cvars.clone(),
             self.cmds.clone(),
             self.console.clone(),
-            self.audio_device.clone(),
+            Some(self.audio_device.clone()),
+        ) {
+            Ok(cl) => cl,
+            Err(e) => {
+                log::error!("Couldn't play demo {}: {}", demo_path.as_ref(), e);
+                self.demo_queue.borrow_mut().set_next(None);
+                self.enter_title();
+                return;
+            }
+        };
+
+        cl.register_cmds(&mut self.cmds.borrow_mut());
+
+        let mut game = Game::new(
+            self.cvars.clone(),
+            self.cmds.clone(),
+            self.ui_renderer.clone(),
+            self.input.clone(),
+            cl,
         )
         .unwrap();
 
-        cl.register_cmds(&mut self.cmds.borrow_mut());
+        if let Some(ref capture) = self.demo_capture {
+            game.set_sound_capture(Some(capture.sound()));
+        }
 
-        self.state.replace(ProgramState::Game(
-            Game::new(
-                self.cvars.clone(),
-                self.cmds.clone(),
-                self.ui_renderer.clone(),
-                self.input.clone(),
-                cl,
-            )
-            .unwrap(),
+        self.state.replace(ProgramState::Game(game));
+    }
+
+    /// Plays a demo, saving each frame to `dir` as a PNG and the game audio as a WAV.
+    fn capture_demo<S, P>(&mut self, demo_path: S, dir: P, fps: u32)
+    where
+        S: AsRef<str>,
+        P: AsRef<Path>,
+    {
+        match DemoCapture::new(dir.as_ref(), fps) {
+            Ok(capture) => self.demo_capture = Some(capture),
+            Err(e) => {
+                log::error!("Couldn't create {}: {}", dir.as_ref().display(), e);
+                return;
+            }
+        }
+
+        self.play_demo(demo_path);
+    }
+
+    /// Plays the next demo in the demo loop, or returns to the title screen if the loop is stopped.
+    fn next_demo(&mut self) {
+        let next = self.demo_queue.borrow_mut().next_demo();
+        match next {
+            Some(path) => self.play_demo(path),
+            None => self.enter_title(),
+        }
+    }
+
+    /// Reloads assets changed on disk if `cl_hotreload` is set.
+    ///
+    /// Assets are reloaded once they've stopped changing for a whole polling interval.
+    fn poll_assets(&mut self) {
+        if self.cvars.borrow().get_value("cl_hotreload").unwrap() == 0.0
+            || self.uptime - self.asset_poll_time < Duration::seconds(1)
+        {
+            return;
+        }
+
+        self.asset_poll_time = self.uptime;
+        let changed = self.vfs.poll_changes();
+        let ready: Vec<String> = self
+            .pending_reloads
+            .drain(..)
+            .filter(|path| !changed.contains(path))
+            .collect();
+        self.pending_reloads = changed;
+
+        if ready.is_empty() {
+            return;
+        }
+
+        if let ProgramState::Game(ref mut game) = *self.state.borrow_mut() {
+            game.reload_assets(&self.gfx_state.borrow(), &ready);
+        }
+
+        if ready.iter().any(|path| is_ui_texture(path)) {
+            self.reload_ui_textures(&ready);
+        }
+    }
+
+    /// Rebuilds the UI renderer to pick up changed 2D graphics.
+    ///
+    /// Map textures are stored in the level's BSP file and are only reloaded with the level.
+    fn reload_ui_textures(&mut self, paths: &[String]) {
+        // the renderers expect their textures to load, so check the new files first
+        for path in paths.iter().filter(|path| is_ui_texture(path)) {
+            let result = if path.eq_ignore_ascii_case("gfx.wad") {
+                self.gfx_state.borrow_mut().reload_gfx_wad()
+            } else {
+                self.vfs
+                    .open(path)
+                    .map_err(failure::Error::from)
+                    .and_then(|file| QPic::load(file).map_err(failure::Error::from))
+                    .map(|_| ())
+            };
+
+            if let Err(e) = result {
+                log::warn!("Couldn't reload {}: {}", path, e);
+                return;
+            }
+        }
+
+        self.rebuild_ui_renderer();
+        log::info!("Reloaded UI textures");
+    }
+
+    // rebuilds the UI renderer from the current gfx.wad and 2D graphics
+    fn rebuild_ui_renderer(&mut self) {
+        self.ui_renderer = Rc::new(UiRenderer::new(
+            &self.gfx_state.borrow(),
+            &self.menu.borrow(),
         ));
+
+        if let ProgramState::Game(ref mut game) = *self.state.borrow_mut() {
+            game.set_ui_renderer(self.ui_renderer.clone());
+        }
+    }
+
+    /// Ends the current game and reloads assets cached from the previous game's files.
+    ///
+    /// Models and sounds belong to the game and are dropped with it. The demo list is rebuilt
+    /// whenever its menu is opened.
+    fn change_game(&mut self) {
+        if let Some(capture) = self.demo_capture.take() {
+            capture.finish();
+        }
+
+        self.demo_queue.borrow_mut().set_next(None);
+        self.enter_title();
+        self.pending_reloads.clear();
+
+        if let Err(e) = self.gfx_state.borrow_mut().reload_gfx_wad() {
+            log::error!("Couldn't reload gfx.wad: {}", e);
+        }
+
+        self.rebuild_ui_renderer();
+    }
+
+    /// Starts or stops demo playback as requested by console commands.
+    fn handle_demo_request(&mut self) {
+        let request = self.demo_queue.borrow_mut().take_request();
+        match request {
+            Some(DemoRequest::Play(path)) => self.play_demo(path),
+
+            Some(DemoRequest::StartLoop) => {
+                let idle = match *self.state.borrow() {
+                    ProgramState::Title => true,
+                    ProgramState::Game(_) => false,
+                };
+
+                // only start the loop if nothing else is running
+                if idle {
+                    self.demo_queue.borrow_mut().set_next(Some(0));
+                    self.next_demo();
+                } else {
+                    self.demo_queue.borrow_mut().set_next(None);
+                }
+            }
+
+            Some(DemoRequest::RestartLoop) => {
+                if self.demo_queue.borrow().next().is_none() {
+                    self.demo_queue.borrow_mut().set_next(Some(1));
+                }
+
+                self.next_demo();
+            }
+
+            Some(DemoRequest::Stop) => {
+                let playing = match *self.state.borrow() {
+                    ProgramState::Game(ref game) => game.demo_playback(),
+                    ProgramState::Title => false,
+                };
+
+                if playing {
+                    self.demo_queue.borrow_mut().set_next(None);
+                    self.enter_title();
+                }
+            }
+
+            None => (),
+        }
     }
 
     /// Builds a new swap chain with the specified present mode and the window's current dimensions.
@@ -281,7 +601,7 @@ impl ClientProgram {
         let swap_chain_output = self.swap_chain.borrow_mut().get_next_frame().unwrap();
 
         match *self.state.borrow_mut() {
-            ProgramState::Title => unimplemented!(),
+            ProgramState::Title => self.render_title(&swap_chain_output.output.view),
             ProgramState::Game(ref mut game) => {
                 let winit::dpi::PhysicalSize { width, height } = self.window.inner_size();
                 game.render(
@@ -297,6 +617,58 @@ impl ClientProgram {
     }
 }
 
+impl ClientProgram {
+    fn render_title(&self, color_attachment_view: &wgpu::TextureView) {
+        let gfx_state = self.gfx_state.borrow();
+        let console = self.console.borrow();
+        let menu = self.menu.borrow();
+        let winit::dpi::PhysicalSize { width, height } = self.window.inner_size();
+
+        let ui_state = UiState::Title {
+            overlay: match self.title_state.get() {
+                TitleState::Menu => UiOverlay::Menu(&menu),
+                TitleState::Console => UiOverlay::Console(&console),
+            },
+        };
+
+        let mut encoder = gfx_state
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        // final render pass
+        {
+            // quad_commands must outlive final pass
+            let mut quad_commands = Vec::new();
+            let mut glyph_commands = Vec::new();
+
+            let final_pass_builder = gfx_state.final_pass_target().render_pass_builder();
+            let mut final_pass = encoder.begin_render_pass(&final_pass_builder.descriptor());
+
+            self.ui_renderer.render_pass(
+                &gfx_state,
+                &mut final_pass,
+                Extent2d { width, height },
+                self.uptime,
+                &ui_state,
+                &mut quad_commands,
+                &mut glyph_commands,
+            );
+        }
+
+        // blit to swap chain
+        {
+            let swap_chain_target = SwapChainTarget::with_swap_chain_view(color_attachment_view);
+            let blit_pass_builder = swap_chain_target.render_pass_builder();
+            let mut blit_pass = encoder.begin_render_pass(&blit_pass_builder.descriptor());
+            gfx_state.blit_pipeline().blit(&gfx_state, &mut blit_pass);
+        }
+
+        let command_buffer = encoder.finish();
+        gfx_state.queue().submit(vec![command_buffer]);
+        gfx_state.device().poll(wgpu::Maintain::Wait);
+    }
+}
+
 impl Program for ClientProgram {
     fn handle_event<T>(
         &mut self,
@@ -317,6 +689,14 @@ impl Program for ClientProgram {
     }
 
     fn frame(&mut self, frame_duration: Duration) {
+        // captured demos are stepped at a fixed rate regardless of real time
+        let frame_duration = match self.demo_capture {
+            Some(ref capture) => capture.frame_duration(),
+            None => frame_duration,
+        };
+
+        self.uptime = self.uptime + frame_duration;
+
         // recreate swapchain if needed
         if self.window_dimensions_changed.get() {
             self.window_dimensions_changed.set(false);
@@ -325,24 +705,49 @@ impl Program for ClientProgram {
 
         let size: Extent2d = self.window.inner_size().into();
 
-        // TODO: warn user if r_msaa_samples is invalid
-        let mut sample_count = self
-            .cvars
-            .borrow()
-            .get_value("r_msaa_samples")
-            .unwrap_or(2.0) as u32;
-        if !&[2, 4].contains(&sample_count) {
-            sample_count = 2;
+        // recreate attachments and rebuild pipelines if necessary
+        self.gfx_state
+            .borrow_mut()
+            .update(size, self.msaa_samples.get());
+
+        if self.game_changed.replace(false) {
+            self.change_game();
         }
 
-        // recreate attachments and rebuild pipelines if necessary
-        self.gfx_state.borrow_mut().update(size, sample_count);
+        self.handle_demo_request();
+        self.poll_assets();
 
+        let mut demo_finished = false;
         match *self.state.borrow_mut() {
-            ProgramState::Title => unimplemented!(),
+            ProgramState::Title => {
+                self.input
+                    .borrow_mut()
+                    .set_focus(match self.title_state.get() {
+                        TitleState::Menu => InputFocus::Menu,
+                        TitleState::Console => InputFocus::Console,
+                    })
+                    .unwrap();
+            }
 
             ProgramState::Game(ref mut game) => {
                 game.frame(&self.gfx_state.borrow(), frame_duration);
+                demo_finished = game.demo_finished();
+
+                if let Some(ref capture) = self.demo_capture {
+                    game.screenshot(capture.frame_path());
+                }
+            }
+        }
+
+        if demo_finished {
+            match self.demo_capture.take() {
+                Some(capture) => {
+                    capture.finish();
+                    self.demo_queue.borrow_mut().set_next(None);
+                    self.enter_title();
+                }
+
+                None => self.next_demo(),
             }
         }
 
@@ -361,11 +766,35 @@ impl Program for ClientProgram {
         // run console commands
         self.console.borrow().execute();
 
+        self.log.flush(&mut self.console.borrow().output_mut());
+
         self.render();
+
+        // nothing is rendered while the level is loading, so those frames are filled in later
+        if let Some(ref mut capture) = self.demo_capture {
+            if let ProgramState::Game(ref game) = *self.state.borrow() {
+                if game.screenshot_pending() {
+                    capture.skip();
+                } else {
+                    capture.advance();
+                }
+            }
+        }
     }
 
     fn shutdown(&mut self) {
-        // TODO: do cleanup things here
+        if let Err(e) = config::host_writeconfig(
+            self.vfs.game_dir(),
+            &self.cvars.borrow(),
+            &self.input.borrow(),
+            &self.console.borrow(),
+        ) {
+            log::error!("Couldn't write {}: {}", config::CONFIG_FILE, e);
+        }
+
+        if let Err(e) = config::save_history(&self.console.borrow()) {
+            log::error!("Couldn't save command history: {}", e);
+        }
     }
 
     fn cvars(&self) -> Ref<CvarRegistry> {
@@ -377,20 +806,72 @@ impl Program for ClientProgram {
     }
 }
 
+/// Returns `true` if `path` holds 2D graphics used by the UI renderer.
+///
+/// The palette is built into the render pipelines and isn't reloaded.
+fn is_ui_texture(path: &str) -> bool {
+    let path = path.to_lowercase();
+    path == "gfx.wad"
+        || (path.starts_with("gfx/")
+            && path.ends_with(".lmp")
+            && path != "gfx/palette.lmp"
+            && path != "gfx/colormap.lmp")
+}
+
+/// Returns an argument completer for commands that take the name of a file in `dir`.
+///
+/// Candidates are given relative to `dir` and without the extension `ext`.
+fn cmd_complete_files(
+    vfs: Rc<Vfs>,
+    dir: &'static str,
+    ext: &'static str,
+) -> Box<dyn Fn(usize) -> Vec<String>> {
+    Box::new(move |arg_id| {
+        if arg_id != 0 {
+            return Vec::new();
+        }
+
+        let suffix = format!(".{}", ext);
+        vfs.glob(format!("{}**{}", dir, suffix))
+            .into_iter()
+            .filter_map(|entry| {
+                let name = entry.path.strip_prefix(dir)?;
+                Some(name.trim_end_matches(suffix.as_str()).to_owned())
+            })
+            .collect()
+    })
+}
+
 #[derive(StructOpt, Debug)]
 struct Opt {
     #[structopt(long)]
     trace: bool,
 
+    /// The directory containing the game directories.
+    #[structopt(long, parse(from_os_str), default_value = ".")]
+    basedir: PathBuf,
+
+    /// A mod directory to layer over the base game.
+    #[structopt(long)]
+    game: Option<String>,
+
     #[structopt(long)]
     connect: Option<SocketAddr>,
 
     #[structopt(long)]
     demo: Option<String>,
+
+    /// Capture the demo given by --demo to a directory of PNG frames and a WAV file.
+    #[structopt(long, parse(from_os_str))]
+    capture: Option<PathBuf>,
+
+    /// Frame rate of captured demos.
+    #[structopt(long, default_value = "60")]
+    capture_fps: u32,
 }
 
 fn main() {
-    env_logger::init();
+    let log = ConsoleLog::init().unwrap();
     let opt = Opt::from_args();
 
     let audio_device = rodio::default_output_device().unwrap();
@@ -419,12 +900,28 @@ fn main() {
         }
     };
 
-    let mut client_program =
-        futures::executor::block_on(ClientProgram::new(window, audio_device, opt.trace));
+    let vfs = match Vfs::with_game(&opt.basedir, opt.game.as_deref()) {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("Couldn't load game data: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut client_program = futures::executor::block_on(ClientProgram::new(
+        window,
+        audio_device,
+        vfs,
+        log,
+        opt.trace,
+    ));
     if let Some(ref server) = opt.connect {
         client_program.connect(server);
     } else if let Some(ref demo) = opt.demo {
-        client_program.play_demo(demo);
+        match opt.capture {
+            Some(ref dir) => client_program.capture_demo(demo, dir, opt.capture_fps),
+            None => client_program.play_demo(demo),
+        }
     }
 
     let mut host = Host::new(client_program);
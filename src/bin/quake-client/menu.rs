@@ -18,15 +18,23 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use richter::client::menu::{Menu, MenuBodyView, MenuBuilder, MenuView};
+use std::{cell::RefCell, rc::Rc};
+
+use richter::{
+    client::{
+        demo,
+        menu::{Menu, MenuBodyView, MenuBuilder, MenuView},
+    },
+    common::{console::Console, vfs::Vfs},
+};
 
 use failure::Error;
 
-pub fn build_main_menu() -> Result<Menu, Error> {
+pub fn build_main_menu(console: Rc<RefCell<Console>>, vfs: Rc<Vfs>) -> Result<Menu, Error> {
     Ok(MenuBuilder::new()
         .add_submenu("Single Player", build_menu_sp()?)
         .add_submenu("Multiplayer", build_menu_mp()?)
-        .add_submenu("Options", build_menu_options()?)
+        .add_submenu("Options", build_menu_options(console, vfs)?)
         .add_action("Help/Ordering", Box::new(|| ()))
         .add_action("Quit", Box::new(|| ()))
         .build(MenuView {
@@ -100,10 +108,20 @@ fn build_menu_mp_join_tcp() -> Result<Menu, Error> {
         }))
 }
 
-fn build_menu_options() -> Result<Menu, Error> {
+fn build_menu_options(console: Rc<RefCell<Console>>, vfs: Rc<Vfs>) -> Result<Menu, Error> {
     Ok(MenuBuilder::new()
         // .add_submenu("Customize controls", unimplemented!())
         .add_action("Go to console", Box::new(|| ()))
+        .add_dynamic_submenu(
+            "Play demo",
+            MenuView {
+                draw_plaque: true,
+                title_path: "gfx/p_option.lmp".to_string(),
+                body: MenuBodyView::Dynamic,
+            },
+            // the demo list changes with the game directory, so find demos when the menu opens
+            Box::new(move |builder| add_demos(builder, &console, &vfs)),
+        )
         .add_action("Reset to defaults", Box::new(|| ()))
         .add_slider("Render scale", 0.25, 1.0, 2, 0, Box::new(|_| ()))?
         .add_slider("Screen Size", 0.0, 1.0, 10, 9, Box::new(|_| ()))?
@@ -122,3 +140,28 @@ fn build_menu_options() -> Result<Menu, Error> {
             body: MenuBodyView::Dynamic,
         }))
 }
+
+fn add_demos(mut builder: MenuBuilder, console: &Rc<RefCell<Console>>, vfs: &Vfs) -> MenuBuilder {
+    let demos = demo::find_demos(vfs);
+    if demos.is_empty() {
+        builder = builder.add_action("No demos found", Box::new(|| ()));
+    }
+
+    for info in demos {
+        let detail = format!(
+            "{} {}K",
+            info.map_name().unwrap_or("?"),
+            (info.size() + 1023) / 1024
+        );
+
+        let console = console.clone();
+        let cmd = format!("togglemenu\nplaydemo \"{}\"\n", info.path());
+        builder = builder.add_action_with_detail(
+            info.path().trim_end_matches(".dem"),
+            detail.as_str(),
+            Box::new(move || console.borrow().stuff_text(&cmd)),
+        );
+    }
+
+    builder
+}
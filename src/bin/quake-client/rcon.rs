@@ -0,0 +1,67 @@
+use std::{cell::RefCell, net::ToSocketAddrs as _, rc::Rc};
+
+use richter::common::{
+    console::{join_args, Console, CvarRegistry},
+    net::connect::{ConnectSocket, Request, Response},
+};
+
+use chrono::Duration;
+use failure::{bail, Error};
+
+// how long to wait for the server to respond
+const RCON_TIMEOUT_MS: i64 = 2500;
+
+/// Implements the `rcon` command.
+///
+/// The arguments are sent to the server at `rcon_address` along with `rcon_password`, and the
+/// output of the command is printed to the console.
+pub fn cmd_rcon(
+    cvars: Rc<RefCell<CvarRegistry>>,
+    console: Rc<RefCell<Console>>,
+) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |args| {
+        if args.is_empty() {
+            bail!("rcon <command> : run a command on the server at rcon_address");
+        }
+
+        let (address, password) = {
+            let cvars = cvars.borrow();
+            (cvars.get("rcon_address")?, cvars.get("rcon_password")?)
+        };
+
+        if address.is_empty() {
+            bail!("rcon_address is not set");
+        }
+
+        let server_addr = match address.to_socket_addrs()?.next() {
+            Some(a) => a,
+            None => bail!("Couldn't resolve {}", address),
+        };
+
+        let command = join_args(args);
+        let mut socket = ConnectSocket::bind("0.0.0.0:0")?;
+        socket.send_request(
+            Request::rcon(password.as_str(), command.as_str()),
+            server_addr,
+        )?;
+
+        let response = loop {
+            match socket.recv_response(Some(Duration::milliseconds(RCON_TIMEOUT_MS)))? {
+                Some((r, remote)) if remote == server_addr => break r,
+
+                // ignore anything that didn't come from the server
+                Some(_) => continue,
+
+                None => bail!("rcon: no response from {}", server_addr),
+            }
+        };
+
+        match response {
+            Response::Rcon(r) => console.borrow().print(r.output),
+            Response::Reject(r) => bail!("rcon: {}", r.message),
+            _ => bail!("rcon: invalid response from {}", server_addr),
+        }
+
+        Ok(())
+    })
+}
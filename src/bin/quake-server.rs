@@ -0,0 +1,100 @@
+// Copyright © 2020 Cormac O'Brien.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this software
+// and associated documentation files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all copies or
+// substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING
+// BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! A headless server.
+//!
+//! The server doesn't run a game yet. It answers rcon requests on its connect socket, so its
+//! console can be driven remotely, but commands like `map` and `kick` don't exist until the
+//! server can load levels and accept clients.
+
+extern crate richter;
+
+use std::{cell::RefCell, net::SocketAddr, path::PathBuf, process::exit, rc::Rc, thread};
+
+use richter::{
+    common::{
+        console::{CmdRegistry, Console, CvarRegistry},
+        vfs::Vfs,
+    },
+    server::{self, ServerListener},
+};
+
+use chrono::Duration;
+use structopt::StructOpt;
+
+// how long to sleep between server frames
+const FRAME_MS: u64 = 50;
+
+#[derive(Debug, StructOpt)]
+struct Opt {
+    /// The directory containing the game directories.
+    #[structopt(long, parse(from_os_str), default_value = ".")]
+    basedir: PathBuf,
+
+    /// A mod directory to layer over the base game.
+    #[structopt(long)]
+    game: Option<String>,
+
+    /// The address to listen for connect requests on.
+    #[structopt(long, default_value = "0.0.0.0:26000")]
+    listen: SocketAddr,
+}
+
+fn main() {
+    env_logger::init();
+    let opt = Opt::from_args();
+
+    let vfs = match Vfs::with_game(&opt.basedir, opt.game.as_deref()) {
+        Ok(v) => Rc::new(v),
+        Err(e) => {
+            log::error!("Couldn't load game data: {}", e);
+            exit(1);
+        }
+    };
+
+    let cvars = Rc::new(RefCell::new(CvarRegistry::new()));
+    server::register_cvars(&cvars.borrow()).unwrap();
+
+    let cmds = Rc::new(RefCell::new(CmdRegistry::new()));
+    let console = Console::new(cmds, cvars.clone(), vfs.clone());
+
+    // server.cfg is the place to set rcon_password
+    if vfs.open("server.cfg").is_ok() {
+        console.stuff_text("exec server.cfg");
+    }
+
+    let mut listener = match ServerListener::bind(opt.listen) {
+        Ok(l) => l,
+        Err(e) => {
+            log::error!("Couldn't listen on {}: {}", opt.listen, e);
+            exit(1);
+        }
+    };
+    log::info!("Listening on {}", opt.listen);
+
+    let start = std::time::Instant::now();
+    loop {
+        console.execute();
+
+        let time = Duration::from_std(start.elapsed()).unwrap();
+        if let Err(e) = listener.poll(time, &cvars.borrow(), &console) {
+            log::error!("Couldn't answer connect requests: {}", e);
+        }
+
+        thread::sleep(std::time::Duration::from_millis(FRAME_MS));
+    }
+}
@@ -66,7 +66,15 @@ fn main() {
         }
     };
 
-    for (k, v) in pak.iter() {
+    for (k, _) in pak.iter() {
+        let data = match pak.open(k) {
+            Ok(d) => d,
+            Err(why) => {
+                println!("Couldn't read {}: {}", k, why);
+                exit(1);
+            }
+        };
+
         let mut path = PathBuf::new();
 
         if let Some(ref d) = opt.output_dir {
@@ -93,7 +101,7 @@ fn main() {
         };
 
         let mut writer = BufWriter::new(file);
-        match writer.write_all(v.as_ref()) {
+        match writer.write_all(&data) {
             Ok(_) => (),
             Err(why) => {
                 println!("Couldn't write to {}: {}", path.to_str().unwrap(), why);
@@ -18,7 +18,7 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use crate::common::console::{CvarRegistry, ConsoleError};
+use crate::common::console::{ConsoleError, CvarKind, CvarRegistry};
 
 pub fn register_cvars(cvars: &CvarRegistry) -> Result<(), ConsoleError> {
     cvars.register("cl_anglespeedkey", "1.5")?;
@@ -26,24 +26,75 @@ pub fn register_cvars(cvars: &CvarRegistry) -> Result<(), ConsoleError> {
     cvars.register("cl_bob", "0.02")?;
     cvars.register("cl_bobcycle", "0.6")?;
     cvars.register("cl_bobup", "0.5")?;
-    cvars.register_archive("_cl_color", "0")?;
+    cvars
+        .define("_cl_color", "0")
+        .kind(CvarKind::Int {
+            min: Some(0),
+            max: Some(255),
+        })
+        .description("player colors, encoded as top color * 16 + bottom color")
+        .archive()
+        .notify()
+        .register()?;
     cvars.register("cl_crossx", "0")?;
     cvars.register("cl_crossy", "0")?;
     cvars.register_archive("cl_forwardspeed", "400")?;
+    cvars
+        .define("cl_hotreload", "0")
+        .kind(CvarKind::Bool)
+        .description("reload models, sprites and sounds when their files change on disk")
+        .register()?;
     cvars.register("cl_movespeedkey", "2.0")?;
-    cvars.register_archive("_cl_name", "player")?;
-    cvars.register("cl_nolerp", "0")?;
+    cvars.register_archive_notify("_cl_name", "player")?;
+    cvars
+        .define("cl_nolerp", "0")
+        .kind(CvarKind::Bool)
+        .description("disable interpolation of entity positions between server updates")
+        .register()?;
     cvars.register("cl_pitchspeed", "150")?;
     cvars.register("cl_rollangle", "2.0")?;
     cvars.register("cl_rollspeed", "200")?;
-    cvars.register("cl_shownet", "0")?;
+    cvars
+        .define("cl_shownet", "0")
+        .kind(CvarKind::Int {
+            min: Some(0),
+            max: Some(2),
+        })
+        .description("print the size (1) or contents (2) of each server message")
+        .register()?;
     cvars.register("cl_sidespeed", "350")?;
     cvars.register("cl_upspeed", "200")?;
     cvars.register("cl_yawspeed", "140")?;
-    cvars.register("fov", "90")?;
+    cvars.register("chase_back", "100")?;
+    cvars.register("chase_up", "16")?;
+    cvars
+        .define("fov", "90")
+        .kind(CvarKind::Float {
+            min: Some(10.0),
+            max: Some(170.0),
+        })
+        .description("horizontal field of view in degrees")
+        .register()?;
     cvars.register_archive("m_pitch", "0.022")?;
     cvars.register_archive("m_yaw", "0.022")?;
-    cvars.register_archive("sensitivity", "3")?;
+    cvars
+        .define("sensitivity", "3")
+        .kind(CvarKind::Float {
+            min: Some(0.0),
+            max: None,
+        })
+        .description("mouse sensitivity")
+        .archive()
+        .register()?;
+    cvars
+        .define("rcon_address", "")
+        .description("address of the server to send rcon commands to")
+        .archive()
+        .register()?;
+    cvars
+        .define("rcon_password", "")
+        .description("password to send with rcon commands")
+        .register()?;
     cvars.register("v_idlescale", "0")?;
     cvars.register("v_ipitch_cycle", "1")?;
     cvars.register("v_ipitch_level", "0.3")?;
@@ -0,0 +1,997 @@
+//! Conversion of demo files between NetQuake protocol versions.
+//!
+//! Demos are transcoded message by message. Commands whose encoding is identical in both
+//! protocols are copied byte for byte; the remainder are decoded and re-encoded for the target
+//! protocol. Upgrading from protocol 15 to 666 is always lossless. Downgrading may have to drop
+//! or truncate data which has no representation in protocol 15, and every such occurrence is
+//! recorded in the returned `ConvertReport`.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{self, BufRead, Cursor, Read, Write},
+};
+
+use crate::common::net::{
+    self, ClientUpdateFlags, NetError, ServerCmdCode, SoundFlags, UpdateFlags, FAST_UPDATE_FLAG,
+};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use num::FromPrimitive;
+use thiserror::Error;
+
+// server commands introduced by protocol 666
+const SVC_SKYBOX: u8 = 37;
+const SVC_BF: u8 = 40;
+const SVC_FOG: u8 = 41;
+const SVC_SPAWN_BASELINE_2: u8 = 42;
+const SVC_SPAWN_STATIC_2: u8 = 43;
+const SVC_SPAWN_STATIC_SOUND_2: u8 = 44;
+
+// entity update bits introduced by protocol 666
+const U_EXTEND_1: u32 = 1 << 15;
+const U_ALPHA: u32 = 1 << 16;
+const U_FRAME_2: u32 = 1 << 17;
+const U_MODEL_2: u32 = 1 << 18;
+const U_LERP_FINISH: u32 = 1 << 19;
+const U_SCALE: u32 = 1 << 20;
+const U_EXTEND_2: u32 = 1 << 23;
+
+// client data bits introduced by protocol 666
+const SU_EXTEND_1: u32 = 1 << 15;
+const SU_WEAPON_2: u32 = 1 << 16;
+const SU_ARMOR_2: u32 = 1 << 17;
+const SU_AMMO_2: u32 = 1 << 18;
+const SU_SHELLS_2: u32 = 1 << 19;
+const SU_NAILS_2: u32 = 1 << 20;
+const SU_ROCKETS_2: u32 = 1 << 21;
+const SU_CELLS_2: u32 = 1 << 22;
+const SU_EXTEND_2: u32 = 1 << 23;
+const SU_WEAPON_FRAME_2: u32 = 1 << 24;
+const SU_WEAPON_ALPHA: u32 = 1 << 25;
+
+// sound bits introduced by protocol 666
+const SND_LARGE_ENTITY: u8 = 1 << 3;
+const SND_LARGE_SOUND: u8 = 1 << 4;
+
+// baseline bits used by the protocol 666 spawn commands
+const B_LARGE_MODEL: u8 = 1 << 0;
+const B_LARGE_FRAME: u8 = 1 << 1;
+const B_ALPHA: u8 = 1 << 2;
+
+// the largest entity ID that fits in the combined entity/channel field of a protocol 15 sound
+const MAX_SHORT_SOUND_ENTITY: u16 = 0x1FFF;
+
+#[derive(Error, Debug)]
+pub enum DemoConvertError {
+    #[error("Invalid CD track number")]
+    InvalidCdTrack,
+    #[error("Unsupported protocol version: {0}")]
+    UnsupportedProtocol(i32),
+    #[error("Invalid server command code: {0}")]
+    InvalidCommand(u8),
+    #[error("Converted message size ({0}) exceeds maximum allowed size {}", net::MAX_MESSAGE)]
+    MessageTooLong(usize),
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Network error: {0}")]
+    Net(#[from] NetError),
+}
+
+/// A NetQuake network protocol version.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Protocol {
+    /// The original NetQuake protocol, version 15.
+    NetQuake,
+
+    /// The FitzQuake protocol, version 666.
+    ///
+    /// This extends protocol 15 with entity alpha, 16-bit model, frame and sound indices, and
+    /// commands for fog and skyboxes.
+    FitzQuake,
+}
+
+impl Protocol {
+    pub fn from_version(version: i32) -> Option<Protocol> {
+        match version {
+            15 => Some(Protocol::NetQuake),
+            666 => Some(Protocol::FitzQuake),
+            _ => None,
+        }
+    }
+
+    pub fn version(&self) -> i32 {
+        match *self {
+            Protocol::NetQuake => 15,
+            Protocol::FitzQuake => 666,
+        }
+    }
+}
+
+/// A feature of the source demo which could not be represented in the target protocol.
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum LossyFeature {
+    /// Entity or weapon model translucency.
+    Alpha,
+    /// Entity model scaling.
+    Scale,
+    /// Explicit interpolation end times for stepping entities.
+    LerpFinish,
+    /// Model indices greater than 255.
+    LargeModelIndex,
+    /// Frame indices greater than 255.
+    LargeFrameIndex,
+    /// Sound indices greater than 255.
+    LargeSoundIndex,
+    /// Entity indices too large for the sound entity field.
+    LargeEntityIndex,
+    /// Player statistics (health, armor, ammo) greater than 255.
+    LargeStat,
+    /// Fog settings.
+    Fog,
+    /// Skybox settings.
+    Skybox,
+}
+
+impl fmt::Display for LossyFeature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use LossyFeature::*;
+        let desc = match *self {
+            Alpha => "entity alpha",
+            Scale => "entity scale",
+            LerpFinish => "interpolation end time",
+            LargeModelIndex => "model index > 255",
+            LargeFrameIndex => "frame index > 255",
+            LargeSoundIndex => "sound index > 255",
+            LargeEntityIndex => "sound entity index > 8191",
+            LargeStat => "player stat > 255",
+            Fog => "fog",
+            Skybox => "skybox",
+        };
+
+        write!(f, "{}", desc)
+    }
+}
+
+/// A summary of a demo conversion.
+#[derive(Debug)]
+pub struct ConvertReport {
+    src_protocol: Option<Protocol>,
+    message_count: usize,
+    lossy: BTreeMap<LossyFeature, usize>,
+}
+
+impl ConvertReport {
+    fn new() -> ConvertReport {
+        ConvertReport {
+            src_protocol: None,
+            message_count: 0,
+            lossy: BTreeMap::new(),
+        }
+    }
+
+    /// The protocol announced by the source demo, if it contained a server info message.
+    pub fn src_protocol(&self) -> Option<Protocol> {
+        self.src_protocol
+    }
+
+    /// The number of messages converted.
+    pub fn message_count(&self) -> usize {
+        self.message_count
+    }
+
+    /// Returns `true` if the conversion preserved all of the source data.
+    pub fn is_lossless(&self) -> bool {
+        self.lossy.is_empty()
+    }
+
+    /// Iterates over features lost in the conversion along with the number of times each was
+    /// encountered.
+    pub fn lossy_features<'a>(&'a self) -> impl Iterator<Item = (LossyFeature, usize)> + 'a {
+        self.lossy.iter().map(|(f, n)| (*f, *n))
+    }
+
+    fn record(&mut self, feature: LossyFeature) {
+        *self.lossy.entry(feature).or_insert(0) += 1;
+    }
+}
+
+/// Reads a demo from `src` and writes it to `dst` using the `target` protocol.
+///
+/// The source protocol is determined from the server info messages in the demo itself. View
+/// angles and the CD track header are copied unchanged.
+pub fn convert_demo<R, W>(
+    src: &mut R,
+    dst: &mut W,
+    target: Protocol,
+) -> Result<ConvertReport, DemoConvertError>
+where
+    R: BufRead,
+    W: Write,
+{
+    // copy CD track number (terminated by newline)
+    let mut header = Vec::new();
+    src.read_until(b'\n', &mut header)?;
+    if header.last() != Some(&b'\n') || header.len() > 4 {
+        Err(DemoConvertError::InvalidCdTrack)?;
+    }
+    dst.write_all(&header)?;
+
+    let mut converter = Converter {
+        // commands preceding the first server info are identical in both protocols
+        src: Protocol::NetQuake,
+        dst: target,
+        report: ConvertReport::new(),
+    };
+
+    let mut msg = Vec::new();
+    let mut out = Vec::new();
+    loop {
+        let msg_len = match src.read_u32::<LittleEndian>() {
+            Ok(l) => l,
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => Err(e)?,
+        };
+
+        let mut view_angles = [0; 12];
+        src.read_exact(&mut view_angles)?;
+
+        msg.resize(msg_len as usize, 0);
+        src.read_exact(&mut msg)?;
+
+        out.clear();
+        converter.convert_message(&msg, &mut out)?;
+
+        if target == Protocol::NetQuake && out.len() > net::MAX_MESSAGE {
+            Err(DemoConvertError::MessageTooLong(out.len()))?;
+        }
+
+        dst.write_u32::<LittleEndian>(out.len() as u32)?;
+        dst.write_all(&view_angles)?;
+        dst.write_all(&out)?;
+        converter.report.message_count += 1;
+    }
+
+    Ok(converter.report)
+}
+
+struct Converter {
+    src: Protocol,
+    dst: Protocol,
+    report: ConvertReport,
+}
+
+impl Converter {
+    fn downgrading(&self) -> bool {
+        self.src == Protocol::FitzQuake && self.dst == Protocol::NetQuake
+    }
+
+    fn convert_message(&mut self, msg: &[u8], out: &mut Vec<u8>) -> Result<(), DemoConvertError> {
+        let mut reader = Cursor::new(msg);
+
+        while (reader.position() as usize) < msg.len() {
+            let code = reader.read_u8()?;
+
+            if code & FAST_UPDATE_FLAG != 0 {
+                self.convert_entity_update(code, &mut reader, out)?;
+                continue;
+            }
+
+            if self.src == Protocol::FitzQuake {
+                match code {
+                    SVC_SKYBOX => {
+                        if self.downgrading() {
+                            let mut skip = Vec::new();
+                            copy_cstring(&mut reader, &mut skip)?;
+                            self.report.record(LossyFeature::Skybox);
+                        } else {
+                            out.push(code);
+                            copy_cstring(&mut reader, out)?;
+                        }
+                        continue;
+                    }
+
+                    SVC_BF => {
+                        if self.downgrading() {
+                            // protocol 15 servers trigger the bonus flash with a stuffed command
+                            out.push(ServerCmdCode::StuffText as u8);
+                            out.extend_from_slice(b"bf\n\0");
+                        } else {
+                            out.push(code);
+                        }
+                        continue;
+                    }
+
+                    SVC_FOG => {
+                        // density, RGB color and fade time
+                        if self.downgrading() {
+                            let mut skip = Vec::new();
+                            copy(&mut reader, &mut skip, 6)?;
+                            self.report.record(LossyFeature::Fog);
+                        } else {
+                            out.push(code);
+                            copy(&mut reader, out, 6)?;
+                        }
+                        continue;
+                    }
+
+                    SVC_SPAWN_BASELINE_2 => {
+                        let ent_id = reader.read_u16::<LittleEndian>()?;
+                        self.convert_spawn(Some(ent_id), &mut reader, out)?;
+                        continue;
+                    }
+
+                    SVC_SPAWN_STATIC_2 => {
+                        self.convert_spawn(None, &mut reader, out)?;
+                        continue;
+                    }
+
+                    SVC_SPAWN_STATIC_SOUND_2 => {
+                        let mut origin = [0; 6];
+                        reader.read_exact(&mut origin)?;
+                        let sound_id = reader.read_u16::<LittleEndian>()?;
+                        let mut rest = [0; 2];
+                        reader.read_exact(&mut rest)?;
+
+                        if self.downgrading() || sound_id <= 0xFF {
+                            if sound_id > 0xFF {
+                                self.report.record(LossyFeature::LargeSoundIndex);
+                            }
+                            out.push(ServerCmdCode::SpawnStaticSound as u8);
+                            out.extend_from_slice(&origin);
+                            out.push(sound_id as u8);
+                        } else {
+                            out.push(code);
+                            out.extend_from_slice(&origin);
+                            out.write_u16::<LittleEndian>(sound_id)?;
+                        }
+                        out.extend_from_slice(&rest);
+                        continue;
+                    }
+
+                    _ => (),
+                }
+            }
+
+            let cmd_code = match ServerCmdCode::from_u8(code) {
+                Some(c) => c,
+                None => Err(DemoConvertError::InvalidCommand(code))?,
+            };
+
+            use ServerCmdCode::*;
+            match cmd_code {
+                ServerInfo => self.convert_server_info(&mut reader, out)?,
+                ClientData => self.convert_client_data(&mut reader, out)?,
+                Sound => self.convert_sound(&mut reader, out)?,
+
+                Version => {
+                    let version = reader.read_i32::<LittleEndian>()?;
+                    if let Some(p) = Protocol::from_version(version) {
+                        self.src = p;
+                    }
+
+                    out.push(code);
+                    out.write_i32::<LittleEndian>(self.dst.version())?;
+                }
+
+                TempEntity => {
+                    // temp entities are identical in both protocols, so measure and copy
+                    let start = reader.position() as usize;
+                    net::TempEntity::read_temp_entity(&mut reader)?;
+                    let end = reader.position() as usize;
+
+                    out.push(code);
+                    out.extend_from_slice(&msg[start..end]);
+                }
+
+                Print | StuffText | CenterPrint | Finale | Cutscene => {
+                    out.push(code);
+                    copy_cstring(&mut reader, out)?;
+                }
+
+                LightStyle | UpdateName => {
+                    out.push(code);
+                    copy(&mut reader, out, 1)?;
+                    copy_cstring(&mut reader, out)?;
+                }
+
+                _ => {
+                    let len = match cmd_code {
+                        Bad | NoOp | Disconnect | KilledMonster | FoundSecret | Intermission
+                        | SellScreen => 0,
+                        SetPause | SignOnStage => 1,
+                        SetView | StopSound | UpdateColors | CdTrack => 2,
+                        SetAngle | UpdateFrags => 3,
+                        Time => 4,
+                        UpdateStat => 5,
+                        Damage => 8,
+                        SpawnStaticSound => 9,
+                        Particle => 11,
+                        SpawnStatic => 13,
+                        SpawnBaseline => 15,
+                        _ => unreachable!(),
+                    };
+
+                    out.push(code);
+                    copy(&mut reader, out, len)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn convert_server_info(
+        &mut self,
+        reader: &mut Cursor<&[u8]>,
+        out: &mut Vec<u8>,
+    ) -> Result<(), DemoConvertError> {
+        let version = reader.read_i32::<LittleEndian>()?;
+        self.src = match Protocol::from_version(version) {
+            Some(p) => p,
+            None => Err(DemoConvertError::UnsupportedProtocol(version))?,
+        };
+
+        if self.report.src_protocol.is_none() {
+            self.report.src_protocol = Some(self.src);
+        }
+
+        out.push(ServerCmdCode::ServerInfo as u8);
+        out.write_i32::<LittleEndian>(self.dst.version())?;
+
+        // max clients, game type, level name
+        copy(reader, out, 2)?;
+        copy_cstring(reader, out)?;
+
+        // model and sound precache lists, each terminated by an empty string
+        for feature in &[LossyFeature::LargeModelIndex, LossyFeature::LargeSoundIndex] {
+            let mut count = 0;
+            loop {
+                let start = out.len();
+                copy_cstring(reader, out)?;
+                if out.len() - start == 1 {
+                    break;
+                }
+                count += 1;
+            }
+
+            // index 0 is reserved, so only 255 entries can be addressed in protocol 15
+            if self.downgrading() && count > 0xFF {
+                self.report.record(*feature);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn convert_entity_update(
+        &mut self,
+        code: u8,
+        reader: &mut Cursor<&[u8]>,
+        out: &mut Vec<u8>,
+    ) -> Result<(), DemoConvertError> {
+        let mut bits = (code & !FAST_UPDATE_FLAG) as u32;
+        if bits & UpdateFlags::MORE_BITS.bits() as u32 != 0 {
+            bits |= (reader.read_u8()? as u32) << 8;
+        }
+
+        if self.src == Protocol::FitzQuake {
+            if bits & U_EXTEND_1 != 0 {
+                bits |= (reader.read_u8()? as u32) << 16;
+            }
+
+            if bits & U_EXTEND_2 != 0 {
+                bits |= (reader.read_u8()? as u32) << 24;
+            }
+        }
+
+        let has = |flag: UpdateFlags| bits & flag.bits() as u32 != 0;
+
+        let mut fields = Vec::new();
+        if has(UpdateFlags::LONG_ENTITY) {
+            copy(reader, &mut fields, 2)?;
+        } else {
+            copy(reader, &mut fields, 1)?;
+        }
+
+        for flag in &[
+            UpdateFlags::MODEL,
+            UpdateFlags::FRAME,
+            UpdateFlags::COLORMAP,
+            UpdateFlags::SKIN,
+            UpdateFlags::EFFECTS,
+        ] {
+            if has(*flag) {
+                copy(reader, &mut fields, 1)?;
+            }
+        }
+
+        // origin components and angles are interleaved
+        for (origin, angle) in &[
+            (UpdateFlags::ORIGIN_X, UpdateFlags::PITCH),
+            (UpdateFlags::ORIGIN_Y, UpdateFlags::YAW),
+            (UpdateFlags::ORIGIN_Z, UpdateFlags::ROLL),
+        ] {
+            if has(*origin) {
+                copy(reader, &mut fields, 2)?;
+            }
+
+            if has(*angle) {
+                copy(reader, &mut fields, 1)?;
+            }
+        }
+
+        let mut extended = Vec::new();
+        if self.src == Protocol::FitzQuake {
+            for (flag, feature) in &[
+                (U_ALPHA, LossyFeature::Alpha),
+                (U_SCALE, LossyFeature::Scale),
+                (U_FRAME_2, LossyFeature::LargeFrameIndex),
+                (U_MODEL_2, LossyFeature::LargeModelIndex),
+                (U_LERP_FINISH, LossyFeature::LerpFinish),
+            ] {
+                if bits & flag != 0 {
+                    let value = reader.read_u8()?;
+
+                    if self.downgrading() {
+                        // a zero high byte carries no information
+                        let significant = match *flag {
+                            U_FRAME_2 | U_MODEL_2 => value != 0,
+                            _ => true,
+                        };
+
+                        if significant {
+                            self.report.record(*feature);
+                        }
+                    } else {
+                        extended.push(value);
+                    }
+                }
+            }
+        }
+
+        if self.downgrading() {
+            bits &= U_EXTEND_1 - 1;
+            if bits & 0xFF00 != 0 {
+                bits |= UpdateFlags::MORE_BITS.bits() as u32;
+            } else {
+                bits &= !(UpdateFlags::MORE_BITS.bits() as u32);
+            }
+        }
+
+        out.push(bits as u8 | FAST_UPDATE_FLAG);
+        if bits & UpdateFlags::MORE_BITS.bits() as u32 != 0 {
+            out.push((bits >> 8) as u8);
+        }
+
+        if self.dst == Protocol::FitzQuake {
+            if bits & U_EXTEND_1 != 0 {
+                out.push((bits >> 16) as u8);
+            }
+
+            if bits & U_EXTEND_2 != 0 {
+                out.push((bits >> 24) as u8);
+            }
+        }
+
+        out.extend_from_slice(&fields);
+        out.extend_from_slice(&extended);
+
+        Ok(())
+    }
+
+    fn convert_client_data(
+        &mut self,
+        reader: &mut Cursor<&[u8]>,
+        out: &mut Vec<u8>,
+    ) -> Result<(), DemoConvertError> {
+        let mut bits = reader.read_u16::<LittleEndian>()? as u32;
+
+        if self.src == Protocol::FitzQuake {
+            if bits & SU_EXTEND_1 != 0 {
+                bits |= (reader.read_u8()? as u32) << 16;
+            }
+
+            if bits & SU_EXTEND_2 != 0 {
+                bits |= (reader.read_u8()? as u32) << 24;
+            }
+        }
+
+        let has = |flag: ClientUpdateFlags| bits & flag.bits() as u32 != 0;
+
+        let mut fields = Vec::new();
+        for flag in &[
+            ClientUpdateFlags::VIEW_HEIGHT,
+            ClientUpdateFlags::IDEAL_PITCH,
+            ClientUpdateFlags::PUNCH_PITCH,
+            ClientUpdateFlags::VELOCITY_X,
+            ClientUpdateFlags::PUNCH_YAW,
+            ClientUpdateFlags::VELOCITY_Y,
+            ClientUpdateFlags::PUNCH_ROLL,
+            ClientUpdateFlags::VELOCITY_Z,
+        ] {
+            if has(*flag) {
+                copy(reader, &mut fields, 1)?;
+            }
+        }
+
+        // item flags
+        copy(reader, &mut fields, 4)?;
+
+        for flag in &[
+            ClientUpdateFlags::WEAPON_FRAME,
+            ClientUpdateFlags::ARMOR,
+            ClientUpdateFlags::WEAPON,
+        ] {
+            if has(*flag) {
+                copy(reader, &mut fields, 1)?;
+            }
+        }
+
+        // health, current ammo, ammo counts and active weapon
+        copy(reader, &mut fields, 8)?;
+
+        let mut extended = Vec::new();
+        if self.src == Protocol::FitzQuake {
+            for (flag, feature) in &[
+                (SU_WEAPON_2, LossyFeature::LargeModelIndex),
+                (SU_ARMOR_2, LossyFeature::LargeStat),
+                (SU_AMMO_2, LossyFeature::LargeStat),
+                (SU_SHELLS_2, LossyFeature::LargeStat),
+                (SU_NAILS_2, LossyFeature::LargeStat),
+                (SU_ROCKETS_2, LossyFeature::LargeStat),
+                (SU_CELLS_2, LossyFeature::LargeStat),
+                (SU_WEAPON_FRAME_2, LossyFeature::LargeFrameIndex),
+                (SU_WEAPON_ALPHA, LossyFeature::Alpha),
+            ] {
+                if bits & flag != 0 {
+                    let value = reader.read_u8()?;
+
+                    if self.downgrading() {
+                        if *flag == SU_WEAPON_ALPHA || value != 0 {
+                            self.report.record(*feature);
+                        }
+                    } else {
+                        extended.push(value);
+                    }
+                }
+            }
+        }
+
+        if self.downgrading() {
+            bits &= SU_EXTEND_1 - 1;
+        }
+
+        out.push(ServerCmdCode::ClientData as u8);
+        out.write_u16::<LittleEndian>(bits as u16)?;
+
+        if self.dst == Protocol::FitzQuake {
+            if bits & SU_EXTEND_1 != 0 {
+                out.push((bits >> 16) as u8);
+            }
+
+            if bits & SU_EXTEND_2 != 0 {
+                out.push((bits >> 24) as u8);
+            }
+        }
+
+        out.extend_from_slice(&fields);
+        out.extend_from_slice(&extended);
+
+        Ok(())
+    }
+
+    fn convert_sound(
+        &mut self,
+        reader: &mut Cursor<&[u8]>,
+        out: &mut Vec<u8>,
+    ) -> Result<(), DemoConvertError> {
+        let mut flags = reader.read_u8()?;
+
+        let mut params = Vec::new();
+        if flags & SoundFlags::VOLUME.bits() != 0 {
+            copy(reader, &mut params, 1)?;
+        }
+
+        if flags & SoundFlags::ATTENUATION.bits() != 0 {
+            copy(reader, &mut params, 1)?;
+        }
+
+        let (entity_id, channel) =
+            if self.src == Protocol::FitzQuake && flags & SND_LARGE_ENTITY != 0 {
+                let entity_id = reader.read_u16::<LittleEndian>()?;
+                let channel = reader.read_u8()?;
+                (entity_id, channel)
+            } else {
+                let entity_channel = reader.read_u16::<LittleEndian>()?;
+                (entity_channel >> 3, (entity_channel & 0b111) as u8)
+            };
+
+        let sound_id = if self.src == Protocol::FitzQuake && flags & SND_LARGE_SOUND != 0 {
+            reader.read_u16::<LittleEndian>()?
+        } else {
+            reader.read_u8()? as u16
+        };
+
+        flags &= !(SND_LARGE_ENTITY | SND_LARGE_SOUND);
+        if self.dst == Protocol::FitzQuake {
+            if entity_id > MAX_SHORT_SOUND_ENTITY {
+                flags |= SND_LARGE_ENTITY;
+            }
+
+            if sound_id > 0xFF {
+                flags |= SND_LARGE_SOUND;
+            }
+        } else {
+            if entity_id > MAX_SHORT_SOUND_ENTITY {
+                self.report.record(LossyFeature::LargeEntityIndex);
+            }
+
+            if sound_id > 0xFF {
+                self.report.record(LossyFeature::LargeSoundIndex);
+            }
+        }
+
+        out.push(ServerCmdCode::Sound as u8);
+        out.push(flags);
+        out.extend_from_slice(&params);
+
+        if flags & SND_LARGE_ENTITY != 0 {
+            out.write_u16::<LittleEndian>(entity_id)?;
+            out.push(channel);
+        } else {
+            out.write_u16::<LittleEndian>(entity_id << 3 | channel as u16 & 0b111)?;
+        }
+
+        if flags & SND_LARGE_SOUND != 0 {
+            out.write_u16::<LittleEndian>(sound_id)?;
+        } else {
+            out.push(sound_id as u8);
+        }
+
+        // position
+        copy(reader, out, 6)?;
+
+        Ok(())
+    }
+
+    /// Converts a protocol 666 baseline (if `ent_id` is provided) or static entity.
+    fn convert_spawn(
+        &mut self,
+        ent_id: Option<u16>,
+        reader: &mut Cursor<&[u8]>,
+        out: &mut Vec<u8>,
+    ) -> Result<(), DemoConvertError> {
+        let bits = reader.read_u8()?;
+
+        let model_id = match bits & B_LARGE_MODEL {
+            0 => reader.read_u8()? as u16,
+            _ => reader.read_u16::<LittleEndian>()?,
+        };
+
+        let frame_id = match bits & B_LARGE_FRAME {
+            0 => reader.read_u8()? as u16,
+            _ => reader.read_u16::<LittleEndian>()?,
+        };
+
+        // colormap, skin, then interleaved origin and angles
+        let mut fields = Vec::new();
+        copy(reader, &mut fields, 11)?;
+
+        let alpha = match bits & B_ALPHA {
+            0 => None,
+            _ => Some(reader.read_u8()?),
+        };
+
+        let extended = model_id > 0xFF || frame_id > 0xFF || alpha.is_some();
+        if self.dst == Protocol::FitzQuake && extended {
+            let mut out_bits = 0;
+            if model_id > 0xFF {
+                out_bits |= B_LARGE_MODEL;
+            }
+
+            if frame_id > 0xFF {
+                out_bits |= B_LARGE_FRAME;
+            }
+
+            if alpha.is_some() {
+                out_bits |= B_ALPHA;
+            }
+
+            match ent_id {
+                Some(id) => {
+                    out.push(SVC_SPAWN_BASELINE_2);
+                    out.write_u16::<LittleEndian>(id)?;
+                }
+                None => out.push(SVC_SPAWN_STATIC_2),
+            }
+
+            out.push(out_bits);
+
+            match out_bits & B_LARGE_MODEL {
+                0 => out.push(model_id as u8),
+                _ => out.write_u16::<LittleEndian>(model_id)?,
+            }
+
+            match out_bits & B_LARGE_FRAME {
+                0 => out.push(frame_id as u8),
+                _ => out.write_u16::<LittleEndian>(frame_id)?,
+            }
+
+            out.extend_from_slice(&fields);
+
+            if let Some(a) = alpha {
+                out.push(a);
+            }
+        } else {
+            if model_id > 0xFF {
+                self.report.record(LossyFeature::LargeModelIndex);
+            }
+
+            if frame_id > 0xFF {
+                self.report.record(LossyFeature::LargeFrameIndex);
+            }
+
+            if alpha.is_some() {
+                self.report.record(LossyFeature::Alpha);
+            }
+
+            match ent_id {
+                Some(id) => {
+                    out.push(ServerCmdCode::SpawnBaseline as u8);
+                    out.write_u16::<LittleEndian>(id)?;
+                }
+                None => out.push(ServerCmdCode::SpawnStatic as u8),
+            }
+
+            out.push(model_id as u8);
+            out.push(frame_id as u8);
+            out.extend_from_slice(&fields);
+        }
+
+        Ok(())
+    }
+}
+
+/// Copies `len` bytes from `reader` to the end of `out`.
+fn copy<R>(reader: &mut R, out: &mut Vec<u8>, len: usize) -> io::Result<()>
+where
+    R: Read,
+{
+    let start = out.len();
+    out.resize(start + len, 0);
+    reader.read_exact(&mut out[start..])
+}
+
+/// Copies a null-terminated string, including the terminator, to the end of `out`.
+fn copy_cstring<R>(reader: &mut R, out: &mut Vec<u8>) -> io::Result<()>
+where
+    R: BufRead,
+{
+    reader.read_until(0, out)?;
+
+    match out.last() {
+        Some(0) => Ok(()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "unterminated string",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::common::net::ServerCmd;
+
+    fn demo(messages: &[Vec<u8>]) -> Vec<u8> {
+        let mut demo = b"-1\n".to_vec();
+        for msg in messages {
+            demo.write_u32::<LittleEndian>(msg.len() as u32).unwrap();
+            demo.extend_from_slice(&[0; 12]);
+            demo.extend_from_slice(msg);
+        }
+
+        demo
+    }
+
+    fn server_info(version: i32) -> Vec<u8> {
+        let mut msg = Vec::new();
+        msg.push(ServerCmdCode::ServerInfo as u8);
+        msg.write_i32::<LittleEndian>(version).unwrap();
+        msg.extend_from_slice(&[1, 0]);
+        msg.extend_from_slice(b"start\0maps/start.bsp\0\0misc/null.wav\0\0");
+        msg
+    }
+
+    #[test]
+    fn test_convert_upgrade_round_trip_eq() {
+        let mut msg = server_info(15);
+        ServerCmd::Print {
+            text: "hello".to_owned(),
+        }
+        .serialize(&mut msg)
+        .unwrap();
+
+        // entity 3: origin x and yaw
+        msg.extend_from_slice(&[
+            FAST_UPDATE_FLAG | UpdateFlags::ORIGIN_X.bits() as u8 | UpdateFlags::YAW.bits() as u8,
+            3,
+            0x40,
+            0x01,
+            0x20,
+        ]);
+
+        let src = demo(&[msg]);
+
+        let mut upgraded = Vec::new();
+        let report = convert_demo(&mut src.as_slice(), &mut upgraded, Protocol::FitzQuake).unwrap();
+        assert_eq!(report.src_protocol(), Some(Protocol::NetQuake));
+        assert!(report.is_lossless());
+
+        let mut downgraded = Vec::new();
+        let report =
+            convert_demo(&mut upgraded.as_slice(), &mut downgraded, Protocol::NetQuake).unwrap();
+        assert_eq!(report.src_protocol(), Some(Protocol::FitzQuake));
+        assert!(report.is_lossless());
+
+        assert_eq!(src, downgraded);
+    }
+
+    #[test]
+    fn test_convert_downgrade_reports_lossy_features() {
+        let mut msg = server_info(666);
+
+        // entity 3 with alpha and a model index of 0x0102
+        let bits = UpdateFlags::MORE_BITS.bits() as u32
+            | UpdateFlags::MODEL.bits() as u32
+            | U_EXTEND_1
+            | U_ALPHA
+            | U_MODEL_2;
+        msg.extend_from_slice(&[
+            bits as u8 | FAST_UPDATE_FLAG,
+            (bits >> 8) as u8,
+            (bits >> 16) as u8,
+            3,
+            0x02,
+            0x80,
+            0x01,
+        ]);
+        msg.push(SVC_FOG);
+        msg.extend_from_slice(&[0; 6]);
+
+        let src = demo(&[msg]);
+        let mut dst = Vec::new();
+        let report = convert_demo(&mut src.as_slice(), &mut dst, Protocol::NetQuake).unwrap();
+
+        let lossy: Vec<_> = report.lossy_features().collect();
+        assert_eq!(
+            lossy,
+            vec![
+                (LossyFeature::Alpha, 1),
+                (LossyFeature::LargeModelIndex, 1),
+                (LossyFeature::Fog, 1),
+            ]
+        );
+
+        // the downgraded message must be readable by a protocol 15 client
+        let mut reader = io::BufReader::new(&dst[3 + 4 + 12..]);
+        match ServerCmd::deserialize(&mut reader).unwrap() {
+            Some(ServerCmd::ServerInfo {
+                protocol_version, ..
+            }) => assert_eq!(protocol_version, 15),
+            x => panic!("expected server info, got {:?}", x),
+        }
+
+        match ServerCmd::deserialize(&mut reader).unwrap() {
+            Some(ServerCmd::FastUpdate(update)) => {
+                assert_eq!(update.ent_id, 3);
+                assert_eq!(update.model_id, Some(0x02));
+            }
+            x => panic!("expected entity update, got {:?}", x),
+        }
+
+        assert!(ServerCmd::deserialize(&mut reader).unwrap().is_none());
+    }
+}
@@ -0,0 +1,368 @@
+pub mod convert;
+
+use std::{
+    fs::File,
+    io::{self, BufRead, ErrorKind, Read},
+    ops::Range,
+};
+
+use crate::common::{
+    net::{self, NetError, ServerCmd},
+    util::read_f32_3,
+    vfs::{Vfs, VirtualFile},
+};
+
+use arrayvec::ArrayVec;
+use byteorder::{LittleEndian, ReadBytesExt};
+use cgmath::{Deg, Vector3};
+use io::BufReader;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum DemoServerError {
+    #[error("Invalid CD track number")]
+    InvalidCdTrack,
+    #[error("No such CD track: {0}")]
+    NoSuchCdTrack(i32),
+    #[error("Message size ({0}) exceeds maximum allowed size {}", net::MAX_MESSAGE)]
+    MessageTooLong(u32),
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Network error: {0}")]
+    Net(#[from] NetError),
+}
+
+// the server info is part of the first signon message, so there's no need to look further
+const MAX_HEADER_MESSAGES: usize = 4;
+
+// reads the CD track number at the start of a demo, which is terminated by a newline
+fn read_cd_track<R>(reader: &mut R) -> Result<Option<u32>, DemoServerError>
+where
+    R: Read,
+{
+    let mut buf = ArrayVec::<[u8; 3]>::new();
+
+    for i in 0..3 {
+        match reader.read_u8()? {
+            b'\n' => break,
+            // cannot panic because we won't exceed capacity with a loop this small
+            b => buf.push(b),
+        }
+
+        if i > 1 {
+            // CD track would be more than 2 digits long, which is impossible
+            Err(DemoServerError::InvalidCdTrack)?;
+        }
+    }
+
+    let track_str = match std::str::from_utf8(&buf) {
+        Ok(s) => s,
+        Err(_) => Err(DemoServerError::InvalidCdTrack)?,
+    };
+
+    match track_str {
+        // if track is empty, default to track 0
+        "" => Ok(Some(0)),
+        s => match s.parse::<i32>() {
+            Ok(track) => match track {
+                // if track is -1, allow demo to specify tracks in messages
+                -1 => Ok(None),
+                t if t < -1 => Err(DemoServerError::InvalidCdTrack),
+                _ => Ok(Some(track as u32)),
+            },
+            Err(_) => Err(DemoServerError::InvalidCdTrack),
+        },
+    }
+}
+
+// returns the name of the map in the server info in `message`, if it has one
+fn find_map_name(message: &[u8]) -> Option<String> {
+    let mut reader = BufReader::new(message);
+
+    // stop at the first command we can't parse, since later commands can't be located
+    while let Ok(Some(cmd)) = ServerCmd::deserialize(&mut reader) {
+        if let ServerCmd::ServerInfo { model_precache, .. } = cmd {
+            // the first model is always the world model
+            let world = model_precache.first()?;
+            let name = world.trim_start_matches("maps/").trim_end_matches(".bsp");
+            return Some(name.to_owned());
+        }
+    }
+
+    None
+}
+
+/// Reads the name of the first map loaded by a demo.
+///
+/// Only the first few messages are read, so this is much cheaper than `DemoServer::new` followed
+/// by `DemoServer::map_name`.
+pub fn read_map_name<R>(reader: R) -> Result<Option<String>, DemoServerError>
+where
+    R: Read,
+{
+    let mut reader = BufReader::new(reader);
+    read_cd_track(&mut reader)?;
+
+    let mut message = Vec::new();
+    for _ in 0..MAX_HEADER_MESSAGES {
+        let msg_len = match reader.read_u32::<LittleEndian>() {
+            Ok(len) => len,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => Err(e)?,
+        };
+
+        if msg_len as usize > net::MAX_MESSAGE {
+            Err(DemoServerError::MessageTooLong(msg_len))?;
+        }
+
+        // view angles
+        read_f32_3(&mut reader)?;
+
+        message.resize(msg_len as usize, 0);
+        reader.read_exact(&mut message)?;
+
+        if let Some(name) = find_map_name(&message) {
+            return Ok(Some(name));
+        }
+    }
+
+    Ok(None)
+}
+
+struct DemoMessage {
+    view_angles: Vector3<Deg<f32>>,
+    msg_range: Range<usize>,
+}
+
+pub struct DemoMessageView<'a> {
+    view_angles: Vector3<Deg<f32>>,
+    message: &'a [u8],
+}
+
+impl<'a> DemoMessageView<'a> {
+    pub fn view_angles(&self) -> Vector3<Deg<f32>> {
+        self.view_angles
+    }
+
+    pub fn message(&self) -> &[u8] {
+        self.message
+    }
+}
+
+pub struct DemoServer {
+    track_override: Option<u32>,
+
+    // id of next message to "send"
+    message_id: usize,
+
+    messages: Vec<DemoMessage>,
+
+    // all message data
+    message_data: Vec<u8>,
+}
+
+impl DemoServer {
+    pub fn new(file: &mut VirtualFile) -> Result<DemoServer, DemoServerError> {
+        let mut dem_reader = BufReader::new(file);
+        let track_override = read_cd_track(&mut dem_reader)?;
+        // TODO: verify that track exists
+
+        let mut message_data = Vec::new();
+        let mut messages = Vec::new();
+
+        // read all messages
+        while let Ok(msg_len) = dem_reader.read_u32::<LittleEndian>() {
+            // get view angles
+            let view_angles_f32 = read_f32_3(&mut dem_reader)?;
+            let view_angles = Vector3::new(
+                Deg(view_angles_f32[0]),
+                Deg(view_angles_f32[1]),
+                Deg(view_angles_f32[2]),
+            );
+
+            // read next message
+            let msg_start = message_data.len();
+            for _ in 0..msg_len {
+                message_data.push(dem_reader.read_u8()?);
+            }
+            let msg_end = message_data.len();
+
+            messages.push(DemoMessage {
+                view_angles,
+                msg_range: msg_start..msg_end,
+            });
+        }
+
+        Ok(DemoServer {
+            track_override,
+            message_id: 0,
+            messages,
+            message_data,
+        })
+    }
+
+    /// Returns `true` if all messages in the demo have been sent.
+    pub fn finished(&self) -> bool {
+        self.message_id >= self.messages.len()
+    }
+
+    /// Returns the name of the first map loaded by the demo, if it can be determined.
+    pub fn map_name(&self) -> Option<String> {
+        self.messages
+            .iter()
+            .find_map(|msg| find_map_name(&self.message_data[msg.msg_range.clone()]))
+    }
+
+    pub fn next(&mut self) -> Option<DemoMessageView> {
+        if self.message_id >= self.messages.len() {
+            return None;
+        }
+
+        let msg = &self.messages[self.message_id];
+        self.message_id += 1;
+
+        Some(DemoMessageView {
+            view_angles: msg.view_angles,
+            message: &self.message_data[msg.msg_range.clone()],
+        })
+    }
+}
+
+/// Summary information about a demo file.
+pub struct DemoInfo {
+    path: String,
+    size: u64,
+    map_name: Option<String>,
+}
+
+impl DemoInfo {
+    /// Returns the virtual path of the demo file.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the size of the demo file in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns the name of the first map loaded by the demo, if it could be determined.
+    pub fn map_name(&self) -> Option<&str> {
+        self.map_name.as_ref().map(|s| s.as_str())
+    }
+}
+
+/// Returns information about every `.dem` file in the virtual filesystem.
+///
+/// Only the start of each demo is read, to find the map it plays on.
+pub fn find_demos(vfs: &Vfs) -> Vec<DemoInfo> {
+    vfs.glob("**.dem")
+        .into_iter()
+        .map(|entry| {
+            let map_name = match vfs.open(&entry.path) {
+                Ok(f) => match read_map_name(f) {
+                    Ok(name) => name,
+                    Err(e) => {
+                        warn!("Couldn't read demo {}: {}", entry.path, e);
+                        None
+                    }
+                },
+                Err(_) => None,
+            };
+
+            DemoInfo {
+                path: entry.path,
+                size: entry.size,
+                map_name,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::common::net::GameType;
+
+    use byteorder::WriteBytesExt;
+
+    fn cd_track(line: &[u8]) -> Result<Option<u32>, DemoServerError> {
+        read_cd_track(&mut &line[..])
+    }
+
+    fn demo(messages: &[Vec<u8>]) -> Vec<u8> {
+        let mut demo = b"-1\n".to_vec();
+        for msg in messages {
+            demo.write_u32::<LittleEndian>(msg.len() as u32).unwrap();
+            demo.extend_from_slice(&[0; 12]);
+            demo.extend_from_slice(msg);
+        }
+
+        demo
+    }
+
+    fn server_info(map: &str) -> Vec<u8> {
+        let mut msg = Vec::new();
+        ServerCmd::ServerInfo {
+            protocol_version: net::PROTOCOL_VERSION as i32,
+            max_clients: 1,
+            game_type: GameType::CoOp,
+            message: "The Slipgate Complex".to_owned(),
+            model_precache: vec![format!("maps/{}.bsp", map)],
+            sound_precache: vec!["misc/null.wav".to_owned()],
+        }
+        .serialize(&mut msg)
+        .unwrap();
+        msg
+    }
+
+    fn print(text: &str) -> Vec<u8> {
+        let mut msg = Vec::new();
+        ServerCmd::Print {
+            text: text.to_owned(),
+        }
+        .serialize(&mut msg)
+        .unwrap();
+        msg
+    }
+
+    #[test]
+    fn test_read_cd_track() {
+        assert_eq!(cd_track(b"\n").unwrap(), Some(0));
+        assert_eq!(cd_track(b"2\n").unwrap(), Some(2));
+        assert_eq!(cd_track(b"11\n").unwrap(), Some(11));
+        assert_eq!(cd_track(b"-1\n").unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_cd_track_invalid() {
+        assert!(cd_track(b"-2\n").is_err());
+        assert!(cd_track(b"100\n").is_err());
+        assert!(cd_track(b"ab\n").is_err());
+        assert!(cd_track(b"1").is_err());
+    }
+
+    #[test]
+    fn test_read_map_name() {
+        let data = demo(&[print("connecting\n"), server_info("e1m1"), print("rest")]);
+        assert_eq!(
+            read_map_name(data.as_slice()).unwrap().as_deref(),
+            Some("e1m1")
+        );
+    }
+
+    #[test]
+    fn test_read_map_name_stops_early() {
+        let mut messages = vec![print("padding"); MAX_HEADER_MESSAGES];
+        messages.push(server_info("e1m1"));
+        assert_eq!(read_map_name(demo(&messages).as_slice()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_map_name_truncated() {
+        let mut data = demo(&[print("connecting\n"), server_info("e1m1")]);
+        data.truncate(data.len() - 4);
+        assert!(read_map_name(data.as_slice()).is_err());
+    }
+}
@@ -18,6 +18,7 @@
 use std::{
     cell::{Cell, RefCell},
     collections::HashMap,
+    io::{self, Write},
     rc::Rc,
     str::FromStr,
     string::ToString,
@@ -538,6 +539,34 @@ impl GameInput {
             .insert(input.into(), target.into())
     }
 
+    /// Writes all bindings as a console script that restores them when executed.
+    pub fn write_bindings<W>(&self, writer: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let bindings = self.bindings.borrow();
+        let mut lines: Vec<String> = bindings
+            .iter()
+            .map(|(input, target)| {
+                let target_str = match target {
+                    BindTarget::Action { .. } => format!("\"{}\"", target.to_string()),
+                    // already quoted
+                    BindTarget::ConsoleInput { .. } => target.to_string(),
+                };
+
+                format!("bind \"{}\" {}", input.to_string(), target_str)
+            })
+            .collect();
+        lines.sort();
+
+        writeln!(writer, "unbindall")?;
+        for line in lines {
+            writeln!(writer, "{}", line)?;
+        }
+
+        Ok(())
+    }
+
     /// Return the `BindTarget` that `input` is bound to, or `None` if `input` is not present.
     pub fn binding<I>(&self, input: I) -> Option<BindTarget>
     where
@@ -622,6 +651,7 @@ impl GameInput {
                     &cmd_name,
                     Box::new(move |_| {
                         action_states.borrow_mut()[action as usize] = state_bool;
+                        Ok(())
                     }),
                 );
             }
@@ -632,38 +662,44 @@ impl GameInput {
         cmds.insert_or_replace(
             "bind",
             Box::new(move |args| {
-                println!("args: {}", args.len());
                 match args.len() {
                     // bind (key)
                     // queries what (key) is bound to, if anything
-                    1 => match BindInput::from_str(args[0]) {
-                        Ok(i) => match bindings.borrow().get(&i) {
-                            Some(t) => println!("\"{}\" = \"{}\"", i.to_string(), t.to_string()),
-                            None => println!("\"{}\" is not bound", i.to_string()),
-                        },
-
-                        Err(_) => println!("\"{}\" isn't a valid key", args[0]),
-                    },
-
-                    // bind (key) [command]
-                    2 => match BindInput::from_str(args[0]) {
-                        Ok(input) => {
-                            match BindTarget::from_str(args[1]) {
-                                Ok(target) => {
-                                    bindings.borrow_mut().insert(input, target);
-                                    debug!("Bound {:?} to {:?}", input, args[1]);
-                                }
-                                Err(_) => {
-                                    println!("\"{}\" isn't a valid bind target", args[1]);
-                                }
-                            };
+                    1 => {
+                        let input = BindInput::from_str(args[0])
+                            .map_err(|_| format_err!("\"{}\" isn't a valid key", args[0]))?;
+                        match bindings.borrow().get(&input) {
+                            Some(t) => {
+                                println!("\"{}\" = \"{}\"", input.to_string(), t.to_string())
+                            }
+                            None => println!("\"{}\" is not bound", input.to_string()),
                         }
+                    }
 
-                        Err(_) => println!("\"{}\" isn't a valid key", args[0]),
-                    },
+                    // bind (key) [command]
+                    2 => {
+                        let input = BindInput::from_str(args[0])
+                            .map_err(|_| format_err!("\"{}\" isn't a valid key", args[0]))?;
+                        let target = BindTarget::from_str(args[1]).map_err(|_| {
+                            format_err!("\"{}\" isn't a valid bind target", args[1])
+                        })?;
+                        bindings.borrow_mut().insert(input, target);
+                        debug!("Bound {:?} to {:?}", input, args[1]);
+                    }
 
                     _ => println!("bind [key] (command): attach a command to a key"),
                 }
+
+                Ok(())
+            }),
+        );
+
+        // complete key names for "bind"
+        cmds.insert_completer(
+            "bind",
+            Box::new(|arg_id| match arg_id {
+                0 => INPUT_NAMES.iter().map(|n| n.to_string()).collect(),
+                _ => Vec::new(),
             }),
         );
 
@@ -671,11 +707,15 @@ impl GameInput {
         let bindings = self.bindings.clone();
         cmds.insert_or_replace(
             "unbindall",
-            Box::new(move |args| match args.len() {
-                0 => {
-                    let _ = bindings.replace(HashMap::new());
+            Box::new(move |args| {
+                match args.len() {
+                    0 => {
+                        let _ = bindings.replace(HashMap::new());
+                    }
+                    _ => println!("unbindall: delete all keybindings"),
                 }
-                _ => println!("unbindall: delete all keybindings"),
+
+                Ok(())
             }),
         );
 
@@ -684,15 +724,16 @@ impl GameInput {
         cmds.insert_or_replace(
             "impulse",
             Box::new(move |args| {
-                println!("args: {}", args.len());
                 match args.len() {
                     1 => match u8::from_str(args[0]) {
                         Ok(i) => impulse.set(i),
-                        Err(_) => println!("Impulse must be a number between 0 and 255"),
+                        Err(_) => bail!("Impulse must be a number between 0 and 255"),
                     },
 
                     _ => println!("impulse [number]"),
                 }
+
+                Ok(())
             }),
         );
     }
@@ -733,4 +774,23 @@ mod test {
 
         assert_eq!(target.to_string(), "+forward");
     }
+
+    #[test]
+    fn test_write_bindings() {
+        let cmds = Rc::new(RefCell::new(CmdRegistry::new()));
+        let cvars = Rc::new(RefCell::new(crate::common::console::CvarRegistry::new()));
+        let vfs = Rc::new(crate::common::vfs::Vfs::new());
+        let console = Rc::new(RefCell::new(Console::new(cmds, cvars, vfs)));
+
+        let mut game_input = GameInput::new(console);
+        game_input.bind(Key::W, BindTarget::from_str("+forward").unwrap());
+        game_input.bind(Key::Key1, BindTarget::from_str("impulse 1").unwrap());
+
+        let mut script = Vec::new();
+        game_input.write_bindings(&mut script).unwrap();
+        assert_eq!(
+            String::from_utf8(script).unwrap(),
+            "unbindall\nbind \"1\" \"impulse 1\"\nbind \"W\" \"+forward\"\n"
+        );
+    }
 }
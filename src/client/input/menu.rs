@@ -56,7 +56,7 @@ impl MenuInput {
 
                     Key::Up => self.menu.borrow().prev()?,
                     Key::Down => self.menu.borrow().next()?,
-                    Key::Return => self.menu.borrow().activate()?,
+                    Key::Return => self.menu.borrow_mut().activate()?,
                     Key::Left => self.menu.borrow().left()?,
                     Key::Right => self.menu.borrow().right()?,
 
@@ -19,7 +19,11 @@ pub mod console;
 pub mod game;
 pub mod menu;
 
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+};
 
 use crate::{
     client::menu::Menu,
@@ -75,6 +79,12 @@ impl Input {
                 ..
             } => self.window_focused = focused,
 
+            // the console tracks modifiers even when unfocused so they're correct when it opens
+            Event::WindowEvent {
+                event: WindowEvent::ModifiersChanged(_),
+                ..
+            } => self.console_input.handle_event(event)?,
+
             _ => {
                 if self.window_focused {
                     match self.current_focus {
@@ -112,6 +122,14 @@ impl Input {
         self.game_input.bind_defaults();
     }
 
+    /// Writes all key bindings as a console script that restores them when executed.
+    pub fn write_bindings<W>(&self, writer: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.game_input.write_bindings(writer)
+    }
+
     pub fn game_input(&self) -> Option<&GameInput> {
         if let InputFocus::Game = self.current_focus {
             Some(&self.game_input)
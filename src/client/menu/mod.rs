@@ -76,6 +76,9 @@ pub struct Menu {
     items: Vec<NamedMenuItem>,
     state: Cell<MenuState>,
     view: MenuView,
+
+    // rebuilds the items each time the menu is opened
+    populate: Option<Box<dyn Fn(MenuBuilder) -> MenuBuilder>>,
 }
 
 impl Menu {
@@ -103,6 +106,19 @@ impl Menu {
         Ok(m)
     }
 
+    fn active_submenu_mut(&mut self) -> Result<&mut Menu, Error> {
+        let mut m = self;
+
+        while let MenuState::InSubMenu { index } = m.state.get() {
+            match m.items[index].item {
+                Item::Submenu(ref mut s) => m = s,
+                _ => bail!("Menu state points to invalid submenu"),
+            }
+        }
+
+        Ok(m)
+    }
+
     /// Return a reference to the parent of the active submenu of this menu.
     ///
     /// If this is the root menu, returns None.
@@ -158,18 +174,23 @@ impl Menu {
     ///
     /// If this item is a `Menu`, sets the active (sub)menu's state to
     /// `MenuState::InSubMenu` and the selected submenu's state to
-    /// `MenuState::Active`.
+    /// `MenuState::Active`. Submenus added with `add_dynamic_submenu` are
+    /// repopulated first.
     ///
     /// If this item is an `Action`, executes the function contained in the
     /// `Action`.
     ///
     /// Otherwise, this has no effect.
-    pub fn activate(&self) -> Result<(), Error> {
-        let m = self.active_submenu()?;
+    pub fn activate(&mut self) -> Result<(), Error> {
+        let m = self.active_submenu_mut()?;
 
         if let MenuState::Active { index } = m.state.get() {
             match m.items[index].item {
-                Item::Submenu(ref submenu) => {
+                Item::Submenu(ref mut submenu) => {
+                    if let Some(ref populate) = submenu.populate {
+                        submenu.items = populate(MenuBuilder::new()).into_items();
+                    }
+
                     m.state.replace(MenuState::InSubMenu { index });
                     submenu.state.replace(MenuState::Active { index: 0 });
                 }
@@ -274,6 +295,15 @@ impl MenuBuilder {
     }
 
     pub fn build(self, view: MenuView) -> Menu {
+        Menu {
+            items: self.into_items(),
+            state: Cell::new(MenuState::Active { index: 0 }),
+            view,
+            populate: None,
+        }
+    }
+
+    fn into_items(self) -> Vec<NamedMenuItem> {
         // deactivate all child menus
         for item in self.items.iter() {
             if let Item::Submenu(ref m) = item.item {
@@ -281,11 +311,7 @@ impl MenuBuilder {
             }
         }
 
-        Menu {
-            items: self.items,
-            state: Cell::new(MenuState::Active { index: 0 }),
-            view,
-        }
+        self.items
     }
 
     pub fn add_submenu<S>(mut self, name: S, submenu: Menu) -> MenuBuilder
@@ -297,6 +323,28 @@ impl MenuBuilder {
         self
     }
 
+    /// Adds a submenu whose items are added by `populate` each time it is opened.
+    ///
+    /// This is for menus whose contents can change while the game is running. `populate` must
+    /// add at least one item, and shouldn't add submenus, since the menu renderer only loads the
+    /// textures of submenus that exist when it is created.
+    pub fn add_dynamic_submenu<S>(
+        mut self,
+        name: S,
+        view: MenuView,
+        populate: Box<dyn Fn(MenuBuilder) -> MenuBuilder>,
+    ) -> MenuBuilder
+    where
+        S: AsRef<str>,
+    {
+        let mut submenu = MenuBuilder::new().build(view);
+        submenu.state.replace(MenuState::Inactive);
+        submenu.populate = Some(populate);
+        self.items
+            .push(NamedMenuItem::new(name, Item::Submenu(submenu)));
+        self
+    }
+
     pub fn add_action<S>(mut self, name: S, action: Box<dyn Fn()>) -> MenuBuilder
     where
         S: AsRef<str>,
@@ -306,6 +354,22 @@ impl MenuBuilder {
         self
     }
 
+    /// Adds an action with a line of descriptive text displayed next to its name.
+    pub fn add_action_with_detail<S>(
+        mut self,
+        name: S,
+        detail: S,
+        action: Box<dyn Fn()>,
+    ) -> MenuBuilder
+    where
+        S: AsRef<str>,
+    {
+        let mut item = NamedMenuItem::new(name, Item::Action(action));
+        item.detail = Some(detail.as_ref().to_string());
+        self.items.push(item);
+        self
+    }
+
     pub fn add_toggle<S>(mut self, name: S, init: bool, on_toggle: Box<dyn Fn(bool)>) -> MenuBuilder
     where
         S: AsRef<str>,
@@ -368,6 +432,7 @@ impl MenuBuilder {
 
 pub struct NamedMenuItem {
     name: String,
+    detail: Option<String>,
     item: Item,
 }
 
@@ -378,6 +443,7 @@ impl NamedMenuItem {
     {
         NamedMenuItem {
             name: name.as_ref().to_string(),
+            detail: None,
             item,
         }
     }
@@ -386,6 +452,10 @@ impl NamedMenuItem {
         &self.name
     }
 
+    pub fn detail(&self) -> Option<&str> {
+        self.detail.as_ref().map(|s| s.as_str())
+    }
+
     pub fn item(&self) -> &Item {
         &self.item
     }
@@ -404,6 +474,13 @@ mod test {
         }
     }
 
+    fn submenu(menu: &Menu, index: usize) -> &Menu {
+        match menu.items[index].item {
+            Item::Submenu(ref m) => m,
+            _ => unreachable!(),
+        }
+    }
+
     fn is_inactive(state: &MenuState) -> bool {
         match state {
             MenuState::Inactive => true,
@@ -454,37 +531,67 @@ mod test {
             )
             .build(view());
 
-        let m = &menu;
-        let m1 = match m.items[0].item {
-            Item::Submenu(ref m1i) => m1i,
-            _ => unreachable!(),
-        };
-        let m2 = match m.items[1].item {
-            Item::Submenu(ref m2i) => m2i,
-            _ => unreachable!(),
-        };
+        let mut m = menu;
+        let m1 = |m: &Menu| submenu(m, 0).state.get();
+        let m2 = |m: &Menu| submenu(m, 1).state.get();
 
         assert!(is_active(&m.state.get()));
-        assert!(is_inactive(&m1.state.get()));
-        assert!(is_inactive(&m2.state.get()));
+        assert!(is_inactive(&m1(&m)));
+        assert!(is_inactive(&m2(&m)));
 
         // enter m1
         m.activate().unwrap();
         assert!(is_insubmenu(&m.state.get()));
-        assert!(is_active(&m1.state.get()));
-        assert!(is_inactive(&m2.state.get()));
+        assert!(is_active(&m1(&m)));
+        assert!(is_inactive(&m2(&m)));
 
         // exit m1
         m.back().unwrap();
         assert!(is_active(&m.state.get()));
-        assert!(is_inactive(&m1.state.get()));
-        assert!(is_inactive(&m2.state.get()));
+        assert!(is_inactive(&m1(&m)));
+        assert!(is_inactive(&m2(&m)));
 
         // enter m2
         m.next().unwrap();
         m.activate().unwrap();
         assert!(is_insubmenu(&m.state.get()));
-        assert!(is_inactive(&m1.state.get()));
-        assert!(is_active(&m2.state.get()));
+        assert!(is_inactive(&m1(&m)));
+        assert!(is_active(&m2(&m)));
+    }
+
+    #[test]
+    fn test_menu_dynamic_submenu() {
+        let opened = Rc::new(Cell::new(0));
+        let opened_handle = opened.clone();
+
+        let mut m = MenuBuilder::new()
+            .add_dynamic_submenu(
+                "dynamic",
+                view(),
+                Box::new(move |builder| {
+                    opened_handle.set(opened_handle.get() + 1);
+                    (0..opened_handle.get()).fold(builder, |b, i| {
+                        b.add_action(format!("action_{}", i), Box::new(|| ()))
+                    })
+                }),
+            )
+            .build(view());
+
+        // not populated until opened
+        assert_eq!(opened.get(), 0);
+        assert!(submenu(&m, 0).items().is_empty());
+        assert!(is_inactive(&submenu(&m, 0).state.get()));
+
+        m.activate().unwrap();
+        assert_eq!(opened.get(), 1);
+        assert_eq!(submenu(&m, 0).items().len(), 1);
+        assert!(is_active(&submenu(&m, 0).state.get()));
+
+        // repopulated each time it's opened
+        m.back().unwrap();
+        m.activate().unwrap();
+        assert_eq!(opened.get(), 2);
+        let names: Vec<&str> = submenu(&m, 0).items().iter().map(|i| i.name()).collect();
+        assert_eq!(names, vec!["action_0", "action_1"]);
     }
 }
@@ -19,7 +19,7 @@
 // SOFTWARE.
 
 mod cvars;
-mod demo;
+pub mod demo;
 pub mod entity;
 pub mod input;
 pub mod menu;
@@ -33,7 +33,7 @@ pub use self::cvars::register_cvars;
 use std::{
     cell::{Cell, RefCell},
     collections::HashMap,
-    io::{BufReader, Read},
+    io::BufReader,
     net::ToSocketAddrs,
     rc::Rc,
 };
@@ -47,13 +47,15 @@ use crate::{
             MAX_STATIC_ENTITIES, MAX_TEMP_ENTITIES,
         },
         input::game::{Action, GameInput},
-        sound::{AudioSource, Channel, Listener, StaticSound},
+        sound::{capture::SoundCapture, AudioSource, Channel, Listener, StaticSound},
         trace::{TraceEntity, TraceFrame},
-        view::{IdleVars, KickVars, MouseVars, RollVars, View},
+        view::{
+            ChaseVars, FreeCamera, FreeCameraVars, IdleVars, KickVars, MouseVars, RollVars, View,
+        },
     },
     common::{
         bsp,
-        console::{CmdRegistry, Console, ConsoleError, CvarRegistry},
+        console::{CmdRegistry, Console, ConsoleError, CvarCallbackId, CvarRegistry},
         engine,
         math::Angles,
         model::{Model, ModelError, ModelFlags, ModelKind, SyncType},
@@ -119,6 +121,8 @@ pub enum ClientError {
     TooManyStaticEntities,
     #[error("No such lightmap animation: {0}")]
     NoSuchLightmapAnimation(usize),
+    #[error("BSP error: {0}")]
+    Bsp(#[from] bsp::BspError),
     #[error("Demo server error: {0}")]
     DemoServer(#[from] DemoServerError),
     #[error("Model error: {0}")]
@@ -166,13 +170,17 @@ struct ClientChannel {
 }
 
 struct Mixer {
-    audio_device: Rc<rodio::Device>,
+    // if None, sounds are not played (but may still be captured)
+    audio_device: Option<Rc<rodio::Device>>,
     // TODO: replace with an array once const type parameters are implemented
     channels: Box<[Option<ClientChannel>]>,
+
+    // if Some, all sounds are also recorded to this capture
+    capture: Option<Rc<SoundCapture>>,
 }
 
 impl Mixer {
-    pub fn new(audio_device: Rc<rodio::Device>) -> Mixer {
+    pub fn new(audio_device: Option<Rc<rodio::Device>>) -> Mixer {
         let mut channel_vec = Vec::new();
 
         for _ in 0..MAX_CHANNELS {
@@ -182,6 +190,7 @@ impl Mixer {
         Mixer {
             audio_device,
             channels: channel_vec.into_boxed_slice(),
+            capture: None,
         }
     }
 
@@ -246,6 +255,10 @@ impl Mixer {
             volume,
             attenuation,
         );
+        if let Some(ref capture) = self.capture {
+            new_channel.capture(capture, &src, ents[ent_id].origin, listener);
+        }
+
         self.channels[chan_id] = Some(ClientChannel {
             start_time: time,
             ent_id,
@@ -266,6 +279,8 @@ struct ClientState {
 
     // audio source precache
     sounds: Vec<AudioSource>,
+    // name of each precached sound, relative to sound/
+    sound_names: Vec<String>,
 
     // ambient sounds (infinite looping, static position)
     static_sounds: Vec<StaticSound>,
@@ -333,12 +348,16 @@ struct ClientState {
 
 impl ClientState {
     // TODO: add parameter for number of player slots and reserve them in entity list
-    pub fn new(vfs: Rc<Vfs>, audio_device: Rc<rodio::Device>) -> Result<ClientState, ClientError> {
+    pub fn new(
+        vfs: Rc<Vfs>,
+        audio_device: Option<Rc<rodio::Device>>,
+    ) -> Result<ClientState, ClientError> {
         Ok(ClientState {
             vfs: vfs.clone(),
             models: vec![Model::none()],
             model_names: HashMap::new(),
             sounds: vec![AudioSource::load(&vfs, "misc/null.wav")?],
+            sound_names: vec!["misc/null.wav".to_owned()],
             static_sounds: Vec::new(),
             entities: Vec::new(),
             static_entities: Vec::new(),
@@ -473,24 +492,66 @@ pub enum UpdateSource {
     Demo(DemoServer),
 }
 
+/// The camera used to view a demo.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DemoCamera {
+    /// The view recorded in the demo.
+    Recorded,
+
+    /// A free-flying spectator camera.
+    Free,
+
+    /// A camera following behind the entity with the given ID.
+    Chase(usize),
+}
+
 pub struct Client {
     vfs: Rc<Vfs>,
     cvars: Rc<RefCell<CvarRegistry>>,
     cmds: Rc<RefCell<CmdRegistry>>,
     console: Rc<RefCell<Console>>,
-    audio_device: Rc<rodio::Device>,
+    audio_device: Option<Rc<rodio::Device>>,
 
     update_src: UpdateSource,
     compose: Vec<u8>,
     signon: Rc<Cell<SignOnStage>>,
 
+    // spectator camera controls for demo playback
+    demo_camera: Rc<Cell<DemoCamera>>,
+    free_camera: Option<FreeCamera>,
+    chase_view: Option<(Vector3<f32>, Angles)>,
+
+    // userinfo updates waiting to be sent to the server
+    userinfo: Rc<RefCell<Vec<String>>>,
+    userinfo_callback: Option<CvarCallbackId>,
+
     state: ClientState,
 }
 
+/// Returns the string command that informs the server of a change to a userinfo cvar.
+fn userinfo_cmd(name: &str, value: &str) -> Option<String> {
+    match name {
+        "_cl_name" => Some(format!("name \"{}\"", value)),
+
+        // _cl_color holds the top color in the high nibble and the bottom color in the low nibble
+        "_cl_color" => {
+            let color = value.parse::<i32>().ok()?;
+            Some(format!("color {} {}", (color >> 4) & 15, color & 15))
+        }
+
+        _ => None,
+    }
+}
+
 impl Client {
     /// Implements the `reconnect` command.
-    fn cmd_reconnect(signon: Rc<Cell<SignOnStage>>) -> Box<dyn Fn(&[&str])> {
-        Box::new(move |_| signon.set(SignOnStage::Not))
+    fn cmd_reconnect(
+        signon: Rc<Cell<SignOnStage>>,
+    ) -> Box<dyn Fn(&[&str]) -> Result<(), failure::Error>> {
+        Box::new(move |_| {
+            signon.set(SignOnStage::Not);
+            Ok(())
+        })
     }
 
     pub fn play_demo<S>(
@@ -499,7 +560,7 @@ impl Client {
         cvars: Rc<RefCell<CvarRegistry>>,
         cmds: Rc<RefCell<CmdRegistry>>,
         console: Rc<RefCell<Console>>,
-        audio_device: Rc<rodio::Device>,
+        audio_device: Option<Rc<rodio::Device>>,
     ) -> Result<Client, ClientError>
     where
         S: AsRef<str>,
@@ -517,6 +578,11 @@ impl Client {
             update_src: UpdateSource::Demo(demo_server),
             compose: Vec::new(),
             signon,
+            demo_camera: Rc::new(Cell::new(DemoCamera::Recorded)),
+            free_camera: None,
+            chase_view: None,
+            userinfo: Rc::new(RefCell::new(Vec::new())),
+            userinfo_callback: None,
             state: ClientState::new(vfs.clone(), audio_device.clone())?,
         })
     }
@@ -527,7 +593,7 @@ impl Client {
         cvars: Rc<RefCell<CvarRegistry>>,
         cmds: Rc<RefCell<CmdRegistry>>,
         console: Rc<RefCell<Console>>,
-        audio_device: Rc<rodio::Device>,
+        audio_device: Option<Rc<rodio::Device>>,
     ) -> Result<Client, ClientError>
     where
         A: ToSocketAddrs,
@@ -606,6 +672,18 @@ impl Client {
         // we're done with the connection socket, so turn it into a QSocket with the new address
         let qsock = con_sock.into_qsocket(new_addr);
 
+        // commands the client doesn't recognize may be handled by the server
+        console.borrow().set_forward_to_server(true);
+
+        // keep the server up to date with our name and colors
+        let userinfo = Rc::new(RefCell::new(Vec::new()));
+        let userinfo_cb = userinfo.clone();
+        let userinfo_callback = cvars.borrow().on_notify(Box::new(move |name, value| {
+            if let Some(cmd) = userinfo_cmd(name, value) {
+                userinfo_cb.borrow_mut().push(cmd);
+            }
+        }));
+
         Ok(Client {
             vfs: vfs.clone(),
             cvars,
@@ -615,6 +693,11 @@ impl Client {
             update_src: UpdateSource::Server(qsock),
             compose: Vec::new(),
             signon,
+            demo_camera: Rc::new(Cell::new(DemoCamera::Recorded)),
+            free_camera: None,
+            chase_view: None,
+            userinfo,
+            userinfo_callback: Some(userinfo_callback),
             state: ClientState::new(vfs.clone(), audio_device.clone())?,
         })
     }
@@ -644,7 +727,25 @@ impl Client {
         frame_time: Duration,
     ) -> Result<(), ClientError> {
         if let UpdateSource::Demo(_) = self.update_src {
-            // ignore game input during demo playback
+            // game input only controls the spectator camera during demo playback
+            if self.free_camera.is_some() {
+                let vars = FreeCameraVars {
+                    cl_anglespeedkey: self.cvar_value("cl_anglespeedkey")?,
+                    cl_backspeed: self.cvar_value("cl_backspeed")?,
+                    cl_forwardspeed: self.cvar_value("cl_forwardspeed")?,
+                    cl_movespeedkey: self.cvar_value("cl_movespeedkey")?,
+                    cl_pitchspeed: self.cvar_value("cl_pitchspeed")?,
+                    cl_sidespeed: self.cvar_value("cl_sidespeed")?,
+                    cl_upspeed: self.cvar_value("cl_upspeed")?,
+                    cl_yawspeed: self.cvar_value("cl_yawspeed")?,
+                };
+                let mouse_vars = self.mouse_vars()?;
+                if let Some(ref mut free_camera) = self.free_camera {
+                    free_camera.handle_input(frame_time, game_input, vars, mouse_vars);
+                }
+            }
+
+            game_input.refresh();
             return Ok(());
         }
 
@@ -848,10 +949,8 @@ impl Client {
                 if self.state.time >= self.state.msg_times[0] {
                     let msg_view = match demo_srv.next() {
                         Some(v) => v,
-                        None => {
-                            self.disconnect();
-                            return Ok(());
-                        }
+                        // end of demo; the host decides what to play next
+                        None => return Ok(()),
                     };
 
                     let mut view_angles = msg_view.view_angles();
@@ -1123,10 +1222,7 @@ impl Client {
                     }
                 }
 
-                ServerCmd::Print { text } => {
-                    // TODO: print to in-game console
-                    println!("{}", text);
-                }
+                ServerCmd::Print { text } => self.console.borrow().print(text),
 
                 ServerCmd::ServerInfo {
                     protocol_version,
@@ -1258,14 +1354,21 @@ impl Client {
                     volume,
                     attenuation,
                 } => {
-                    self.state.static_sounds.push(StaticSound::new(
-                        &self.audio_device,
+                    let src = &self.state.sounds[sound_id as usize];
+                    let mut static_sound = StaticSound::new(
+                        self.audio_device.as_deref(),
                         origin,
-                        self.state.sounds[sound_id as usize].clone(),
+                        src.clone(),
                         volume as f32 / 255.0,
                         attenuation as f32 / 64.0,
                         &self.state.listener,
-                    ));
+                    );
+
+                    if let Some(ref capture) = self.state.mixer.capture {
+                        static_sound.capture(capture, src, &self.state.listener);
+                    }
+
+                    self.state.static_sounds.push(static_sound);
                 }
 
                 ServerCmd::TempEntity { temp_entity } => {
@@ -1397,13 +1500,13 @@ impl Client {
                 })?;
             }
             SignOnStage::ClientInfo => {
-                // TODO: fill in client info here
-                self.add_cmd(ClientCmd::StringCmd {
-                    cmd: format!("name \"{}\"\n", "UNNAMED"),
-                })?;
-                self.add_cmd(ClientCmd::StringCmd {
-                    cmd: format!("color {} {}", 0, 0),
-                })?;
+                for name in &["_cl_name", "_cl_color"] {
+                    let value = self.cvars.borrow().get(name).map_err(ClientError::Cvar)?;
+                    if let Some(cmd) = userinfo_cmd(name, &value) {
+                        self.add_cmd(ClientCmd::StringCmd { cmd })?;
+                    }
+                }
+
                 // TODO: need default spawn parameters?
                 self.add_cmd(ClientCmd::StringCmd {
                     cmd: format!("spawn {}", ""),
@@ -1436,6 +1539,7 @@ impl Client {
         sound_precache: Vec<String>,
     ) -> Result<(), ClientError> {
         let mut new_client_state = ClientState::new(self.vfs.clone(), self.audio_device.clone())?;
+        new_client_state.mixer.capture = self.state.mixer.capture.clone();
 
         // check protocol version
         if protocol_version != net::PROTOCOL_VERSION as i32 {
@@ -1450,7 +1554,15 @@ impl Client {
         for mod_name in model_precache {
             if mod_name.ends_with(".bsp") {
                 let bsp_data = self.vfs.open(&mod_name)?;
-                let (mut brush_models, _) = bsp::load(bsp_data).unwrap();
+
+                // use colored lighting if the map comes with a .lit file
+                let lit_name = format!("{}.lit", mod_name.trim_end_matches(".bsp"));
+                let lit_data = self.vfs.open(&lit_name).ok();
+                if lit_data.is_some() {
+                    debug!("Loading {}", lit_name);
+                }
+
+                let (mut brush_models, _) = bsp::load_with_lit(bsp_data, lit_data).unwrap();
                 new_client_state.models.append(&mut brush_models);
             } else if !mod_name.starts_with("*") {
                 debug!("Loading model {}", mod_name);
@@ -1475,6 +1587,7 @@ impl Client {
             new_client_state
                 .sounds
                 .push(AudioSource::load(&self.vfs, snd_name)?);
+            new_client_state.sound_names.push(snd_name.to_owned());
 
             // TODO: send keepalive message?
         }
@@ -1495,6 +1608,55 @@ impl Client {
         Ok(())
     }
 
+    /// Reloads precached models and sounds whose files have changed.
+    ///
+    /// `paths` are virtual paths as returned by `Vfs::poll_changes`. Returns the IDs of the models
+    /// which were reloaded so their renderers can be rebuilt. Brush models belong to the level
+    /// and aren't reloaded.
+    pub fn reload_assets(&mut self, paths: &[String]) -> Vec<usize> {
+        let mut reloaded = Vec::new();
+
+        for path in paths {
+            let folded = path.to_lowercase();
+
+            let model_id = self.state.models.iter().position(|m| match m.kind() {
+                ModelKind::Alias(_) | ModelKind::Sprite(_) => m.name().to_lowercase() == folded,
+                _ => false,
+            });
+
+            if let Some(id) = model_id {
+                let name = self.state.models[id].name().to_owned();
+                match Model::load(&self.vfs, &name) {
+                    Ok(model) => {
+                        info!("Reloaded {}", name);
+                        self.state.models[id] = model;
+                        reloaded.push(id);
+                    }
+                    Err(e) => warn!("Couldn't reload {}: {}", name, e),
+                }
+            }
+
+            let sound_id = self
+                .state
+                .sound_names
+                .iter()
+                .position(|n| format!("sound/{}", n).to_lowercase() == folded);
+
+            if let Some(id) = sound_id {
+                let name = &self.state.sound_names[id];
+                match AudioSource::load(&self.vfs, name) {
+                    Ok(src) => {
+                        info!("Reloaded sound/{}", name);
+                        self.state.sounds[id] = src;
+                    }
+                    Err(e) => warn!("Couldn't reload sound/{}: {}", name, e),
+                }
+            }
+        }
+
+        reloaded
+    }
+
     pub fn signon_stage(&self) -> SignOnStage {
         self.signon.get()
     }
@@ -1514,11 +1676,27 @@ impl Client {
     }
 
     pub fn view_origin(&self) -> Vector3<f32> {
+        if let Some(ref free_camera) = self.free_camera {
+            return free_camera.origin();
+        }
+
+        if let Some((origin, _)) = self.chase_view {
+            return origin;
+        }
+
         self.state.entities[self.state.view.entity_id()].origin
             + Vector3::new(0.0, 0.0, self.state.view.view_height())
     }
 
     pub fn view_angles(&self, time: Duration) -> Result<Angles, ClientError> {
+        if let Some(ref free_camera) = self.free_camera {
+            return Ok(free_camera.angles());
+        }
+
+        if let Some((_, angles)) = self.chase_view {
+            return Ok(angles);
+        }
+
         let angles = match self.update_src {
             UpdateSource::Server(_) => self.state.view.angles(
                 time,
@@ -1541,6 +1719,27 @@ impl Client {
         Ok(angles)
     }
 
+    /// Returns `true` if this client is playing a demo.
+    pub fn demo_playback(&self) -> bool {
+        match self.update_src {
+            UpdateSource::Demo(_) => true,
+            UpdateSource::Server(_) => false,
+        }
+    }
+
+    /// Returns `true` if this client is playing a demo and has reached the end of it.
+    pub fn demo_finished(&self) -> bool {
+        match self.update_src {
+            UpdateSource::Demo(ref demo_srv) => demo_srv.finished(),
+            UpdateSource::Server(_) => false,
+        }
+    }
+
+    /// Records all sounds played by this client to `capture`, or stops recording if `None`.
+    pub fn set_sound_capture(&mut self, capture: Option<Rc<SoundCapture>>) {
+        self.state.mixer.capture = capture;
+    }
+
     pub fn view_ent(&self) -> usize {
         self.state.view.entity_id()
     }
@@ -1873,6 +2072,57 @@ impl Client {
         }
     }
 
+    // switch between spectator cameras as requested by demo_freecam and demo_chase
+    fn update_demo_camera(&mut self) -> Result<(), ClientError> {
+        self.chase_view = None;
+
+        // the recorded view entity doesn't exist until the demo has signed on
+        if self.signon.get() != SignOnStage::Done {
+            return Ok(());
+        }
+
+        match self.demo_camera.get() {
+            DemoCamera::Recorded => self.free_camera = None,
+
+            DemoCamera::Free => {
+                if self.free_camera.is_none() {
+                    // start from the recorded view
+                    let origin = self.view_origin();
+                    let angles = self.view_angles(self.state.time)?;
+                    self.free_camera = Some(FreeCamera::new(origin, angles));
+                }
+            }
+
+            DemoCamera::Chase(ent_id) => {
+                self.free_camera = None;
+
+                let ent = match self.state.entities.get(ent_id) {
+                    Some(e) => e,
+                    None => {
+                        self.console
+                            .borrow()
+                            .output_mut()
+                            .println(format!("demo_chase: no entity {}", ent_id));
+                        self.demo_camera.set(DemoCamera::Recorded);
+                        return Ok(());
+                    }
+                };
+
+                let vars = ChaseVars {
+                    chase_back: self.cvar_value("chase_back")?,
+                    chase_up: self.cvar_value("chase_up")?,
+                };
+                let world = match self.state.models[1].kind() {
+                    ModelKind::Brush(ref bmodel) => bmodel.hull(0)?,
+                    _ => panic!("non-brush worldmodel"),
+                };
+                self.chase_view = Some(view::chase(&world, ent.origin, ent.angles, vars)?);
+            }
+        }
+
+        Ok(())
+    }
+
     fn view_leaf_contents(&self) -> bsp::BspLeafContents {
         match self.state.models[1].kind() {
             ModelKind::Brush(ref bmodel) => {
@@ -1965,6 +2215,10 @@ impl Client {
         // interpolate entity data
         self.relink_entities();
 
+        if let UpdateSource::Demo(_) = self.update_src {
+            self.update_demo_camera()?;
+        }
+
         // update temp entities (lightning, etc.)
         self.update_temp_entities();
 
@@ -1977,6 +2231,20 @@ impl Client {
             .update(self.state.time, frame_time, self.cvar_value("sv_gravity")?);
 
         if let UpdateSource::Server(_) = self.update_src {
+            // pass along any console commands the client couldn't handle
+            let forwarded = self.console.borrow().take_forwarded();
+            for cmd in forwarded {
+                self.add_cmd(ClientCmd::StringCmd { cmd })?;
+            }
+
+            // name and color changes are sent once the server knows who we are
+            if self.signon.get() == SignOnStage::Done {
+                let userinfo: Vec<String> = self.userinfo.borrow_mut().drain(..).collect();
+                for cmd in userinfo {
+                    self.add_cmd(ClientCmd::StringCmd { cmd })?;
+                }
+            }
+
             // respond to the server
             self.send()?;
         }
@@ -1996,6 +2264,11 @@ impl Client {
         Ok(())
     }
 
+    /// Returns the IDs of the dynamic entities visible this frame.
+    pub fn visible_entity_ids(&self) -> &[usize] {
+        &self.state.visible_entity_ids
+    }
+
     pub fn iter_visible_entities(&self) -> impl Iterator<Item = &ClientEntity> + Clone {
         self.state
             .visible_entity_ids
@@ -2014,41 +2287,60 @@ impl Client {
     }
 
     pub fn register_cmds(&self, cmds: &mut CmdRegistry) {
-        let bonus_cshift = self.state.color_shifts[ColorShiftCode::Bonus as usize].clone();
+        let demo_camera = self.demo_camera.clone();
+        let console = self.console.clone();
         cmds.insert_or_replace(
-            "bf",
+            "demo_freecam",
             Box::new(move |_| {
-                bonus_cshift.replace(ColorShift {
-                    dest_color: [215, 186, 69],
-                    percent: 50,
-                });
+                let console = console.borrow();
+                match demo_camera.get() {
+                    DemoCamera::Free => {
+                        console.output_mut().println("demo_freecam: OFF");
+                        demo_camera.set(DemoCamera::Recorded);
+                    }
+
+                    _ => {
+                        console.output_mut().println("demo_freecam: ON");
+                        demo_camera.set(DemoCamera::Free);
+                    }
+                }
+
+                Ok(())
             }),
         );
 
-        let vfs = self.vfs.clone();
+        let demo_camera = self.demo_camera.clone();
         let console = self.console.clone();
         cmds.insert_or_replace(
-            "exec",
+            "demo_chase",
             Box::new(move |args| {
                 match args.len() {
-                    // exec (filename): execute a script file
-                    1 => {
-                        let mut script_file = match vfs.open(args[0]) {
-                            Ok(s) => s,
-                            Err(e) => {
-                                println!("Couldn't exec {}: {:?}", args[0], e);
-                                return;
-                            }
-                        };
-
-                        let mut script = String::new();
-                        script_file.read_to_string(&mut script).unwrap();
-
-                        console.borrow().stuff_text(script);
+                    0 => {
+                        console.borrow().output_mut().println("demo_chase: OFF");
+                        demo_camera.set(DemoCamera::Recorded);
                     }
 
-                    _ => println!("exec (filename): execute a script file"),
+                    1 => match args[0].parse::<usize>() {
+                        Ok(ent_id) => demo_camera.set(DemoCamera::Chase(ent_id)),
+                        Err(_) => bail!("demo_chase: invalid entity number {}", args[0]),
+                    },
+
+                    _ => bail!("usage: demo_chase [entity number]"),
                 }
+
+                Ok(())
+            }),
+        );
+
+        let bonus_cshift = self.state.color_shifts[ColorShiftCode::Bonus as usize].clone();
+        cmds.insert_or_replace(
+            "bf",
+            Box::new(move |_| {
+                bonus_cshift.replace(ColorShift {
+                    dest_color: [215, 186, 69],
+                    percent: 50,
+                });
+                Ok(())
             }),
         );
     }
@@ -2376,5 +2668,96 @@ impl std::ops::Drop for Client {
     fn drop(&mut self) {
         // if this errors, it was already removed so we don't care
         let _ = self.cmds.borrow_mut().remove("reconnect");
+        self.console.borrow().set_forward_to_server(false);
+
+        if let Some(id) = self.userinfo_callback {
+            self.cvars.borrow().remove_callback(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::util::TempDir;
+
+    use std::io::Cursor;
+
+    use byteorder::{LittleEndian, WriteBytesExt};
+
+    // builds a sprite with a single frame of the given size
+    fn build_sprite(width: i32, height: i32) -> Vec<u8> {
+        let mut data = b"IDSP".to_vec();
+        data.write_i32::<LittleEndian>(1).unwrap(); // version
+        data.write_i32::<LittleEndian>(0).unwrap(); // kind
+        data.write_f32::<LittleEndian>(1.0).unwrap(); // radius
+
+        // max size, frame count, beam length, sync type, then the frame's kind, origin and size
+        for x in &[width, height, 1, 0, 0, 0, 0, 0, width, height] {
+            data.write_i32::<LittleEndian>(*x).unwrap();
+        }
+
+        data.resize(data.len() + (width * height) as usize, 0);
+        data
+    }
+
+    fn build_wav(sample_count: usize) -> Vec<u8> {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 11025,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+
+        let mut data = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut data, spec).unwrap();
+        for _ in 0..sample_count {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        data.into_inner()
+    }
+
+    #[test]
+    fn test_reload_assets() {
+        let tmp = TempDir::new();
+        tmp.write("id1/test.dem", "-1\n");
+        tmp.write("id1/progs/s_test.spr", build_sprite(2, 2));
+        tmp.write("id1/sound/misc/null.wav", build_wav(16));
+
+        let vfs = Rc::new(Vfs::with_game(tmp.path(), None).unwrap());
+        let cvars = Rc::new(RefCell::new(CvarRegistry::new()));
+        let cmds = Rc::new(RefCell::new(CmdRegistry::new()));
+        let console = Rc::new(RefCell::new(Console::new(
+            cmds.clone(),
+            cvars.clone(),
+            vfs.clone(),
+        )));
+        let mut client =
+            Client::play_demo("test.dem", vfs.clone(), cvars, cmds, console, None).unwrap();
+
+        client
+            .state
+            .models
+            .push(Model::load(&vfs, "progs/s_test.spr").unwrap());
+        assert_eq!(client.state.models[1].max(), Vector3::new(1.0, 1.0, 1.0));
+
+        // files that aren't precached are ignored
+        tmp.write("id1/progs/s_other.spr", build_sprite(2, 2));
+        let paths = vec!["progs/s_other.spr".to_owned(), "maps/e1m1.bsp".to_owned()];
+        assert!(client.reload_assets(&paths).is_empty());
+
+        tmp.write("id1/progs/s_test.spr", build_sprite(4, 6));
+        let paths = vec!["progs/S_TEST.spr".to_owned()];
+        assert_eq!(client.reload_assets(&paths), vec![1]);
+        assert_eq!(client.state.models[1].max(), Vector3::new(2.0, 2.0, 3.0));
+
+        // sounds don't need their renderers rebuilt, and broken files keep the old sound
+        let paths = vec!["sound/misc/null.wav".to_owned()];
+        tmp.write("id1/sound/misc/null.wav", build_wav(32));
+        assert!(client.reload_assets(&paths).is_empty());
+        tmp.write("id1/sound/misc/null.wav", "RIFF");
+        assert!(client.reload_assets(&paths).is_empty());
+        assert_eq!(client.state.sounds.len(), 1);
     }
 }
@@ -18,9 +18,19 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use crate::common::console::CvarRegistry;
+use crate::common::console::{CvarKind, CvarRegistry};
 
 pub fn register_cvars(cvars: &CvarRegistry) {
-    cvars.register("r_lightmap", "0").unwrap();
-    cvars.register("r_msaa_samples", "4").unwrap();
+    cvars
+        .define("r_lightmap", "0")
+        .kind(CvarKind::Bool)
+        .description("draw lightmaps only, without textures")
+        .register()
+        .unwrap();
+    cvars
+        .define("r_msaa_samples", "4")
+        .kind(CvarKind::Enum(&["2", "4"]))
+        .description("number of samples per pixel used for antialiasing")
+        .register()
+        .unwrap();
 }
@@ -102,7 +102,7 @@ const LIGHT_ATTACHMENT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8U
 
 const DIFFUSE_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
 const FULLBRIGHT_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
-const LIGHTMAP_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+const LIGHTMAP_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
 
 /// Create a `wgpu::TextureDescriptor` appropriate for the provided texture data.
 pub fn texture_descriptor<'a>(
@@ -202,7 +202,7 @@ impl<'a> TextureData<'a> {
         (match self {
             TextureData::Diffuse(_) => size_of::<[u8; 4]>(),
             TextureData::Fullbright(_) => size_of::<u8>(),
-            TextureData::Lightmap(_) => size_of::<u8>(),
+            TextureData::Lightmap(_) => size_of::<[u8; 4]>(),
         }) as u32
     }
 
@@ -402,7 +402,7 @@ impl GraphicsState {
             1,
             1,
             &TextureData::Lightmap(LightmapData {
-                lightmap: (&[0xFF][..]).into(),
+                lightmap: (&[0xFF; 4][..]).into(),
             }),
         );
         let default_lightmap_view = default_lightmap.create_default_view();
@@ -620,4 +620,13 @@ impl GraphicsState {
     pub fn gfx_wad(&self) -> &Wad {
         &self.gfx_wad
     }
+
+    /// Reloads `gfx.wad` from the virtual filesystem.
+    ///
+    /// Renderers upload the lumps they use when they're created, so they must be rebuilt to pick
+    /// up the new contents. If the new file can't be loaded, the old one is kept.
+    pub fn reload_gfx_wad(&mut self) -> Result<(), Error> {
+        self.gfx_wad = Wad::load(self.vfs.open("gfx.wad")?)?;
+        Ok(())
+    }
 }
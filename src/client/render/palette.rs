@@ -79,4 +79,36 @@ impl Palette {
             },
         )
     }
+
+    /// Translates a set of indices into a list of RGBA values using a palette embedded in a
+    /// texture.
+    ///
+    /// Embedded palettes have no fullbright colors. If `transparent` is set, index 255 is fully
+    /// transparent.
+    pub fn translate_embedded(
+        &self,
+        indices: &[u8],
+        transparent: bool,
+    ) -> (DiffuseData, FullbrightData) {
+        let mut rgba = Vec::with_capacity(indices.len() * 4);
+
+        for index in indices {
+            match *index {
+                0xFF if transparent => rgba.extend_from_slice(&[0, 0, 0, 0]),
+                i => {
+                    rgba.extend_from_slice(&self.rgb[i as usize]);
+                    rgba.push(0xFF);
+                }
+            }
+        }
+
+        (
+            DiffuseData {
+                rgba: Cow::Owned(rgba),
+            },
+            FullbrightData {
+                fullbright: Cow::Owned(vec![0; indices.len()]),
+            },
+        )
+    }
 }
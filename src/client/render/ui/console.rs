@@ -5,7 +5,7 @@ use crate::{
             layout::{Anchor, AnchorCoord, Layout, ScreenPosition, Size},
             quad::{QuadRendererCommand, QuadTexture},
         },
-        GraphicsState,
+        Extent2d, GraphicsState,
     },
     common::{console::Console, engine, wad::QPic},
 };
@@ -14,6 +14,32 @@ use chrono::Duration;
 
 const PAD_LEFT: i32 = GLYPH_WIDTH as i32;
 
+// splits a line into rows of at most `width` characters, breaking at spaces where possible
+fn wrap(text: &[char], width: usize) -> Vec<&[char]> {
+    let width = width.max(1);
+    let mut rows = Vec::new();
+    let mut rest = text;
+
+    while rest.len() > width {
+        // break at the last space that fits, or mid-word if there isn't one
+        let split = rest[..=width]
+            .iter()
+            .rposition(|c| *c == ' ')
+            .filter(|i| *i > 0)
+            .unwrap_or(width);
+        rows.push(&rest[..split]);
+        rest = &rest[split..];
+
+        // the space at the break isn't drawn
+        if rest.first() == Some(&' ') {
+            rest = &rest[1..];
+        }
+    }
+
+    rows.push(rest);
+    rows
+}
+
 pub struct ConsoleRenderer {
     conback: QuadTexture,
 }
@@ -31,6 +57,7 @@ impl ConsoleRenderer {
     pub fn generate_commands<'a>(
         &'a self,
         console: &Console,
+        display_size: Extent2d,
         time: Duration,
         quad_cmds: &mut Vec<QuadRendererCommand<'a>>,
         glyph_cmds: &mut Vec<GlyphRendererCommand>,
@@ -64,9 +91,19 @@ impl ConsoleRenderer {
             scale,
         });
 
-        // draw input line
+        // draw input line, or the query if the scrollback or history is being searched
+        let (prompt, input_text, cursor) = if let Some(query) = console.search_query() {
+            ('/', query.to_owned(), query.chars().count())
+        } else if let Some((query, found)) = console.history_search() {
+            let label = format!("(reverse-i-search)`{}'", query);
+            let cursor = label.chars().count() - 1;
+            let text = format!("{}: {}", label, found.unwrap_or_default());
+            (']', text, cursor)
+        } else {
+            (']', console.get_string(), console.cursor())
+        };
         glyph_cmds.push(GlyphRendererCommand::Glyph {
-            glyph_id: ']' as u8,
+            glyph_id: prompt as u8,
             position: ScreenPosition::Relative {
                 anchor: console_anchor,
                 x_ofs: PAD_LEFT,
@@ -75,7 +112,6 @@ impl ConsoleRenderer {
             anchor: Anchor::BOTTOM_LEFT,
             scale,
         });
-        let input_text = console.get_string();
         glyph_cmds.push(GlyphRendererCommand::Text {
             text: input_text,
             position: ScreenPosition::Relative {
@@ -92,7 +128,7 @@ impl ConsoleRenderer {
                 glyph_id: 11,
                 position: ScreenPosition::Relative {
                     anchor: console_anchor,
-                    x_ofs: PAD_LEFT + (GLYPH_WIDTH * (console.cursor() + 1)) as i32,
+                    x_ofs: PAD_LEFT + (GLYPH_WIDTH * (cursor + 1)) as i32,
                     y_ofs: 0,
                 },
                 anchor: Anchor::BOTTOM_LEFT,
@@ -100,37 +136,108 @@ impl ConsoleRenderer {
             });
         }
 
-        // draw previous output
-        for (line_id, line) in console.output().lines().enumerate() {
-            // TODO: implement scrolling
-            if line_id > 100 {
-                break;
-            }
+        // size of the output area in glyphs, leaving room for padding and the input line
+        let glyph_w = GLYPH_WIDTH as f32 * scale;
+        let glyph_h = GLYPH_HEIGHT as f32 * scale;
+        let columns = ((display_size.width as f32 / glyph_w) as usize).saturating_sub(2);
+        let rows = ((display_size.height as f32 * proportion / glyph_h) as usize).saturating_sub(1);
 
-            for (chr_id, chr) in line.iter().enumerate() {
-                let position = ScreenPosition::Relative {
-                    anchor: console_anchor,
-                    x_ofs: PAD_LEFT + (1 + chr_id * GLYPH_WIDTH) as i32,
-                    y_ofs: ((line_id + 1) * GLYPH_HEIGHT) as i32,
-                };
-
-                let c = if *chr as u32 > std::u8::MAX as u32 {
-                    warn!(
-                        "char \"{}\" (U+{:4}) cannot be displayed in the console",
-                        *chr, *chr as u32
-                    );
-                    '?'
-                } else {
-                    *chr
-                };
-
-                glyph_cmds.push(GlyphRendererCommand::Glyph {
-                    glyph_id: c as u8,
-                    position,
-                    anchor: Anchor::BOTTOM_LEFT,
+        let output = console.output();
+        let mut row_id = 0;
+
+        // mark the bottom row if scrolled back from the newest output
+        if output.scroll() > 0 && rows > 0 {
+            let marker: Vec<char> = (0..columns)
+                .map(|i| if i % 4 == 0 { '^' } else { ' ' })
+                .collect();
+            self.push_row(&marker, row_id, false, console_anchor, scale, glyph_cmds);
+            row_id += 1;
+        }
+
+        // draw previous output, newest at the bottom
+        'lines: for line in output.entries().skip(output.scroll()) {
+            let text: Vec<char> = if output.timestamps() {
+                line.timestamp()
+                    .chars()
+                    .chain(line.text().iter().cloned())
+                    .collect()
+            } else {
+                line.text().to_vec()
+            };
+
+            for row in wrap(&text, columns).into_iter().rev() {
+                if row_id >= rows {
+                    break 'lines;
+                }
+
+                self.push_row(
+                    row,
+                    row_id,
+                    line.highlight(),
+                    console_anchor,
                     scale,
-                });
+                    glyph_cmds,
+                );
+                row_id += 1;
             }
         }
     }
+
+    // draws a row of output above the input line
+    fn push_row(
+        &self,
+        row: &[char],
+        row_id: usize,
+        highlight: bool,
+        console_anchor: Anchor,
+        scale: f32,
+        glyph_cmds: &mut Vec<GlyphRendererCommand>,
+    ) {
+        for (chr_id, chr) in row.iter().enumerate() {
+            let position = ScreenPosition::Relative {
+                anchor: console_anchor,
+                x_ofs: PAD_LEFT + (1 + chr_id * GLYPH_WIDTH) as i32,
+                y_ofs: ((row_id + 1) * GLYPH_HEIGHT) as i32,
+            };
+
+            // this isn't logged, since log output is printed to the console
+            let glyph_id = if *chr as u32 > std::u8::MAX as u32 {
+                b'?'
+            } else {
+                *chr as u8
+            };
+
+            // the upper half of conchars is drawn in a different color
+            let glyph_id = if highlight { glyph_id | 0x80 } else { glyph_id };
+
+            glyph_cmds.push(GlyphRendererCommand::Glyph {
+                glyph_id,
+                position,
+                anchor: Anchor::BOTTOM_LEFT,
+                scale,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn wrap_str(text: &str, width: usize) -> Vec<String> {
+        let chars: Vec<char> = text.chars().collect();
+        wrap(&chars, width)
+            .into_iter()
+            .map(|row| row.iter().collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap_str("hello world", 5), vec!["hello", "world"]);
+        assert_eq!(wrap_str("hello world", 20), vec!["hello world"]);
+        assert_eq!(wrap_str("abcdefgh", 3), vec!["abc", "def", "gh"]);
+        assert_eq!(wrap_str("a bc def", 4), vec!["a bc", "def"]);
+        assert_eq!(wrap_str("", 4), vec![""]);
+    }
 }
@@ -272,6 +272,11 @@ impl MenuRenderer {
                     self.cmd_draw_slider(x, y, slider.position(), scale, glyph_cmds)
                 }
                 Item::TextField(_) => (),
+                Item::Action(_) => {
+                    if let Some(detail) = item.detail() {
+                        self.cmd_draw_item_text(x, y, detail, scale, glyph_cmds);
+                    }
+                }
                 _ => (),
             }
         }
@@ -133,10 +133,13 @@ impl UiRenderer {
                     self.menu_renderer
                         .generate_commands(menu, time, quad_commands, glyph_commands)
                 }
-                UiOverlay::Console(console) => {
-                    self.console_renderer
-                        .generate_commands(console, time, quad_commands, glyph_commands)
-                }
+                UiOverlay::Console(console) => self.console_renderer.generate_commands(
+                    console,
+                    target_size,
+                    time,
+                    quad_commands,
+                    glyph_commands,
+                ),
             }
         }
 
@@ -32,12 +32,12 @@ use crate::{
         pipeline::PushConstantUpdate,
         warp,
         world::{BindGroupLayoutId, WorldPipelineBase},
-        Camera, GraphicsState, LightmapData, Pipeline, TextureData,
+        Camera, GraphicsState, LightmapData, Palette, Pipeline, TextureData,
     },
     common::{
         bsp::{
-            self, BspData, BspFace, BspLeaf, BspModel, BspTexInfo, BspTexture, BspTextureKind,
-            BspTextureMipmap,
+            self, BspData, BspFace, BspLeaf, BspLightmap, BspLightmapFormat, BspModel, BspTexInfo,
+            BspTexture, BspTextureFrame, BspTextureKind, BspTextureMipmap,
         },
         math,
         util::any_slice_as_bytes,
@@ -226,6 +226,29 @@ impl Pipeline for BrushPipeline {
     }
 }
 
+// expands lightmap samples to RGBA
+fn lightmap_rgba(lightmap: &BspLightmap) -> Vec<u8> {
+    let data = lightmap.data();
+    let mut rgba = Vec::with_capacity(data.len() / lightmap.format().sample_size() * 4);
+
+    match lightmap.format() {
+        BspLightmapFormat::Mono => {
+            for l in data {
+                rgba.extend_from_slice(&[*l, *l, *l, 0xFF]);
+            }
+        }
+
+        BspLightmapFormat::Rgb => {
+            for rgb in data.chunks_exact(3) {
+                rgba.extend_from_slice(rgb);
+                rgba.push(0xFF);
+            }
+        }
+    }
+
+    rgba
+}
+
 fn calculate_lightmap_texcoords(
     position: Vector3<f32>,
     face: &BspFace,
@@ -464,7 +487,7 @@ impl BrushRendererBuilder {
         let mut lightmap_ids = Vec::new();
         for lightmap in lightmaps {
             let lightmap_data = TextureData::Lightmap(LightmapData {
-                lightmap: Cow::Borrowed(lightmap.data()),
+                lightmap: Cow::Owned(lightmap_rgba(&lightmap)),
             });
 
             let texture =
@@ -537,7 +560,7 @@ impl BrushRendererBuilder {
     fn create_brush_texture_frame<S>(
         &self,
         state: &GraphicsState,
-        mipmap: &[u8],
+        bsp_frame: &BspTextureFrame,
         width: u32,
         height: u32,
         name: S,
@@ -547,7 +570,14 @@ impl BrushRendererBuilder {
     {
         let name = name.as_ref();
 
-        let (diffuse_data, fullbright_data) = state.palette().translate(mipmap);
+        let mipmap = bsp_frame.mipmap(BspTextureMipmap::Full);
+        let (diffuse_data, fullbright_data) = match bsp_frame.palette() {
+            // textures whose names start with '{' use index 255 for transparency
+            Some(palette) => {
+                Palette::new(palette).translate_embedded(mipmap, name.starts_with('{'))
+            }
+            None => state.palette().translate(mipmap),
+        };
         let diffuse =
             state.create_texture(None, width, height, &TextureData::Diffuse(diffuse_data));
         let fullbright = state.create_texture(
@@ -597,27 +627,13 @@ impl BrushRendererBuilder {
             BspTextureKind::Animated { primary, alternate } => {
                 let primary_frames: Vec<_> = primary
                     .iter()
-                    .map(|f| {
-                        self.create_brush_texture_frame(
-                            state,
-                            f.mipmap(BspTextureMipmap::Full),
-                            width,
-                            height,
-                            tex.name(),
-                        )
-                    })
+                    .map(|f| self.create_brush_texture_frame(state, f, width, height, tex.name()))
                     .collect();
 
                 let alternate_frames: Option<Vec<_>> = alternate.as_ref().map(|a| {
                     a.iter()
                         .map(|f| {
-                            self.create_brush_texture_frame(
-                                state,
-                                f.mipmap(BspTextureMipmap::Full),
-                                width,
-                                height,
-                                tex.name(),
-                            )
+                            self.create_brush_texture_frame(state, f, width, height, tex.name())
                         })
                         .collect()
                 });
@@ -631,7 +647,7 @@ impl BrushRendererBuilder {
             BspTextureKind::Static(bsp_tex) => {
                 BrushTexture::Static(self.create_brush_texture_frame(
                     state,
-                    bsp_tex.mipmap(BspTextureMipmap::Full),
+                    bsp_tex,
                     tex.width(),
                     tex.height(),
                     tex.name(),
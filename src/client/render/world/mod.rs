@@ -323,29 +323,7 @@ impl WorldRenderer {
                     _ => panic!("Invalid worldmodel"),
                 }
             } else {
-                match *model.kind() {
-                    ModelKind::Alias(ref amodel) => entity_renderers.push(EntityRenderer::Alias(
-                        AliasRenderer::new(state, amodel).unwrap(),
-                    )),
-
-                    ModelKind::Brush(ref bmodel) => {
-                        entity_renderers.push(EntityRenderer::Brush(
-                            BrushRendererBuilder::new(bmodel, false)
-                                .build(state)
-                                .unwrap(),
-                        ));
-                    }
-
-                    ModelKind::Sprite(ref smodel) => {
-                        entity_renderers
-                            .push(EntityRenderer::Sprite(SpriteRenderer::new(&state, smodel)));
-                    }
-
-                    _ => {
-                        warn!("Non-brush renderers not implemented!");
-                        entity_renderers.push(EntityRenderer::None);
-                    }
-                }
+                entity_renderers.push(WorldRenderer::build_entity_renderer(state, model));
             }
         }
 
@@ -357,6 +335,37 @@ impl WorldRenderer {
         }
     }
 
+    fn build_entity_renderer(state: &GraphicsState, model: &Model) -> EntityRenderer {
+        match *model.kind() {
+            ModelKind::Alias(ref amodel) => {
+                EntityRenderer::Alias(AliasRenderer::new(state, amodel).unwrap())
+            }
+
+            ModelKind::Brush(ref bmodel) => EntityRenderer::Brush(
+                BrushRendererBuilder::new(bmodel, false)
+                    .build(state)
+                    .unwrap(),
+            ),
+
+            ModelKind::Sprite(ref smodel) => {
+                EntityRenderer::Sprite(SpriteRenderer::new(&state, smodel))
+            }
+
+            _ => {
+                warn!("Non-brush renderers not implemented!");
+                EntityRenderer::None
+            }
+        }
+    }
+
+    /// Rebuilds the renderer for an entity model after it has been reloaded.
+    ///
+    /// This uploads the model's new geometry and skins. The worldmodel can't be reloaded.
+    pub fn reload_model(&mut self, state: &GraphicsState, model_id: usize, model: &Model) {
+        // the worldmodel has no entity renderer, as in `renderer_for_entity`
+        self.entity_renderers[model_id - 1] = WorldRenderer::build_entity_renderer(state, model);
+    }
+
     pub fn update_uniform_buffers<'a, I>(
         &self,
         state: &GraphicsState,
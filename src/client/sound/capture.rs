@@ -0,0 +1,248 @@
+use std::{
+    cell::{Cell, RefCell},
+    path::Path,
+    rc::{Rc, Weak},
+};
+
+use crate::client::sound::{AudioSource, SoundError};
+
+use chrono::Duration;
+use hound::{SampleFormat, WavSpec, WavWriter};
+use rodio::Source;
+
+/// The sample rate of captured audio.
+pub const CAPTURE_SAMPLE_RATE: u32 = 44100;
+
+/// Playback state of a single captured sound, shared with the channel playing it.
+#[derive(Debug)]
+pub struct Voice {
+    volume: Cell<f32>,
+    finished: Cell<bool>,
+}
+
+impl Voice {
+    pub fn set_volume(&self, volume: f32) {
+        self.volume.set(volume);
+    }
+
+    /// Returns `true` if this voice has played all of its samples.
+    pub fn finished(&self) -> bool {
+        self.finished.get()
+    }
+}
+
+struct CaptureVoice {
+    voice: Weak<Voice>,
+    samples: Vec<f32>,
+    // number of source samples to advance per output sample
+    step: f64,
+    position: f64,
+    looping: bool,
+}
+
+impl CaptureVoice {
+    // adds this voice to `out`, returning false once the voice is no longer playing
+    fn mix_into(&mut self, out: &mut [f32]) -> bool {
+        let voice = match self.voice.upgrade() {
+            Some(voice) => voice,
+            None => return false,
+        };
+
+        let len = self.samples.len();
+        if len == 0 {
+            voice.finished.set(true);
+            return false;
+        }
+
+        let volume = voice.volume.get();
+        for sample in out.iter_mut() {
+            if self.position >= len as f64 {
+                if self.looping {
+                    self.position %= len as f64;
+                } else {
+                    voice.finished.set(true);
+                    return false;
+                }
+            }
+
+            // linear interpolation between neighboring source samples
+            let i = self.position as usize;
+            let frac = (self.position - i as f64) as f32;
+            let a = self.samples[i];
+            let b = match self.samples.get(i + 1) {
+                Some(b) => *b,
+                None if self.looping => self.samples[0],
+                None => 0.0,
+            };
+
+            *sample += (a + (b - a) * frac) * volume;
+            self.position += self.step;
+        }
+
+        true
+    }
+}
+
+/// Mixes game audio in software so that it can be written to a file.
+///
+/// Unlike the audio device, which plays sounds in real time, a `SoundCapture` only advances when
+/// `mix` is called. This allows audio to stay in sync with video rendered at a fixed timestep.
+pub struct SoundCapture {
+    voices: RefCell<Vec<CaptureVoice>>,
+    samples: RefCell<Vec<f32>>,
+    // total mixed time, used to avoid accumulating rounding error
+    elapsed: Cell<Duration>,
+}
+
+impl SoundCapture {
+    pub fn new() -> SoundCapture {
+        SoundCapture {
+            voices: RefCell::new(Vec::new()),
+            samples: RefCell::new(Vec::new()),
+            elapsed: Cell::new(Duration::zero()),
+        }
+    }
+
+    /// Starts capturing a sound at the current mix position.
+    ///
+    /// The sound is captured until it finishes or the returned `Voice` is dropped.
+    pub fn start(&self, src: &AudioSource, volume: f32, looping: bool) -> Rc<Voice> {
+        let channels = src.0.channels().max(1) as usize;
+        let sample_rate = src.0.sample_rate();
+
+        // downmix to mono, since spatialization only applies a single volume
+        let interleaved: Vec<f32> = src.0.clone().collect();
+        let samples = interleaved
+            .chunks(channels)
+            .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+            .collect();
+
+        let voice = Rc::new(Voice {
+            volume: Cell::new(volume),
+            finished: Cell::new(false),
+        });
+
+        self.voices.borrow_mut().push(CaptureVoice {
+            voice: Rc::downgrade(&voice),
+            samples,
+            step: sample_rate as f64 / CAPTURE_SAMPLE_RATE as f64,
+            position: 0.0,
+            looping,
+        });
+
+        voice
+    }
+
+    /// Mixes `duration` worth of audio from all active voices.
+    pub fn mix(&self, duration: Duration) {
+        let start = self.elapsed.get();
+        let end = start + duration;
+        self.elapsed.set(end);
+
+        let sample_count = sample_index(end) - sample_index(start);
+        let mut samples = self.samples.borrow_mut();
+        let offset = samples.len();
+        samples.resize(offset + sample_count, 0.0);
+        let out = &mut samples[offset..];
+
+        let mut voices = self.voices.borrow_mut();
+        let mut i = 0;
+        while i < voices.len() {
+            if voices[i].mix_into(out) {
+                i += 1;
+            } else {
+                voices.swap_remove(i);
+            }
+        }
+    }
+
+    /// Writes all audio mixed so far to a 16-bit mono WAV file.
+    pub fn write_wav<P>(&self, path: P) -> Result<(), SoundError>
+    where
+        P: AsRef<Path>,
+    {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: CAPTURE_SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+
+        let mut writer = WavWriter::create(path, spec)?;
+        for sample in self.samples.borrow().iter() {
+            let clamped = sample.max(-1.0).min(1.0);
+            writer.write_sample((clamped * std::i16::MAX as f32) as i16)?;
+        }
+        writer.finalize()?;
+
+        Ok(())
+    }
+}
+
+// index of the output sample at the given time
+fn sample_index(time: Duration) -> usize {
+    let micros = time.num_microseconds().unwrap_or(0).max(0) as u64;
+    (micros * CAPTURE_SAMPLE_RATE as u64 / 1_000_000) as usize
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_voice(looping: bool) -> (Rc<Voice>, CaptureVoice) {
+        let voice = Rc::new(Voice {
+            volume: Cell::new(0.5),
+            finished: Cell::new(false),
+        });
+
+        let capture_voice = CaptureVoice {
+            voice: Rc::downgrade(&voice),
+            samples: vec![1.0; 4],
+            step: 1.0,
+            position: 0.0,
+            looping,
+        };
+
+        (voice, capture_voice)
+    }
+
+    #[test]
+    fn test_mix_voice_finishes() {
+        let (voice, mut capture_voice) = test_voice(false);
+        let mut out = [0.0; 8];
+
+        assert!(!capture_voice.mix_into(&mut out));
+        assert!(voice.finished());
+        assert_eq!(out, [0.5, 0.5, 0.5, 0.5, 0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_mix_voice_looping() {
+        let (voice, mut capture_voice) = test_voice(true);
+        let mut out = [0.0; 8];
+
+        assert!(capture_voice.mix_into(&mut out));
+        assert!(!voice.finished());
+        assert_eq!(out, [0.5; 8]);
+    }
+
+    #[test]
+    fn test_mix_voice_dropped() {
+        let (voice, mut capture_voice) = test_voice(true);
+        let mut out = [0.0; 8];
+
+        drop(voice);
+        assert!(!capture_voice.mix_into(&mut out));
+        assert_eq!(out, [0.0; 8]);
+    }
+
+    #[test]
+    fn test_sample_index() {
+        assert_eq!(sample_index(Duration::zero()), 0);
+        assert_eq!(sample_index(Duration::seconds(1)), CAPTURE_SAMPLE_RATE as usize);
+        assert_eq!(
+            sample_index(Duration::milliseconds(500)),
+            CAPTURE_SAMPLE_RATE as usize / 2
+        );
+    }
+}
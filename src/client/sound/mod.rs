@@ -18,13 +18,18 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+pub mod capture;
+
 use std::{
     cell::{Cell, RefCell},
     io::{self, BufReader, BufWriter, Cursor, Read},
     rc::Rc,
 };
 
-use crate::common::vfs::{Vfs, VfsError};
+use crate::{
+    client::sound::capture::{SoundCapture, Voice},
+    common::vfs::{Vfs, VfsError},
+};
 
 use cgmath::{InnerSpace, Vector3};
 use hound::{WavReader, WavWriter};
@@ -156,56 +161,79 @@ impl AudioSource {
 
 pub struct StaticSound {
     origin: Vector3<f32>,
-    sink: RefCell<Sink>,
+    sink: RefCell<Option<Sink>>,
     volume: f32,
     attenuation: f32,
+    capture: Option<Rc<Voice>>,
 }
 
 impl StaticSound {
+    /// Create a new `StaticSound`. If `device` is `None`, the sound is not played.
     pub fn new(
-        device: &Device,
+        device: Option<&Device>,
         origin: Vector3<f32>,
         src: AudioSource,
         volume: f32,
         attenuation: f32,
         listener: &Listener,
     ) -> StaticSound {
-        let sink = Sink::new(device);
-        let infinite = src.0.clone().repeat_infinite();
-        sink.append(infinite);
-        sink.set_volume(listener.attenuate(origin, volume, attenuation));
+        let sink = device.map(|device| {
+            let sink = Sink::new(device);
+            let infinite = src.0.clone().repeat_infinite();
+            sink.append(infinite);
+            sink.set_volume(listener.attenuate(origin, volume, attenuation));
+            sink
+        });
 
         StaticSound {
             origin,
             sink: RefCell::new(sink),
             volume,
             attenuation,
+            capture: None,
         }
     }
 
+    /// Records this sound to `capture` in addition to playing it.
+    pub fn capture(&mut self, capture: &SoundCapture, src: &AudioSource, listener: &Listener) {
+        let volume = listener.attenuate(self.origin, self.volume, self.attenuation);
+        self.capture = Some(capture.start(src, volume, true));
+    }
+
     pub fn update(&self, listener: &Listener) {
-        let sink = self.sink.borrow_mut();
+        let volume = listener.attenuate(self.origin, self.volume, self.attenuation);
 
-        sink.set_volume(listener.attenuate(self.origin, self.volume, self.attenuation));
+        if let Some(ref sink) = *self.sink.borrow_mut() {
+            sink.set_volume(volume);
+        }
+        if let Some(ref voice) = self.capture {
+            voice.set_volume(volume);
+        }
     }
 }
 
 /// Represents a single audio channel, capable of playing one sound at a time.
 pub struct Channel {
-    device: Rc<Device>,
+    device: Option<Rc<Device>>,
     sink: RefCell<Option<Sink>>,
     master_vol: Cell<f32>,
     attenuation: Cell<f32>,
+
+    // if Some, the current sound is also being recorded to a SoundCapture
+    capture: RefCell<Option<Rc<Voice>>>,
 }
 
 impl Channel {
     /// Create a new `Channel` backed by the given `Device`.
-    pub fn new(device: Rc<Device>) -> Channel {
+    ///
+    /// If `device` is `None`, sounds on this channel are not played.
+    pub fn new(device: Option<Rc<Device>>) -> Channel {
         Channel {
             device,
             sink: RefCell::new(None),
             master_vol: Cell::new(0.0),
             attenuation: Cell::new(0.0),
+            capture: RefCell::new(None),
         }
     }
 
@@ -223,37 +251,67 @@ impl Channel {
 
         // stop the old sound
         self.sink.replace(None);
+        self.capture.replace(None);
 
         // start the new sound
-        let new_sink = Sink::new(&self.device);
-        new_sink.append(src.0);
-        new_sink.set_volume(listener.attenuate(
-            ent_pos,
-            self.master_vol.get(),
-            self.attenuation.get(),
-        ));
-
-        self.sink.replace(Some(new_sink));
-    }
-
-    pub fn update(&self, ent_pos: Vector3<f32>, listener: &Listener) {
-        if let Some(ref sink) = *self.sink.borrow_mut() {
-            // attenuate using quake coordinates since distance is the same either way
-            sink.set_volume(listener.attenuate(
+        if let Some(ref device) = self.device {
+            let new_sink = Sink::new(device);
+            new_sink.append(src.0);
+            new_sink.set_volume(listener.attenuate(
                 ent_pos,
                 self.master_vol.get(),
                 self.attenuation.get(),
             ));
+
+            self.sink.replace(Some(new_sink));
+        }
+    }
+
+    /// Records the sound currently playing on this channel to `capture`.
+    ///
+    /// This should be called immediately after `play`.
+    pub fn capture(
+        &self,
+        capture: &SoundCapture,
+        src: &AudioSource,
+        ent_pos: Vector3<f32>,
+        listener: &Listener,
+    ) {
+        let volume = listener.attenuate(ent_pos, self.master_vol.get(), self.attenuation.get());
+        self.capture.replace(Some(capture.start(src, volume, false)));
+    }
+
+    pub fn update(&self, ent_pos: Vector3<f32>, listener: &Listener) {
+        // attenuate using quake coordinates since distance is the same either way
+        let volume = listener.attenuate(ent_pos, self.master_vol.get(), self.attenuation.get());
+
+        if let Some(ref sink) = *self.sink.borrow_mut() {
+            sink.set_volume(volume);
         };
+
+        if let Some(ref voice) = *self.capture.borrow() {
+            voice.set_volume(volume);
+        }
     }
 
     /// Stop the sound currently playing on this channel, if there is one.
     pub fn stop(&self) {
         self.sink.replace(None);
+        self.capture.replace(None);
     }
 
     /// Returns whether or not this `Channel` is currently in use.
     pub fn in_use(&self) -> bool {
+        // captured sounds advance with the capture rather than in real time
+        let captured = self.capture.borrow().as_ref().map(|voice| voice.finished());
+        if let Some(finished) = captured {
+            if finished {
+                self.stop();
+            }
+
+            return !finished;
+        }
+
         let replace_sink;
         match *self.sink.borrow() {
             Some(ref sink) => replace_sink = sink.empty(),
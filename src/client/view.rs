@@ -3,6 +3,7 @@ use std::f32::consts::PI;
 use crate::{
     client::input::game::{Action, GameInput},
     common::{
+        bsp::{BspCollisionHull, BspError, BspLeafContents},
         engine::{duration_to_f32, duration_from_f32},
         math::{self, Angles},
     },
@@ -200,6 +201,182 @@ impl View {
     }
 }
 
+/// A free-flying camera for spectating during demo playback.
+///
+/// The camera moves like a player in noclip mode: it flies in the direction it is facing and
+/// passes through walls.
+pub struct FreeCamera {
+    origin: Vector3<f32>,
+
+    // only the input angles are used
+    view: View,
+}
+
+impl FreeCamera {
+    pub fn new(origin: Vector3<f32>, angles: Angles) -> FreeCamera {
+        let mut view = View::new();
+        view.update_input_angles(angles);
+
+        FreeCamera { origin, view }
+    }
+
+    pub fn origin(&self) -> Vector3<f32> {
+        self.origin
+    }
+
+    pub fn angles(&self) -> Angles {
+        self.view.input_angles()
+    }
+
+    pub fn handle_input(
+        &mut self,
+        frame_time: Duration,
+        game_input: &GameInput,
+        vars: FreeCameraVars,
+        mouse_vars: MouseVars,
+    ) {
+        // free camera always uses mouselook
+        self.view.handle_input(
+            frame_time,
+            game_input,
+            None,
+            true,
+            vars.cl_anglespeedkey,
+            vars.cl_pitchspeed,
+            vars.cl_yawspeed,
+            mouse_vars,
+        );
+
+        let factor = |action| game_input.action_state(action) as i32 as f32;
+        let mut forward_speed = vars.cl_forwardspeed * factor(Action::Forward)
+            - vars.cl_backspeed * factor(Action::Back);
+        let mut side_speed =
+            vars.cl_sidespeed * (factor(Action::MoveRight) - factor(Action::MoveLeft));
+        let mut up_speed = vars.cl_upspeed * (factor(Action::MoveUp) - factor(Action::MoveDown));
+
+        if game_input.action_state(Action::Speed) {
+            forward_speed *= vars.cl_movespeedkey;
+            side_speed *= vars.cl_movespeedkey;
+            up_speed *= vars.cl_movespeedkey;
+        }
+
+        let angles = self.view.input_angles();
+        let (sin_pitch, cos_pitch) = angles.pitch.sin_cos();
+        let (sin_yaw, cos_yaw) = angles.yaw.sin_cos();
+        let forward = Vector3::new(cos_pitch * cos_yaw, cos_pitch * sin_yaw, -sin_pitch);
+        let right = Vector3::new(sin_yaw, -cos_yaw, 0.0);
+        let up = Vector3::unit_z();
+
+        let velocity = forward * forward_speed + right * side_speed + up * up_speed;
+        self.origin += velocity * duration_to_f32(frame_time);
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct FreeCameraVars {
+    pub cl_anglespeedkey: f32,
+    pub cl_backspeed: f32,
+    pub cl_forwardspeed: f32,
+    pub cl_movespeedkey: f32,
+    pub cl_pitchspeed: f32,
+    pub cl_sidespeed: f32,
+    pub cl_upspeed: f32,
+    pub cl_yawspeed: f32,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ChaseVars {
+    pub chase_back: f32,
+    pub chase_up: f32,
+}
+
+// how far the chase camera stays from walls
+const CHASE_WALL_DIST: f32 = 4.0;
+
+// how far past a contents boundary to step when checking what lies beyond it
+const CHASE_TRACE_STEP: f32 = 0.125;
+
+// maximum number of non-solid boundaries (e.g. water surfaces) to trace through
+const MAX_CHASE_TRACES: usize = 16;
+
+/// Calculates the origin and angles of a camera following behind an entity.
+///
+/// The camera is traced from `target_origin` against `world` and pulled in if it would end up
+/// inside or behind a wall.
+pub fn chase(
+    world: &BspCollisionHull,
+    target_origin: Vector3<f32>,
+    target_angles: Vector3<Deg<f32>>,
+    vars: ChaseVars,
+) -> Result<(Vector3<f32>, Angles), BspError> {
+    let (sin_yaw, cos_yaw) = target_angles.y.sin_cos();
+    let behind = -Vector3::new(cos_yaw, sin_yaw, 0.0);
+    let ideal = target_origin + behind * vars.chase_back + Vector3::unit_z() * vars.chase_up;
+
+    let hit = trace_solid(world, target_origin, ideal)?;
+    let origin = if hit == ideal {
+        ideal
+    } else {
+        // stop short of the wall so the near plane doesn't clip into it
+        let offset = hit - target_origin;
+        let dist = offset.magnitude();
+        if dist > CHASE_WALL_DIST {
+            target_origin + offset * ((dist - CHASE_WALL_DIST) / dist)
+        } else {
+            target_origin
+        }
+    };
+
+    // look down at the target as if the camera were at its ideal position, so the angle doesn't
+    // change when the camera is pulled in
+    let to_target = target_origin - ideal;
+    let pitch = Deg::atan2(-to_target.z, to_target.truncate().magnitude());
+
+    let angles = Angles {
+        pitch,
+        roll: Deg(0.0),
+        yaw: target_angles.y,
+    };
+
+    Ok((origin, angles))
+}
+
+/// Traces from `start` to `end` and returns the first point at which the line enters a solid.
+///
+/// Hull traces stop at any change in contents, so boundaries into liquids are stepped through.
+fn trace_solid(
+    hull: &BspCollisionHull,
+    start: Vector3<f32>,
+    end: Vector3<f32>,
+) -> Result<Vector3<f32>, BspError> {
+    let len = (end - start).magnitude();
+    if len == 0.0 {
+        return Ok(end);
+    }
+    let dir = (end - start) / len;
+
+    let mut from = start;
+    for _ in 0..MAX_CHASE_TRACES {
+        let point = hull.trace(from, end)?.end_point();
+        if point == end {
+            return Ok(end);
+        }
+
+        let past = point + dir * CHASE_TRACE_STEP;
+        if hull.contents_at_point(past)? == BspLeafContents::Solid {
+            return Ok(point);
+        }
+
+        if (past - start).magnitude() >= len {
+            return Ok(end);
+        }
+
+        from = past;
+    }
+
+    Ok(from)
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct MouseVars {
     pub m_pitch: f32,
@@ -277,3 +454,205 @@ pub fn idle(time: Duration, vars: IdleVars) -> Angles {
 
     Angles { pitch, roll, yaw }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::{
+        console::{CmdRegistry, Console, CvarRegistry},
+        vfs::Vfs,
+    };
+
+    use std::{cell::RefCell, rc::Rc};
+
+    const CHASE_VARS: ChaseVars = ChaseVars {
+        chase_back: 100.0,
+        chase_up: 16.0,
+    };
+
+    const FREE_CAMERA_VARS: FreeCameraVars = FreeCameraVars {
+        cl_anglespeedkey: 1.5,
+        cl_backspeed: 200.0,
+        cl_forwardspeed: 200.0,
+        cl_movespeedkey: 2.0,
+        cl_pitchspeed: 150.0,
+        cl_sidespeed: 350.0,
+        cl_upspeed: 200.0,
+        cl_yawspeed: 140.0,
+    };
+
+    // no mouse movement is simulated, but zero it anyway so the camera can't turn
+    const MOUSE_VARS: MouseVars = MouseVars {
+        m_pitch: 0.0,
+        m_yaw: 0.0,
+        sensitivity: 0.0,
+    };
+
+    fn assert_near(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!((a - b).magnitude() < 1e-3, "{:?} != {:?}", a, b);
+    }
+
+    // a world whose only solid is a box well away from the test entities
+    fn empty_world() -> BspCollisionHull {
+        BspCollisionHull::for_bounds(
+            Vector3::new(1000.0, 1000.0, 1000.0),
+            Vector3::new(1010.0, 1010.0, 1010.0),
+        )
+        .unwrap()
+    }
+
+    fn angles(pitch: f32, yaw: f32) -> Angles {
+        Angles {
+            pitch: Deg(pitch),
+            roll: Deg(0.0),
+            yaw: Deg(yaw),
+        }
+    }
+
+    fn game_input() -> (Rc<RefCell<CmdRegistry>>, GameInput) {
+        let cmds = Rc::new(RefCell::new(CmdRegistry::new()));
+        let cvars = Rc::new(RefCell::new(CvarRegistry::new()));
+        let vfs = Rc::new(Vfs::new());
+        let console = Rc::new(RefCell::new(Console::new(cmds.clone(), cvars, vfs)));
+
+        let game_input = GameInput::new(console);
+        game_input.register_cmds(&mut cmds.borrow_mut());
+        (cmds, game_input)
+    }
+
+    #[test]
+    fn test_chase_offset() {
+        let world = empty_world();
+        let target = Vector3::new(10.0, 20.0, 30.0);
+
+        let (origin, angles) = chase(
+            &world,
+            target,
+            Vector3::new(Deg(0.0), Deg(0.0), Deg(0.0)),
+            CHASE_VARS,
+        )
+        .unwrap();
+        assert_near(origin, Vector3::new(-90.0, 20.0, 46.0));
+        assert!((angles.pitch.0 - 16.0f32.atan2(100.0).to_degrees()).abs() < 1e-3);
+        assert_eq!(angles.yaw, Deg(0.0));
+
+        // facing +y puts the camera on the -y side
+        let (origin, angles) = chase(
+            &world,
+            target,
+            Vector3::new(Deg(0.0), Deg(90.0), Deg(0.0)),
+            CHASE_VARS,
+        )
+        .unwrap();
+        assert_near(origin, Vector3::new(10.0, -80.0, 46.0));
+        assert_eq!(angles.yaw, Deg(90.0));
+    }
+
+    #[test]
+    fn test_chase_wall() {
+        // a wall between the target and the ideal camera position
+        let world = BspCollisionHull::for_bounds(
+            Vector3::new(-60.0, -1000.0, -1000.0),
+            Vector3::new(-50.0, 1000.0, 1000.0),
+        )
+        .unwrap();
+        let target = Vector3::new(0.0, 0.0, 0.0);
+
+        let (origin, angles) = chase(
+            &world,
+            target,
+            Vector3::new(Deg(0.0), Deg(0.0), Deg(0.0)),
+            CHASE_VARS,
+        )
+        .unwrap();
+
+        // the line to (-100, 0, 16) enters the wall halfway
+        let hit = Vector3::new(-50.0f32, 0.0, 8.0);
+        let dist = hit.magnitude();
+        assert_near(origin, hit * ((dist - CHASE_WALL_DIST) / dist));
+
+        // pulling the camera in doesn't change where it looks
+        assert!((angles.pitch.0 - 16.0f32.atan2(100.0).to_degrees()).abs() < 1e-3);
+
+        // a target pressed against the wall keeps the camera at the target
+        let target = Vector3::new(-48.0, 0.0, 0.0);
+        let (origin, _) = chase(
+            &world,
+            target,
+            Vector3::new(Deg(0.0), Deg(0.0), Deg(0.0)),
+            CHASE_VARS,
+        )
+        .unwrap();
+        assert_near(origin, target);
+    }
+
+    #[test]
+    fn test_free_camera_move() {
+        let (cmds, game_input) = game_input();
+        let mut camera = FreeCamera::new(Vector3::new(0.0, 0.0, 0.0), angles(0.0, 90.0));
+
+        // standing still
+        camera.handle_input(
+            Duration::seconds(1),
+            &game_input,
+            FREE_CAMERA_VARS,
+            MOUSE_VARS,
+        );
+        assert_near(camera.origin(), Vector3::new(0.0, 0.0, 0.0));
+
+        // facing +y, so right is +x
+        cmds.borrow().exec("+forward", &[]).unwrap();
+        cmds.borrow().exec("+moveright", &[]).unwrap();
+        camera.handle_input(
+            Duration::milliseconds(500),
+            &game_input,
+            FREE_CAMERA_VARS,
+            MOUSE_VARS,
+        );
+        assert_near(camera.origin(), Vector3::new(175.0, 100.0, 0.0));
+        assert_eq!(camera.angles().yaw, Deg(90.0));
+
+        cmds.borrow().exec("-moveright", &[]).unwrap();
+        cmds.borrow().exec("+speed", &[]).unwrap();
+        cmds.borrow().exec("+moveup", &[]).unwrap();
+        camera.handle_input(
+            Duration::milliseconds(500),
+            &game_input,
+            FREE_CAMERA_VARS,
+            MOUSE_VARS,
+        );
+        assert_near(camera.origin(), Vector3::new(175.0, 300.0, 200.0));
+    }
+
+    #[test]
+    fn test_free_camera_pitch() {
+        let (cmds, game_input) = game_input();
+
+        // positive pitch looks down, clamped to 80 degrees
+        let mut camera = FreeCamera::new(Vector3::new(0.0, 0.0, 0.0), angles(90.0, 0.0));
+        cmds.borrow().exec("+forward", &[]).unwrap();
+        camera.handle_input(
+            Duration::seconds(1),
+            &game_input,
+            FREE_CAMERA_VARS,
+            MOUSE_VARS,
+        );
+        assert_eq!(camera.angles().pitch, Deg(80.0));
+
+        let pitch = 80.0f32.to_radians();
+        assert_near(
+            camera.origin(),
+            Vector3::new(200.0 * pitch.cos(), 0.0, -200.0 * pitch.sin()),
+        );
+
+        cmds.borrow().exec("-forward", &[]).unwrap();
+        cmds.borrow().exec("+back", &[]).unwrap();
+        camera.handle_input(
+            Duration::seconds(1),
+            &game_input,
+            FREE_CAMERA_VARS,
+            MOUSE_VARS,
+        );
+        assert_near(camera.origin(), Vector3::new(0.0, 0.0, 0.0));
+    }
+}
@@ -25,9 +25,9 @@ use std::{
 use crate::common::{
     bsp::{
         BspCollisionHull, BspCollisionNode, BspCollisionNodeChild, BspData, BspEdge,
-        BspEdgeDirection, BspEdgeIndex, BspFace, BspFaceSide, BspLeaf, BspLeafContents, BspModel,
-        BspRenderNode, BspRenderNodeChild, BspTexInfo, BspTexture, MAX_HULLS, MAX_LIGHTSTYLES,
-        MIPLEVELS,
+        BspEdgeDirection, BspEdgeIndex, BspFace, BspFaceSide, BspHullLayout, BspLeaf,
+        BspLeafContents, BspLightmapFormat, BspModel, BspRenderNode, BspRenderNodeChild,
+        BspTexInfo, BspTexture, MAX_HULLS, MAX_LIGHTSTYLES, MIPLEVELS,
     },
     math::{Axis, Hyperplane},
     model::Model,
@@ -43,6 +43,11 @@ use num::FromPrimitive;
 use thiserror::Error;
 
 const VERSION: i32 = 29;
+const BSP30_VERSION: i32 = 30;
+const LIT_MAGIC: [u8; 4] = *b"QLIT";
+const LIT_VERSION: i32 = 1;
+const BSP2_MAGIC: [u8; 4] = *b"BSP2";
+const BSP2_RMQ_MAGIC: [u8; 4] = *b"2PSB";
 
 pub const MAX_MODELS: usize = 256;
 const MAX_LEAVES: usize = 32767;
@@ -78,24 +83,34 @@ const ASCII_SMALL_J: usize = 'j' as usize;
 pub enum BspFileError {
     #[error("I/O error")]
     Io(#[from] std::io::Error),
-    #[error("unsupported BSP format version (expected {}, found {0})", VERSION)]
+    #[error(
+        "unsupported BSP format version (expected {}, {}, BSP2 or 2PSB, found {0})",
+        VERSION,
+        BSP30_VERSION
+    )]
     UnsupportedVersion(i32),
     #[error("negative BSP file section offset: {0}")]
     NegativeSectionOffset(i32),
     #[error("negative BSP file section size: {0}")]
     NegativeSectionSize(i32),
     #[error(
-        "invalid BSP file section size: section {section:?} size is {size}, must be multiple of {}",
-        section.element_size(),
+        "invalid BSP file section size: section {section:?} size is {size}, must be multiple of {element_size}"
     )]
     InvalidSectionSize {
         section: BspFileSectionId,
         size: usize,
+        element_size: usize,
     },
     #[error("invalid BSP texture frame specifier: {0}")]
     InvalidTextureFrameSpecifier(String),
     #[error("texture has primary animation with 0 frames: {0}")]
     EmptyPrimaryAnimation(String),
+    #[error("invalid .lit file header")]
+    InvalidLitHeader,
+    #[error("unsupported .lit file version (expected {}, found {0})", LIT_VERSION)]
+    UnsupportedLitVersion(i32),
+    #[error("invalid .lit file size: expected {expected} bytes of lightmap data, found {found}")]
+    InvalidLitSize { expected: usize, found: usize },
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -155,36 +170,143 @@ const EDGELIST_SIZE: usize = 4;
 const MODEL_SIZE: usize = 64;
 const VERTEX_SIZE: usize = 12;
 
+// element sizes of the sections which are wider in the BSP2 formats
+const BSP2_RMQ_RENDER_NODE_SIZE: usize = 32;
+const BSP2_RENDER_NODE_SIZE: usize = 44;
+const BSP2_RMQ_LEAF_SIZE: usize = 32;
+const BSP2_LEAF_SIZE: usize = 44;
+const BSP2_FACE_SIZE: usize = 28;
+const BSP2_COLLISION_NODE_SIZE: usize = 12;
+const BSP2_FACELIST_SIZE: usize = 4;
+const BSP2_EDGE_SIZE: usize = 8;
+
+// mins and maxs of collision hulls 1 to 3
+const QUAKE_HULL_BOUNDS: [([f32; 3], [f32; 3]); 3] = [
+    ([-16.0, -16.0, -24.0], [16.0, 16.0, 32.0]),
+    ([-32.0, -32.0, -24.0], [32.0, 32.0, 64.0]),
+    // never used
+    ([0.0, 0.0, 0.0], [0.0, 0.0, 0.0]),
+];
+const GOLDSRC_HULL_BOUNDS: [([f32; 3], [f32; 3]); 3] = [
+    ([-16.0, -16.0, -36.0], [16.0, 16.0, 36.0]),
+    ([-32.0, -32.0, -32.0], [32.0, 32.0, 32.0]),
+    ([-16.0, -16.0, -18.0], [16.0, 16.0, 18.0]),
+];
+
+/// The on-disk layout of a BSP file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum BspFormat {
+    /// The original format (version 29), with 16-bit indices and bounds.
+    Bsp29,
+
+    /// The GoldSrc format (version 30). Its layout matches version 29, but textures carry their
+    /// own palettes and lightmaps are RGB.
+    Bsp30,
+
+    /// The `2PSB` format, with 32-bit indices but 16-bit bounds.
+    Bsp2Rmq,
+
+    /// The `BSP2` format, with 32-bit indices and floating-point bounds.
+    Bsp2,
+}
+
+impl BspFormat {
+    fn from_header(header: [u8; 4]) -> Result<BspFormat, BspFileError> {
+        match header {
+            BSP2_MAGIC => Ok(BspFormat::Bsp2),
+            BSP2_RMQ_MAGIC => Ok(BspFormat::Bsp2Rmq),
+            h => match i32::from_le_bytes(h) {
+                VERSION => Ok(BspFormat::Bsp29),
+                BSP30_VERSION => Ok(BspFormat::Bsp30),
+                other => Err(BspFileError::UnsupportedVersion(other)),
+            },
+        }
+    }
+
+    // whether the format uses 32-bit indices
+    fn is_bsp2(self) -> bool {
+        match self {
+            BspFormat::Bsp29 | BspFormat::Bsp30 => false,
+            BspFormat::Bsp2Rmq | BspFormat::Bsp2 => true,
+        }
+    }
+
+    // reads a signed value which is 16 bits wide in BSP29 and 32 bits wide in BSP2
+    fn read_short<R>(self, reader: &mut R) -> Result<i32, std::io::Error>
+    where
+        R: ReadBytesExt,
+    {
+        match self.is_bsp2() {
+            false => Ok(reader.read_i16::<LittleEndian>()? as i32),
+            true => reader.read_i32::<LittleEndian>(),
+        }
+    }
+
+    // reads an unsigned value which is 16 bits wide in BSP29 and 32 bits wide in BSP2
+    fn read_ushort<R>(self, reader: &mut R) -> Result<u32, std::io::Error>
+    where
+        R: ReadBytesExt,
+    {
+        match self.is_bsp2() {
+            false => Ok(reader.read_u16::<LittleEndian>()? as u32),
+            true => reader.read_u32::<LittleEndian>(),
+        }
+    }
+
+    // reads the bounding box corner of a node or leaf
+    fn read_bounds<R>(self, reader: &mut R) -> Result<[f32; 3], std::io::Error>
+    where
+        R: ReadBytesExt,
+    {
+        match self {
+            BspFormat::Bsp2 => read_f32_3(reader),
+            _ => {
+                let b = read_i16_3(reader)?;
+                Ok([b[0] as f32, b[1] as f32, b[2] as f32])
+            }
+        }
+    }
+}
+
 impl BspFileSectionId {
     // the size on disk of one element of a BSP file section.
-    fn element_size(&self) -> usize {
+    fn element_size(&self, format: BspFormat) -> usize {
         use BspFileSectionId::*;
-        match self {
-            Entities => size_of::<u8>(),
-            Planes => PLANE_SIZE,
-            Textures => size_of::<u8>(),
-            Vertices => VERTEX_SIZE,
-            Visibility => size_of::<u8>(),
-            RenderNodes => RENDER_NODE_SIZE,
-            TextureInfo => TEXTURE_INFO_SIZE,
-            Faces => FACE_SIZE,
-            Lightmaps => size_of::<u8>(),
-            CollisionNodes => COLLISION_NODE_SIZE,
-            Leaves => LEAF_SIZE,
-            FaceList => FACELIST_SIZE,
-            Edges => EDGE_SIZE,
-            EdgeList => EDGELIST_SIZE,
-            Models => MODEL_SIZE,
+        match (self, format) {
+            (Entities, _) => size_of::<u8>(),
+            (Planes, _) => PLANE_SIZE,
+            (Textures, _) => size_of::<u8>(),
+            (Vertices, _) => VERTEX_SIZE,
+            (Visibility, _) => size_of::<u8>(),
+            (RenderNodes, BspFormat::Bsp2Rmq) => BSP2_RMQ_RENDER_NODE_SIZE,
+            (RenderNodes, BspFormat::Bsp2) => BSP2_RENDER_NODE_SIZE,
+            (RenderNodes, _) => RENDER_NODE_SIZE,
+            (TextureInfo, _) => TEXTURE_INFO_SIZE,
+            (Faces, f) if f.is_bsp2() => BSP2_FACE_SIZE,
+            (Faces, _) => FACE_SIZE,
+            (Lightmaps, _) => size_of::<u8>(),
+            (CollisionNodes, f) if f.is_bsp2() => BSP2_COLLISION_NODE_SIZE,
+            (CollisionNodes, _) => COLLISION_NODE_SIZE,
+            (Leaves, BspFormat::Bsp2Rmq) => BSP2_RMQ_LEAF_SIZE,
+            (Leaves, BspFormat::Bsp2) => BSP2_LEAF_SIZE,
+            (Leaves, _) => LEAF_SIZE,
+            (FaceList, f) if f.is_bsp2() => BSP2_FACELIST_SIZE,
+            (FaceList, _) => FACELIST_SIZE,
+            (Edges, f) if f.is_bsp2() => BSP2_EDGE_SIZE,
+            (Edges, _) => EDGE_SIZE,
+            (EdgeList, _) => EDGELIST_SIZE,
+            (Models, _) => MODEL_SIZE,
         }
     }
 }
 
 struct BspFileTable {
+    format: BspFormat,
     sections: [BspFileSection; SECTION_COUNT],
 }
 
 impl BspFileTable {
-    fn read_from<R>(reader: &mut R) -> Result<BspFileTable, BspFileError>
+    fn read_from<R>(reader: &mut R, format: BspFormat) -> Result<BspFileTable, BspFileError>
     where
         R: ReadBytesExt,
     {
@@ -193,21 +315,29 @@ impl BspFileTable {
         for (id, section) in sections.iter_mut().enumerate() {
             *section = BspFileSection::read_from(reader)?;
             let section_id = BspFileSectionId::from_usize(id).unwrap();
-            if section.size % section_id.element_size() != 0 {
+            let element_size = section_id.element_size(format);
+            if section.size % element_size != 0 {
                 Err(BspFileError::InvalidSectionSize {
                     section: section_id,
                     size: section.size,
+                    element_size,
                 })?
             }
         }
 
-        Ok(BspFileTable { sections })
+        Ok(BspFileTable { format, sections })
     }
 
     fn section(&self, section_id: BspFileSectionId) -> BspFileSection {
         self.sections[section_id as usize]
     }
 
+    // the number of elements in a section
+    fn count(&self, section_id: BspFileSectionId) -> usize {
+        let element_size = section_id.element_size(self.format);
+        self.section(section_id).size / element_size
+    }
+
     fn check_end_position<S>(
         &self,
         seeker: &mut S,
@@ -251,6 +381,7 @@ struct BspFileTexture {
     width: u32,
     height: u32,
     mipmaps: [Vec<u8>; MIPLEVELS],
+    palette: Option<Box<[u8]>>,
 }
 
 // load a textures from the BSP file.
@@ -261,6 +392,7 @@ fn load_texture<R>(
     mut reader: &mut R,
     tex_section_ofs: u64,
     tex_ofs: u64,
+    format: BspFormat,
 ) -> Result<BspFileTexture, failure::Error>
 where
     R: ReadBytesExt + Seek,
@@ -284,6 +416,12 @@ where
         mip_offsets[m] = reader.read_u32::<LittleEndian>()? as usize;
     }
 
+    // version 30 textures with no mip offsets are stored in an external WAD
+    if format == BspFormat::Bsp30 && mip_offsets[0] == 0 {
+        warn!("Texture {} is not embedded in the map", tex_name);
+        return Ok(missing_texture(tex_name, width, height));
+    }
+
     let mut mipmaps = [Vec::new(), Vec::new(), Vec::new(), Vec::new()];
     for m in 0..MIPLEVELS {
         let factor = 2usize.pow(m as u32);
@@ -295,15 +433,60 @@ where
             .read_to_end(&mut mipmaps[m])?;
     }
 
+    // version 30 textures are followed by a color count and their palette
+    let palette = match format {
+        BspFormat::Bsp30 => {
+            let color_count = reader.read_u16::<LittleEndian>()? as usize;
+            ensure!(
+                color_count <= 256,
+                "Invalid palette size for texture {} ({})",
+                tex_name,
+                color_count
+            );
+
+            let mut palette = vec![0u8; 256 * 3];
+            reader.read_exact(&mut palette[..color_count * 3])?;
+            Some(palette.into_boxed_slice())
+        }
+        _ => None,
+    };
+
     Ok(BspFileTexture {
         name: tex_name,
         width,
         height,
         mipmaps,
+        palette,
     })
 }
 
-fn load_render_node<R>(reader: &mut R) -> Result<BspRenderNode, failure::Error>
+// builds a black and magenta checkerboard to stand in for a texture that can't be loaded
+fn missing_texture(name: String, width: u32, height: u32) -> BspFileTexture {
+    let mut mipmaps = [Vec::new(), Vec::new(), Vec::new(), Vec::new()];
+    for m in 0..MIPLEVELS {
+        let factor = 2u32.pow(m as u32);
+        let (w, h) = (width / factor, height / factor);
+        let check = (8 / factor).max(1);
+        for y in 0..h {
+            for x in 0..w {
+                mipmaps[m].push(((x / check + y / check) % 2) as u8);
+            }
+        }
+    }
+
+    let mut palette = vec![0u8; 256 * 3];
+    palette[3..6].copy_from_slice(&[0xFF, 0x00, 0xFF]);
+
+    BspFileTexture {
+        name,
+        width,
+        height,
+        mipmaps,
+        palette: Some(palette.into_boxed_slice()),
+    }
+}
+
+fn load_render_node<R>(reader: &mut R, format: BspFormat) -> Result<BspRenderNode, failure::Error>
 where
     R: ReadBytesExt,
 {
@@ -315,26 +498,22 @@ where
     // If the child ID is positive, it points to another internal node. If it is negative, its
     // bitwise negation points to a leaf node.
 
-    let front = match reader.read_i16::<LittleEndian>()? {
+    let front = match format.read_short(reader)? {
         f if f < 0 => BspRenderNodeChild::Leaf((!f) as usize),
         f => BspRenderNodeChild::Node(f as usize),
     };
 
-    let back = match reader.read_i16::<LittleEndian>()? {
+    let back = match format.read_short(reader)? {
         b if b < 0 => BspRenderNodeChild::Leaf((!b) as usize),
         b => BspRenderNodeChild::Node(b as usize),
     };
 
-    let min = read_i16_3(reader)?;
-    let max = read_i16_3(reader)?;
+    let min = format.read_bounds(reader)?;
+    let max = format.read_bounds(reader)?;
 
-    let face_id = reader.read_i16::<LittleEndian>()?;
-    if face_id < 0 {
-        bail!("Invalid face id");
-    }
-
-    let face_count = reader.read_u16::<LittleEndian>()?;
-    if face_count as usize > MAX_FACES {
+    let face_id = format.read_ushort(reader)?;
+    let face_count = format.read_ushort(reader)?;
+    if !format.is_bsp2() && face_count as usize > MAX_FACES {
         bail!("Invalid face count");
     }
 
@@ -379,20 +558,62 @@ where
     })
 }
 
+// reads the RGB lightmaps from a .lit file. mono_size is the size of the BSP file's lightmap
+// section, which must have one sample for each RGB sample in the .lit file.
+fn load_lit<L>(lit: L, mono_size: usize) -> Result<Vec<u8>, BspFileError>
+where
+    L: Read,
+{
+    let mut reader = BufReader::new(lit);
+
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != LIT_MAGIC {
+        return Err(BspFileError::InvalidLitHeader);
+    }
+
+    match reader.read_i32::<LittleEndian>()? {
+        LIT_VERSION => (),
+        other => return Err(BspFileError::UnsupportedLitVersion(other)),
+    }
+
+    let mut lightmaps = Vec::with_capacity(mono_size * 3);
+    reader.read_to_end(&mut lightmaps)?;
+    if lightmaps.len() != mono_size * 3 {
+        return Err(BspFileError::InvalidLitSize {
+            expected: mono_size * 3,
+            found: lightmaps.len(),
+        });
+    }
+
+    Ok(lightmaps)
+}
+
 /// Load a BSP file, returning the models it contains and a `String` describing the entities
 /// it contains.
 pub fn load<R>(data: R) -> Result<(Vec<Model>, String), failure::Error>
 where
     R: Read + Seek,
+{
+    load_with_lit::<R, R>(data, None)
+}
+
+/// Loads a BSP file, using the RGB lightmaps from the `.lit` file `lit` if one is given.
+///
+/// If the `.lit` file doesn't match the map, it's ignored and the map's own lightmaps are used.
+pub fn load_with_lit<R, L>(data: R, lit: Option<L>) -> Result<(Vec<Model>, String), failure::Error>
+where
+    R: Read + Seek,
+    L: Read,
 {
     let mut reader = BufReader::new(data);
 
-    let _version = match reader.read_i32::<LittleEndian>()? {
-        VERSION => Ok(VERSION),
-        other => Err(BspFileError::UnsupportedVersion(other)),
-    }?;
+    let mut header = [0u8; 4];
+    reader.read_exact(&mut header)?;
+    let format = BspFormat::from_header(header)?;
+    debug!("BSP format: {:?}", format);
 
-    let table = BspFileTable::read_from(&mut reader)?;
+    let table = BspFileTable::read_from(&mut reader, format)?;
 
     let ent_section = table.section(BspFileSectionId::Entities);
     let plane_section = table.section(BspFileSectionId::Planes);
@@ -402,6 +623,10 @@ where
     let texinfo_section = table.section(BspFileSectionId::TextureInfo);
     let face_section = table.section(BspFileSectionId::Faces);
     let lightmap_section = table.section(BspFileSectionId::Lightmaps);
+    let lightmap_format = match format {
+        BspFormat::Bsp30 => BspLightmapFormat::Rgb,
+        _ => BspLightmapFormat::Mono,
+    };
     let collision_node_section = table.section(BspFileSectionId::CollisionNodes);
     let leaf_section = table.section(BspFileSectionId::Leaves);
     let facelist_section = table.section(BspFileSectionId::FaceList);
@@ -410,42 +635,44 @@ where
     let model_section = table.section(BspFileSectionId::Models);
     let render_node_section = table.section(BspFileSectionId::RenderNodes);
 
-    let plane_count = plane_section.size / PLANE_SIZE;
-    let vert_count = vert_section.size / VERTEX_SIZE;
-    let render_node_count = render_node_section.size / RENDER_NODE_SIZE;
-    let texinfo_count = texinfo_section.size / TEXTURE_INFO_SIZE;
-    let face_count = face_section.size / FACE_SIZE;
-    let collision_node_count = collision_node_section.size / COLLISION_NODE_SIZE;
-    let leaf_count = leaf_section.size / LEAF_SIZE;
-    let facelist_count = facelist_section.size / FACELIST_SIZE;
-    let edge_count = edge_section.size / EDGE_SIZE;
-    let edgelist_count = edgelist_section.size / EDGELIST_SIZE;
-    let model_count = model_section.size / MODEL_SIZE;
-
-    // check limits
-    ensure!(plane_count <= MAX_PLANES, "Plane count exceeds MAX_PLANES");
-    ensure!(
-        vert_count <= MAX_VERTICES,
-        "Vertex count exceeds MAX_VERTICES"
-    );
-    ensure!(
-        vis_section.size <= MAX_VISLIST,
-        "Visibility data size exceeds MAX_VISLIST"
-    );
-    ensure!(
-        render_node_count <= MAX_RENDER_NODES,
-        "Render node count exceeds MAX_RENDER_NODES"
-    );
-    ensure!(
-        collision_node_count <= MAX_COLLISION_NODES,
-        "Collision node count exceeds MAX_COLLISION_NODES"
-    );
-    ensure!(leaf_count <= MAX_LEAVES, "Leaf count exceeds MAX_LEAVES");
-    ensure!(edge_count <= MAX_EDGES, "Edge count exceeds MAX_EDGES");
-    ensure!(
-        edgelist_count <= MAX_EDGELIST,
-        "Edge list count exceeds MAX_EDGELIST"
-    );
+    let plane_count = table.count(BspFileSectionId::Planes);
+    let vert_count = table.count(BspFileSectionId::Vertices);
+    let render_node_count = table.count(BspFileSectionId::RenderNodes);
+    let texinfo_count = table.count(BspFileSectionId::TextureInfo);
+    let face_count = table.count(BspFileSectionId::Faces);
+    let collision_node_count = table.count(BspFileSectionId::CollisionNodes);
+    let leaf_count = table.count(BspFileSectionId::Leaves);
+    let facelist_count = table.count(BspFileSectionId::FaceList);
+    let edge_count = table.count(BspFileSectionId::Edges);
+    let edgelist_count = table.count(BspFileSectionId::EdgeList);
+    let model_count = table.count(BspFileSectionId::Models);
+
+    // check limits. the BSP2 formats exist to lift these, so only the 16-bit formats have them
+    if !format.is_bsp2() {
+        ensure!(plane_count <= MAX_PLANES, "Plane count exceeds MAX_PLANES");
+        ensure!(
+            vert_count <= MAX_VERTICES,
+            "Vertex count exceeds MAX_VERTICES"
+        );
+        ensure!(
+            vis_section.size <= MAX_VISLIST,
+            "Visibility data size exceeds MAX_VISLIST"
+        );
+        ensure!(
+            render_node_count <= MAX_RENDER_NODES,
+            "Render node count exceeds MAX_RENDER_NODES"
+        );
+        ensure!(
+            collision_node_count <= MAX_COLLISION_NODES,
+            "Collision node count exceeds MAX_COLLISION_NODES"
+        );
+        ensure!(leaf_count <= MAX_LEAVES, "Leaf count exceeds MAX_LEAVES");
+        ensure!(edge_count <= MAX_EDGES, "Edge count exceeds MAX_EDGES");
+        ensure!(
+            edgelist_count <= MAX_EDGELIST,
+            "Edge list count exceeds MAX_EDGELIST"
+        );
+    }
     ensure!(
         model_count > 0,
         "No brush models (need at least 1 for worldmodel)"
@@ -498,7 +725,8 @@ where
         match tex_ofs {
             Some(ofs) => {
                 reader.seek(SeekFrom::Start(tex_section.offset + ofs as u64))?;
-                let texture = load_texture(&mut reader, tex_section.offset as u64, ofs as u64)?;
+                let texture =
+                    load_texture(&mut reader, tex_section.offset as u64, ofs as u64, format)?;
                 debug!(
                     "Texture {id:>width$}: {name}",
                     id = id,
@@ -515,11 +743,17 @@ where
                     width: 0,
                     height: 0,
                     mipmaps: [Vec::new(), Vec::new(), Vec::new(), Vec::new()],
+                    palette: None,
                 });
             }
         }
     }
-    table.check_end_position(&mut reader, BspFileSectionId::Textures)?;
+
+    // palettes are padded to a 4-byte boundary in version 30, so the last one may not end the
+    // section exactly
+    if format != BspFormat::Bsp30 {
+        table.check_end_position(&mut reader, BspFileSectionId::Textures)?;
+    }
 
     struct BspFileTextureAnimations {
         primary: Vec<(usize, BspFileTexture)>,
@@ -579,6 +813,7 @@ where
                     width,
                     height,
                     mipmaps,
+                    palette,
                 } = file_texture;
 
                 let texture_id = textures.len();
@@ -588,7 +823,7 @@ where
                     name,
                     width,
                     height,
-                    kind: BspTextureKind::Static(BspTextureFrame { mipmaps }),
+                    kind: BspTextureKind::Static(BspTextureFrame { mipmaps, palette }),
                 });
             }
         };
@@ -626,6 +861,7 @@ where
             corresponding_file_ids.push(file_id);
             primary.push(BspTextureFrame {
                 mipmaps: file_texture.mipmaps,
+                palette: file_texture.palette,
             });
         }
 
@@ -639,6 +875,7 @@ where
                     alt_corresp_file_ids.push(file_id);
                     alternate.push(BspTextureFrame {
                         mipmaps: file_texture.mipmaps,
+                        palette: file_texture.palette,
                     });
                 }
                 Some(alternate)
@@ -703,7 +940,7 @@ where
     debug!("Render node count = {}", render_node_count);
     let mut render_nodes = Vec::with_capacity(render_node_count);
     for _ in 0..render_node_count {
-        render_nodes.push(load_render_node(&mut reader)?);
+        render_nodes.push(load_render_node(&mut reader, format)?);
     }
     table.check_end_position(&mut reader, BspFileSectionId::RenderNodes)?;
 
@@ -722,12 +959,12 @@ where
     reader.seek(SeekFrom::Start(face_section.offset))?;
     let mut faces = Vec::with_capacity(face_count);
     for _ in 0..face_count {
-        let plane_id = reader.read_i16::<LittleEndian>()?;
+        let plane_id = format.read_short(&mut reader)?;
         if plane_id < 0 || plane_id as usize > plane_count {
             bail!("Invalid plane count");
         }
 
-        let side = match reader.read_i16::<LittleEndian>()? {
+        let side = match format.read_short(&mut reader)? {
             0 => BspFaceSide::Front,
             1 => BspFaceSide::Back,
             _ => bail!("Invalid face side"),
@@ -738,12 +975,12 @@ where
             bail!("Invalid edge ID");
         }
 
-        let edge_count = reader.read_i16::<LittleEndian>()?;
+        let edge_count = format.read_short(&mut reader)?;
         if edge_count < 3 {
             bail!("Invalid edge count");
         }
 
-        let texinfo_id = reader.read_i16::<LittleEndian>()?;
+        let texinfo_id = format.read_short(&mut reader)?;
         if texinfo_id < 0 || texinfo_id as usize > texinfo_count {
             bail!("Invalid texinfo ID");
         }
@@ -753,10 +990,11 @@ where
             light_styles[i] = reader.read_u8()?;
         }
 
+        // the offset is in bytes, so convert it to a sample index for RGB lightmaps
         let lightmap_id = match reader.read_i32::<LittleEndian>()? {
             o if o < -1 => bail!("Invalid lightmap offset"),
             -1 => None,
-            o => Some(o as usize),
+            o => Some(o as usize / lightmap_format.sample_size()),
         };
 
         faces.push(BspFace {
@@ -780,6 +1018,20 @@ where
         .read_to_end(&mut lightmaps)?;
     table.check_end_position(&mut reader, BspFileSectionId::Lightmaps)?;
 
+    // .lit files only apply to monochrome lightmaps
+    let (lightmaps, lightmap_format) = match lit {
+        Some(lit) if lightmap_format == BspLightmapFormat::Mono => {
+            match load_lit(lit, lightmaps.len()) {
+                Ok(rgb) => (rgb, BspLightmapFormat::Rgb),
+                Err(e) => {
+                    warn!("Ignoring .lit file: {}", e);
+                    (lightmaps, lightmap_format)
+                }
+            }
+        }
+        _ => (lightmaps, lightmap_format),
+    };
+
     reader.seek(SeekFrom::Start(collision_node_section.offset))?;
 
     let mut collision_nodes = Vec::with_capacity(collision_node_count);
@@ -789,16 +1041,16 @@ where
             x => x as usize,
         };
 
-        let front = match reader.read_i16::<LittleEndian>()? {
-            x if x < 0 => match BspLeafContents::from_i16(-x) {
+        let front = match format.read_short(&mut reader)? {
+            x if x < 0 => match BspLeafContents::from_i32(-x) {
                 Some(c) => BspCollisionNodeChild::Contents(c),
                 None => bail!("Invalid leaf contents ({})", -x),
             },
             x => BspCollisionNodeChild::Node(x as usize),
         };
 
-        let back = match reader.read_i16::<LittleEndian>()? {
-            x if x < 0 => match BspLeafContents::from_i16(-x) {
+        let back = match format.read_short(&mut reader)? {
+            x if x < 0 => match BspLeafContents::from_i32(-x) {
                 Some(c) => BspCollisionNodeChild::Contents(c),
                 None => bail!("Invalid leaf contents ({})", -x),
             },
@@ -813,23 +1065,22 @@ where
 
     let collision_nodes_rc = Rc::new(collision_nodes.into_boxed_slice());
 
-    let hull_1 = BspCollisionHull {
-        planes: planes_rc.clone(),
-        nodes: collision_nodes_rc.clone(),
-        node_id: 0,
-        node_count: collision_node_count,
-        mins: Vector3::new(-16.0, -16.0, -24.0),
-        maxs: Vector3::new(16.0, 16.0, 32.0),
+    let (hull_layout, hull_bounds) = match format {
+        BspFormat::Bsp30 => (BspHullLayout::GoldSrc, GOLDSRC_HULL_BOUNDS),
+        _ => (BspHullLayout::Quake, QUAKE_HULL_BOUNDS),
     };
 
-    let hull_2 = BspCollisionHull {
+    let clip_hull = |(mins, maxs): ([f32; 3], [f32; 3])| BspCollisionHull {
         planes: planes_rc.clone(),
         nodes: collision_nodes_rc.clone(),
         node_id: 0,
         node_count: collision_node_count,
-        mins: Vector3::new(-32.0, -32.0, -24.0),
-        maxs: Vector3::new(32.0, 32.0, 64.0),
+        mins: mins.into(),
+        maxs: maxs.into(),
     };
+    let hull_1 = clip_hull(hull_bounds[0]);
+    let hull_2 = clip_hull(hull_bounds[1]);
+    let hull_3 = clip_hull(hull_bounds[2]);
 
     if reader.seek(SeekFrom::Current(0))?
         != reader.seek(SeekFrom::Start(
@@ -868,11 +1119,11 @@ where
             x => Some(x as usize),
         };
 
-        let min = read_i16_3(&mut reader)?;
-        let max = read_i16_3(&mut reader)?;
+        let min = format.read_bounds(&mut reader)?;
+        let max = format.read_bounds(&mut reader)?;
 
-        let facelist_id = reader.read_u16::<LittleEndian>()? as usize;
-        let facelist_count = reader.read_u16::<LittleEndian>()? as usize;
+        let facelist_id = format.read_ushort(&mut reader)? as usize;
+        let facelist_count = format.read_ushort(&mut reader)? as usize;
         let mut sounds = [0u8; NUM_AMBIENTS];
         reader.read(&mut sounds)?;
         leaves.push(BspLeaf {
@@ -890,7 +1141,7 @@ where
     reader.seek(SeekFrom::Start(facelist_section.offset))?;
     let mut facelist = Vec::with_capacity(facelist_count);
     for _ in 0..facelist_count {
-        facelist.push(reader.read_u16::<LittleEndian>()? as usize);
+        facelist.push(format.read_ushort(&mut reader)? as usize);
     }
     if reader.seek(SeekFrom::Current(0))?
         != reader.seek(SeekFrom::Start(
@@ -905,8 +1156,8 @@ where
     for _ in 0..edge_count {
         edges.push(BspEdge {
             vertex_ids: [
-                reader.read_u16::<LittleEndian>()?,
-                reader.read_u16::<LittleEndian>()?,
+                format.read_ushort(&mut reader)?,
+                format.read_ushort(&mut reader)?,
             ],
         });
     }
@@ -964,8 +1215,9 @@ where
         let b_maxs = [(s_max / 16.0).ceil(), (t_max / 16.0).ceil()];
 
         for i in 0..2 {
-            face.texture_mins[i] = b_mins[i] as i16 * 16;
-            face.extents[i] = (b_maxs[i] - b_mins[i]) as i16 * 16;
+            // BSP2 maps can be large enough that these don't fit in 16 bits
+            face.texture_mins[i] = b_mins[i] as i32 * 16;
+            face.extents[i] = (b_maxs[i] - b_mins[i]) as i32 * 16;
 
             if !texinfo.special && face.extents[i] > 2000 {
                 bail!(
@@ -1022,7 +1274,9 @@ where
         texinfo: texinfo.into_boxed_slice(),
         faces: faces.into_boxed_slice(),
         lightmaps: lightmaps.into_boxed_slice(),
-        hulls: [hull_0, hull_1, hull_2],
+        lightmap_format,
+        hulls: [hull_0, hull_1, hull_2, hull_3],
+        hull_layout,
         leaves: leaves.into_boxed_slice(),
         facelist: facelist.into_boxed_slice(),
         edges: edges.into_boxed_slice(),
@@ -1051,10 +1305,6 @@ where
             };
         }
 
-        // throw away the last collision node ID -- BSP files make room for 4 collision hulls but
-        // only 3 are ever used.
-        reader.read_i32::<LittleEndian>()?;
-
         debug!("model[{}].headnodes = {:?}", i, collision_node_ids);
 
         let leaf_id = total_leaf_count;
@@ -1117,3 +1367,381 @@ where
     reader.read_i16_into::<LittleEndian>(&mut ar)?;
     Ok(ar)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use crate::common::{bsp::BspModel, model::ModelKind};
+
+    fn put_i32s(data: &mut Vec<u8>, values: &[i32]) {
+        for v in values {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+
+    fn put_f32s(data: &mut Vec<u8>, values: &[f32]) {
+        for v in values {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+
+    // writes a value which is 16 bits wide in BSP29 and 32 bits wide in BSP2
+    fn put_short(format: BspFormat, data: &mut Vec<u8>, value: i32) {
+        match format.is_bsp2() {
+            false => data.extend_from_slice(&(value as i16).to_le_bytes()),
+            true => data.extend_from_slice(&value.to_le_bytes()),
+        }
+    }
+
+    fn put_bounds(format: BspFormat, data: &mut Vec<u8>, bounds: [f32; 3]) {
+        for b in &bounds {
+            match format {
+                BspFormat::Bsp2 => data.extend_from_slice(&b.to_le_bytes()),
+                _ => data.extend_from_slice(&(*b as i16).to_le_bytes()),
+            }
+        }
+    }
+
+    // builds a map whose world is a single 32x32 floor face, lit by the samples in `lightmap`
+    // starting at byte offset `lightmap_ofs`
+    fn build_map(format: BspFormat, lightmap: &[u8], lightmap_ofs: i32) -> Vec<u8> {
+        build_map_at(format, lightmap, lightmap_ofs, 0.0)
+    }
+
+    // builds the map from `build_map` with the floor face moved `x` units along the x axis
+    fn build_map_at(format: BspFormat, lightmap: &[u8], lightmap_ofs: i32, x: f32) -> Vec<u8> {
+        use BspFileSectionId::*;
+
+        let mut sections = vec![Vec::new(); SECTION_COUNT];
+
+        sections[Entities as usize] = b"{\n\"classname\" \"worldspawn\"\n}\n\0".to_vec();
+
+        // z = 0
+        let planes = &mut sections[Planes as usize];
+        put_f32s(planes, &[0.0, 0.0, 1.0, 0.0]);
+        put_i32s(planes, &[Axis::Z as i32]);
+
+        let textures = &mut sections[Textures as usize];
+        put_i32s(textures, &[1, 8]);
+        textures.extend_from_slice(b"floor\0\0\0\0\0\0\0\0\0\0\0");
+        put_i32s(textures, &[16, 16, 40, 296, 360, 376]);
+        textures.extend(std::iter::repeat(7).take(256 + 64 + 16 + 4));
+        if format == BspFormat::Bsp30 {
+            textures.extend_from_slice(&1u16.to_le_bytes());
+            textures.extend_from_slice(&[0x80, 0x80, 0x80, 0, 0]);
+        }
+
+        put_f32s(
+            &mut sections[Vertices as usize],
+            &[
+                x,
+                0.0,
+                0.0,
+                x + 32.0,
+                0.0,
+                0.0,
+                x + 32.0,
+                32.0,
+                0.0,
+                x,
+                32.0,
+                0.0,
+            ],
+        );
+
+        // front is leaf 1, back is leaf 0
+        let nodes = &mut sections[RenderNodes as usize];
+        put_i32s(nodes, &[0]);
+        put_short(format, nodes, -2);
+        put_short(format, nodes, -1);
+        put_bounds(format, nodes, [x, 0.0, 0.0]);
+        put_bounds(format, nodes, [x + 32.0, 32.0, 0.0]);
+        put_short(format, nodes, 0);
+        put_short(format, nodes, 1);
+
+        let texinfo = &mut sections[TextureInfo as usize];
+        put_f32s(texinfo, &[1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+        put_i32s(texinfo, &[0, 0]);
+
+        let faces = &mut sections[Faces as usize];
+        put_short(format, faces, 0);
+        put_short(format, faces, 0);
+        put_i32s(faces, &[0]);
+        put_short(format, faces, 4);
+        put_short(format, faces, 0);
+        faces.extend_from_slice(&[0, 255, 255, 255]);
+        put_i32s(faces, &[lightmap_ofs]);
+
+        sections[Lightmaps as usize] = lightmap.to_vec();
+
+        let clipnodes = &mut sections[CollisionNodes as usize];
+        put_i32s(clipnodes, &[0]);
+        put_short(format, clipnodes, -1);
+        put_short(format, clipnodes, -2);
+
+        let leaves = &mut sections[Leaves as usize];
+        for (contents, face_count) in &[(-2, 0), (-1, 1)] {
+            put_i32s(leaves, &[*contents, -1]);
+            put_bounds(format, leaves, [x, 0.0, 0.0]);
+            put_bounds(format, leaves, [x + 32.0, 32.0, 0.0]);
+            put_short(format, leaves, 0);
+            put_short(format, leaves, *face_count);
+            leaves.extend_from_slice(&[0; NUM_AMBIENTS]);
+        }
+
+        put_short(format, &mut sections[FaceList as usize], 0);
+
+        // edge 0 can't be referenced backwards, so it's left unused
+        let edges = &mut sections[Edges as usize];
+        for (a, b) in &[(0, 0), (0, 1), (1, 2), (2, 3), (3, 0)] {
+            put_short(format, edges, *a);
+            put_short(format, edges, *b);
+        }
+
+        put_i32s(&mut sections[EdgeList as usize], &[1, 2, 3, 4]);
+
+        let models = &mut sections[Models as usize];
+        put_f32s(models, &[x, 0.0, 0.0, x + 32.0, 32.0, 0.0, 0.0, 0.0, 0.0]);
+        put_i32s(models, &[0, 0, 0, 0, 1, 0, 1]);
+
+        let mut data = match format {
+            BspFormat::Bsp29 => VERSION.to_le_bytes().to_vec(),
+            BspFormat::Bsp30 => BSP30_VERSION.to_le_bytes().to_vec(),
+            BspFormat::Bsp2Rmq => BSP2_RMQ_MAGIC.to_vec(),
+            BspFormat::Bsp2 => BSP2_MAGIC.to_vec(),
+        };
+
+        let mut offset = 4 + SECTION_COUNT * 8;
+        for section in &sections {
+            put_i32s(&mut data, &[offset as i32, section.len() as i32]);
+            offset += section.len();
+        }
+
+        for section in sections {
+            data.extend(section);
+        }
+
+        data
+    }
+
+    fn world(models: &[Model]) -> &BspModel {
+        match models[0].kind() {
+            ModelKind::Brush(bmodel) => bmodel,
+            _ => panic!("worldmodel is not a brush model"),
+        }
+    }
+
+    #[test]
+    fn test_format_from_header() {
+        assert_eq!(
+            BspFormat::from_header(29i32.to_le_bytes()).unwrap(),
+            BspFormat::Bsp29
+        );
+        assert_eq!(BspFormat::from_header(*b"BSP2").unwrap(), BspFormat::Bsp2);
+        assert_eq!(
+            BspFormat::from_header(*b"2PSB").unwrap(),
+            BspFormat::Bsp2Rmq
+        );
+        assert_eq!(
+            BspFormat::from_header(30i32.to_le_bytes()).unwrap(),
+            BspFormat::Bsp30
+        );
+        match BspFormat::from_header(31i32.to_le_bytes()) {
+            Err(BspFileError::UnsupportedVersion(31)) => (),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_format_reads() {
+        let mut reader = Cursor::new(vec![0xff, 0xff, 0x00, 0x80]);
+        assert_eq!(BspFormat::Bsp29.read_ushort(&mut reader).unwrap(), 0xffff);
+        assert_eq!(BspFormat::Bsp29.read_short(&mut reader).unwrap(), -32768);
+
+        let mut reader = Cursor::new(vec![0x00, 0x00, 0x01, 0x00]);
+        assert_eq!(BspFormat::Bsp2.read_ushort(&mut reader).unwrap(), 0x10000);
+
+        let mut reader = Cursor::new(
+            [1.5f32, -2.0, 3.0]
+                .iter()
+                .flat_map(|f| f.to_le_bytes().to_vec())
+                .collect::<Vec<u8>>(),
+        );
+        assert_eq!(
+            BspFormat::Bsp2.read_bounds(&mut reader).unwrap(),
+            [1.5, -2.0, 3.0]
+        );
+
+        let mut reader = Cursor::new(vec![0x01, 0x00, 0xfe, 0xff, 0x03, 0x00]);
+        assert_eq!(
+            BspFormat::Bsp2Rmq.read_bounds(&mut reader).unwrap(),
+            [1.0, -2.0, 3.0]
+        );
+    }
+
+    #[test]
+    fn test_load_bsp2() {
+        for format in &[BspFormat::Bsp29, BspFormat::Bsp2Rmq, BspFormat::Bsp2] {
+            let lightmap: Vec<u8> = (0..10).collect();
+            let (models, ents) = load(Cursor::new(build_map(*format, &lightmap, 1))).unwrap();
+            assert!(ents.contains("worldspawn"), "{:?}", format);
+
+            let bsp = world(&models).bsp_data();
+            let node = &bsp.render_nodes()[0];
+            assert_eq!(node.max, [32.0, 32.0, 0.0], "{:?}", format);
+            assert_eq!(node.face_count, 1, "{:?}", format);
+
+            let leaf = &bsp.leaves()[1];
+            assert_eq!(leaf.contents, BspLeafContents::Empty, "{:?}", format);
+            assert_eq!(leaf.max, [32.0, 32.0, 0.0], "{:?}", format);
+            assert_eq!(leaf.facelist_count, 1, "{:?}", format);
+            assert_eq!(bsp.facelist(), &[0], "{:?}", format);
+            assert_eq!(bsp.edges()[3].vertex_ids, [2, 3], "{:?}", format);
+
+            let face = bsp.face(0);
+            assert_eq!(face.edge_count, 4, "{:?}", format);
+            assert_eq!(face.extents, [32, 32], "{:?}", format);
+            let verts: Vec<_> = bsp.face_iter_vertices(0).collect();
+            assert_eq!(verts[2], Vector3::new(32.0, 32.0, 0.0), "{:?}", format);
+
+            let lightmaps = bsp.face_lightmaps(0);
+            assert_eq!(lightmaps.len(), 1, "{:?}", format);
+            assert_eq!(lightmaps[0].data(), &lightmap[1..10], "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_load_bsp2_large() {
+        // beyond the range of BSP29's 16-bit bounds
+        let lightmap: Vec<u8> = (0..10).collect();
+        let data = build_map_at(BspFormat::Bsp2, &lightmap, 1, 40000.0);
+        let (models, _) = load(Cursor::new(data)).unwrap();
+
+        let bsp = world(&models).bsp_data();
+        assert_eq!(bsp.render_nodes()[0].min, [40000.0, 0.0, 0.0]);
+
+        let face = bsp.face(0);
+        assert_eq!(face.texture_mins, [40000, 0]);
+        assert_eq!(face.extents, [32, 32]);
+        assert_eq!(bsp.face_lightmaps(0)[0].data(), &lightmap[1..10]);
+    }
+
+    #[test]
+    fn test_load_bsp30() {
+        let lightmap: Vec<u8> = (0..30).collect();
+        let (models, _) = load(Cursor::new(build_map(BspFormat::Bsp30, &lightmap, 3))).unwrap();
+        let world = world(&models);
+        let bsp = world.bsp_data();
+
+        // the byte offset of 3 is the second RGB sample
+        assert_eq!(bsp.face(0).lightmap_id, Some(1));
+        let lightmaps = bsp.face_lightmaps(0);
+        assert_eq!(lightmaps.len(), 1);
+        assert_eq!(lightmaps[0].format(), BspLightmapFormat::Rgb);
+        assert_eq!((lightmaps[0].width(), lightmaps[0].height()), (3, 3));
+        assert_eq!(lightmaps[0].data(), &lightmap[3..30]);
+
+        let palette = match bsp.textures()[0].kind() {
+            BspTextureKind::Static(frame) => frame.palette().unwrap(),
+            _ => panic!("texture is animated"),
+        };
+        assert_eq!(&palette[..3], &[0x80, 0x80, 0x80]);
+
+        assert_eq!(
+            world.hull(1).unwrap().min(),
+            Vector3::new(-16.0, -16.0, -36.0)
+        );
+        assert_eq!(world.hull(3).unwrap().max(), Vector3::new(16.0, 16.0, 18.0));
+        assert_eq!(world.hull_index_for_size(Vector3::new(0.0, 0.0, 0.0)), 0);
+        assert_eq!(world.hull_index_for_size(Vector3::new(32.0, 32.0, 72.0)), 1);
+        assert_eq!(world.hull_index_for_size(Vector3::new(64.0, 64.0, 64.0)), 2);
+        assert_eq!(world.hull_index_for_size(Vector3::new(32.0, 32.0, 36.0)), 3);
+    }
+
+    #[test]
+    fn test_quake_hulls() {
+        let (models, _) = load(Cursor::new(build_map(BspFormat::Bsp29, &[0; 9], 0))).unwrap();
+        let world = world(&models);
+
+        assert_eq!(
+            world.hull(1).unwrap().min(),
+            Vector3::new(-16.0, -16.0, -24.0)
+        );
+        assert_eq!(world.hull(2).unwrap().max(), Vector3::new(32.0, 32.0, 64.0));
+        assert_eq!(world.hull_index_for_size(Vector3::new(32.0, 32.0, 36.0)), 1);
+        assert_eq!(world.hull_index_for_size(Vector3::new(64.0, 64.0, 88.0)), 2);
+    }
+
+    #[test]
+    fn test_load_bsp30_texture() {
+        let mut data = Vec::new();
+        data.extend_from_slice(b"{fence\0\0\0\0\0\0\0\0\0\0");
+        data.extend_from_slice(&8u32.to_le_bytes());
+        data.extend_from_slice(&8u32.to_le_bytes());
+        for ofs in &[40u32, 104, 120, 124] {
+            data.extend_from_slice(&ofs.to_le_bytes());
+        }
+        data.extend((0..64 + 16 + 4 + 1).map(|i| (i % 2) as u8));
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&[10, 20, 30, 40, 50, 60]);
+
+        let tex = load_texture(&mut Cursor::new(data), 0, 0, BspFormat::Bsp30).unwrap();
+        assert_eq!(tex.name, "{fence");
+        assert_eq!(tex.mipmaps[0].len(), 64);
+        assert_eq!(tex.mipmaps[3].len(), 1);
+
+        let palette = tex.palette.unwrap();
+        assert_eq!(palette.len(), 768);
+        assert_eq!(&palette[..6], &[10, 20, 30, 40, 50, 60]);
+        assert!(palette[6..].iter().all(|c| *c == 0));
+    }
+
+    #[test]
+    fn test_load_bsp30_wad_texture() {
+        let mut data = Vec::new();
+        data.extend_from_slice(b"wall\0\0\0\0\0\0\0\0\0\0\0\0");
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&[0; 16]);
+
+        let tex = load_texture(&mut Cursor::new(data), 0, 0, BspFormat::Bsp30).unwrap();
+        assert_eq!(tex.mipmaps[0].len(), 256);
+        assert_eq!(tex.mipmaps[3].len(), 4);
+        assert!(tex.palette.is_some());
+    }
+
+    #[test]
+    fn test_load_lit() {
+        let mut lit = Vec::new();
+        lit.extend_from_slice(b"QLIT");
+        lit.extend_from_slice(&1i32.to_le_bytes());
+        lit.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+        assert_eq!(
+            load_lit(Cursor::new(&lit), 2).unwrap(),
+            vec![1, 2, 3, 4, 5, 6]
+        );
+
+        match load_lit(Cursor::new(&lit), 3) {
+            Err(BspFileError::InvalidLitSize {
+                expected: 9,
+                found: 6,
+            }) => (),
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        lit[4] = 2;
+        match load_lit(Cursor::new(&lit), 2) {
+            Err(BspFileError::UnsupportedLitVersion(2)) => (),
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        lit[0] = b'X';
+        match load_lit(Cursor::new(&lit), 2) {
+            Err(BspFileError::InvalidLitHeader) => (),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+}
@@ -128,10 +128,10 @@ use crate::server::world::{Trace, TraceEnd, TraceStart};
 use cgmath::Vector3;
 use chrono::Duration;
 
-pub use self::load::{load, BspFileError};
+pub use self::load::{load, load_with_lit, BspFileError};
 
-// this is 4 in the original source, but the 4th hull is never used.
-const MAX_HULLS: usize = 3;
+// Quake never uses the 4th hull, but GoldSrc uses it for crouching.
+const MAX_HULLS: usize = 4;
 
 pub const MAX_LIGHTMAPS: usize = 64;
 pub const MAX_LIGHTSTYLES: usize = 4;
@@ -193,12 +193,21 @@ pub enum BspTextureMipmap {
 #[derive(Debug)]
 pub struct BspTextureFrame {
     mipmaps: [Vec<u8>; MIPLEVELS],
+    palette: Option<Box<[u8]>>,
 }
 
 impl BspTextureFrame {
     pub fn mipmap(&self, level: BspTextureMipmap) -> &[u8] {
         &self.mipmaps[level as usize]
     }
+
+    /// Returns the 256-color RGB palette embedded in the texture, if any.
+    ///
+    /// Version 30 BSP files store a palette with each texture. Textures without one use the
+    /// palette from `gfx/palette.lmp`.
+    pub fn palette(&self) -> Option<&[u8]> {
+        self.palette.as_deref()
+    }
 }
 
 #[derive(Debug)]
@@ -253,8 +262,8 @@ pub enum BspRenderNodeChild {
 pub struct BspRenderNode {
     pub plane_id: usize,
     pub children: [BspRenderNodeChild; 2],
-    pub min: [i16; 3],
-    pub max: [i16; 3],
+    pub min: [f32; 3],
+    pub max: [f32; 3],
     pub face_id: usize,
     pub face_count: usize,
 }
@@ -285,8 +294,8 @@ pub struct BspFace {
     pub light_styles: [u8; MAX_LIGHTSTYLES],
     pub lightmap_id: Option<usize>,
 
-    pub texture_mins: [i16; 2],
-    pub extents: [i16; 2],
+    pub texture_mins: [i32; 2],
+    pub extents: [i32; 2],
 }
 
 /// The contents of a leaf in the BSP tree, specifying how it should look and behave.
@@ -653,8 +662,8 @@ impl BspCollisionHull {
 pub struct BspLeaf {
     pub contents: BspLeafContents,
     pub vis_offset: Option<usize>,
-    pub min: [i16; 3],
-    pub max: [i16; 3],
+    pub min: [f32; 3],
+    pub max: [f32; 3],
     pub facelist_id: usize,
     pub facelist_count: usize,
     pub sounds: [u8; MAX_SOUNDS],
@@ -662,7 +671,7 @@ pub struct BspLeaf {
 
 #[derive(Debug)]
 pub struct BspEdge {
-    pub vertex_ids: [u16; 2],
+    pub vertex_ids: [u32; 2],
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -677,10 +686,41 @@ pub struct BspEdgeIndex {
     pub index: usize,
 }
 
+/// The set of collision hulls a map was compiled with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BspHullLayout {
+    /// Quake's point, player and large monster hulls.
+    Quake,
+
+    /// GoldSrc's point, standing, large and crouching hulls.
+    GoldSrc,
+}
+
+/// The layout of the samples in a map's lightmap data.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BspLightmapFormat {
+    /// One byte of intensity per sample.
+    Mono,
+
+    /// Three bytes of red, green and blue intensity per sample.
+    Rgb,
+}
+
+impl BspLightmapFormat {
+    /// Returns the size in bytes of one lightmap sample.
+    pub fn sample_size(&self) -> usize {
+        match self {
+            BspLightmapFormat::Mono => 1,
+            BspLightmapFormat::Rgb => 3,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct BspLightmap<'a> {
     width: u32,
     height: u32,
+    format: BspLightmapFormat,
     data: &'a [u8],
 }
 
@@ -693,6 +733,10 @@ impl<'a> BspLightmap<'a> {
         self.height
     }
 
+    pub fn format(&self) -> BspLightmapFormat {
+        self.format
+    }
+
     pub fn data(&self) -> &[u8] {
         self.data
     }
@@ -708,11 +752,13 @@ pub struct BspData {
     pub(crate) texinfo: Box<[BspTexInfo]>,
     pub(crate) faces: Box<[BspFace]>,
     pub(crate) lightmaps: Box<[u8]>,
+    pub(crate) lightmap_format: BspLightmapFormat,
     pub(crate) leaves: Box<[BspLeaf]>,
     pub(crate) facelist: Box<[usize]>,
     pub(crate) edges: Box<[BspEdge]>,
     pub(crate) edgelist: Box<[BspEdgeIndex]>,
     pub(crate) hulls: [BspCollisionHull; MAX_HULLS],
+    pub(crate) hull_layout: BspHullLayout,
 }
 
 impl BspData {
@@ -760,17 +806,19 @@ impl BspData {
                 let lightmap_w = face.extents[0] as u32 / 16 + 1;
                 let lightmap_h = face.extents[1] as u32 / 16 + 1;
                 let lightmap_size = (lightmap_w * lightmap_h) as usize;
+                let sample_size = self.lightmap_format.sample_size();
 
                 face.light_styles
                     .iter()
                     .take_while(|style| **style != 255)
                     .enumerate()
                     .map(|(i, _)| {
-                        let start = lightmap_id + lightmap_size * i as usize;
-                        let end = start + lightmap_size;
+                        let start = (lightmap_id + lightmap_size * i as usize) * sample_size;
+                        let end = start + lightmap_size * sample_size;
                         BspLightmap {
                             width: lightmap_w,
                             height: lightmap_h,
+                            format: self.lightmap_format,
                             data: &self.lightmaps[start..end],
                         }
                     })
@@ -788,6 +836,10 @@ impl BspData {
         &self.lightmaps
     }
 
+    pub fn lightmap_format(&self) -> BspLightmapFormat {
+        self.lightmap_format
+    }
+
     pub fn leaves(&self) -> &[BspLeaf] {
         &self.leaves
     }
@@ -989,8 +1041,22 @@ impl BspModel {
         &self.bsp_data.facelist[self.face_id..self.face_id + self.face_count]
     }
 
+    /// Returns the index of the hull used to collide a box of the given size with this model.
+    pub fn hull_index_for_size(&self, size: Vector3<f32>) -> usize {
+        if size.x < 3.0 {
+            return 0;
+        }
+
+        match self.bsp_data.hull_layout {
+            BspHullLayout::Quake if size.x <= 32.0 => 1,
+            BspHullLayout::GoldSrc if size.x <= 32.0 && size.z <= 36.0 => 3,
+            BspHullLayout::GoldSrc if size.x <= 32.0 => 1,
+            _ => 2,
+        }
+    }
+
     pub fn hull(&self, index: usize) -> Result<BspCollisionHull, BspError> {
-        if index > MAX_HULLS {
+        if index >= MAX_HULLS {
             return Err(BspError::with_msg(format!(
                 "Invalid hull index ({})",
                 index
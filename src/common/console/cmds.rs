@@ -0,0 +1,419 @@
+//! Core console commands available regardless of game state.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    io::Read as _,
+    rc::Rc,
+};
+
+use crate::common::{
+    console::{CmdRegistry, ConsoleOutput, CvarRegistry},
+    vfs::Vfs,
+};
+
+use failure::Error;
+
+// maximum nesting depth of scripts run by `exec` within a single frame
+const MAX_EXEC_DEPTH: usize = 16;
+
+/// Implements the `echo` command.
+pub fn cmd_echo(output: Rc<RefCell<ConsoleOutput>>) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |args| {
+        output.borrow_mut().println(args.join(" "));
+        Ok(())
+    })
+}
+
+/// Implements the `alias` command.
+pub fn cmd_alias(
+    aliases: Rc<RefCell<HashMap<String, String>>>,
+    output: Rc<RefCell<ConsoleOutput>>,
+) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |args| {
+        match args.len() {
+            // list all aliases
+            0 => {
+                let aliases = aliases.borrow();
+                let mut names: Vec<&String> = aliases.keys().collect();
+                names.sort();
+
+                let mut output = output.borrow_mut();
+                for name in names {
+                    output.println(format!("    {}: {}", name, aliases[name]));
+                }
+                output.println(format!("{} alias command(s)", aliases.len()));
+            }
+
+            // show a single alias
+            1 => match aliases.borrow().get(args[0]) {
+                Some(script) => output
+                    .borrow_mut()
+                    .println(format!("{}: {}", args[0], script)),
+                None => bail!("No alias named \"{}\"", args[0]),
+            },
+
+            // define an alias, joining the remaining arguments into the script
+            _ => {
+                let name = args[0].to_string();
+                let script = args[1..].join(" ");
+
+                // scripts are quoted when aliases are saved, and quotes can't be escaped
+                if script.contains('"') {
+                    bail!("Alias scripts can't contain quotes");
+                }
+
+                let _ = aliases.borrow_mut().insert(name, script);
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Implements the `exec` command.
+///
+/// The script is inserted at the front of the execution buffer, so it runs before any commands
+/// following the `exec`.
+///
+/// `exec_tails` holds, for each script being executed, the length of the buffer following its
+/// text. Scripts which nest too deeply (e.g. a script which runs `exec` on itself) are stopped and
+/// the rest of the outermost script is discarded.
+pub fn cmd_exec(
+    vfs: Rc<Vfs>,
+    buffer: Rc<RefCell<String>>,
+    exec_tails: Rc<RefCell<Vec<usize>>>,
+    output: Rc<RefCell<ConsoleOutput>>,
+) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |args| {
+        if args.len() != 1 {
+            output
+                .borrow_mut()
+                .println("exec <filename> : execute a script file");
+            return Ok(());
+        }
+
+        let mut data = Vec::new();
+        let read = vfs
+            .open(args[0])
+            .map_err(|e| e.to_string())
+            .and_then(|mut f| f.read_to_end(&mut data).map_err(|e| e.to_string()));
+
+        if let Err(e) = read {
+            bail!("Couldn't exec {}: {}", args[0], e);
+        }
+
+        let mut buffer = buffer.borrow_mut();
+        let mut tails = exec_tails.borrow_mut();
+
+        // this command may follow the end of the innermost script
+        while tails.last().map_or(false, |t| buffer.len() < *t) {
+            tails.pop();
+        }
+
+        if tails.len() >= MAX_EXEC_DEPTH {
+            let end = buffer.len() - tails[0];
+            buffer.replace_range(..end, "");
+            tails.clear();
+            bail!("exec: script nesting too deep");
+        }
+
+        // scripts aren't guaranteed to be valid UTF-8
+        let mut script = String::from_utf8_lossy(&data).into_owned();
+        script.push('\n');
+        tails.push(buffer.len());
+        buffer.insert_str(0, &script);
+
+        Ok(())
+    })
+}
+
+/// Implements the `wait` command.
+pub fn cmd_wait(wait: Rc<Cell<bool>>) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |_| {
+        wait.set(true);
+        Ok(())
+    })
+}
+
+/// Implements the `cmdlist` command.
+pub fn cmd_cmdlist(
+    cmds: Rc<RefCell<CmdRegistry>>,
+    output: Rc<RefCell<ConsoleOutput>>,
+) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |args| {
+        let pattern = args.get(0).copied().unwrap_or("*");
+        let cmds = cmds.borrow();
+        let mut names: Vec<&str> = cmds.names().filter(|n| glob_match(pattern, n)).collect();
+        names.sort();
+
+        let mut output = output.borrow_mut();
+        for name in names.iter() {
+            output.println(format!("    {}", name));
+        }
+        output.println(format!("{} command(s)", names.len()));
+
+        Ok(())
+    })
+}
+
+/// Implements the `cvarlist` command.
+pub fn cmd_cvarlist(
+    cvars: Rc<RefCell<CvarRegistry>>,
+    output: Rc<RefCell<ConsoleOutput>>,
+) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |args| {
+        let pattern = args.get(0).copied().unwrap_or("*");
+        let cvars = cvars.borrow();
+        let mut names: Vec<String> = cvars
+            .names()
+            .into_iter()
+            .filter(|n| glob_match(pattern, n))
+            .collect();
+        names.sort();
+
+        let mut output = output.borrow_mut();
+        for name in names.iter() {
+            output.println(format!("    {} \"{}\"", name, cvars.get(name)?));
+        }
+        output.println(format!("{} cvar(s)", names.len()));
+
+        Ok(())
+    })
+}
+
+/// Implements the `toggle` command.
+pub fn cmd_toggle(
+    cvars: Rc<RefCell<CvarRegistry>>,
+    output: Rc<RefCell<ConsoleOutput>>,
+) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |args| {
+        if args.len() != 1 {
+            output
+                .borrow_mut()
+                .println("toggle <cvar> : toggle a cvar between 0 and 1");
+            return Ok(());
+        }
+
+        let cvars = cvars.borrow();
+        let new_value = if cvars.get_value(args[0])? == 0.0 {
+            "1"
+        } else {
+            "0"
+        };
+        cvars.set(args[0], new_value)?;
+
+        Ok(())
+    })
+}
+
+/// Implements the `inc` and `dec` commands.
+///
+/// `sign` should be `1.0` for `inc` and `-1.0` for `dec`.
+pub fn cmd_inc(
+    name: &'static str,
+    sign: f32,
+    cvars: Rc<RefCell<CvarRegistry>>,
+    output: Rc<RefCell<ConsoleOutput>>,
+) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |args| {
+        let amount = match args.len() {
+            1 => 1.0,
+            2 => match args[1].parse::<f32>() {
+                Ok(a) => a,
+                Err(_) => bail!("{}: \"{}\" is not a number", name, args[1]),
+            },
+            _ => {
+                output
+                    .borrow_mut()
+                    .println(format!("{} <cvar> [amount]", name));
+                return Ok(());
+            }
+        };
+
+        let cvars = cvars.borrow();
+        let value = cvars.get_value(args[0])? + sign * amount;
+        cvars.set(args[0], value.to_string().as_str())?;
+
+        Ok(())
+    })
+}
+
+/// Implements the `reset` command.
+pub fn cmd_reset(
+    cvars: Rc<RefCell<CvarRegistry>>,
+    output: Rc<RefCell<ConsoleOutput>>,
+) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |args| {
+        if args.len() != 1 {
+            output
+                .borrow_mut()
+                .println("reset <cvar> : reset a cvar to its default value");
+            return Ok(());
+        }
+
+        cvars.borrow().reset(args[0])?;
+        Ok(())
+    })
+}
+
+/// Implements the `resetall` command.
+pub fn cmd_resetall(cvars: Rc<RefCell<CvarRegistry>>) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |_| {
+        cvars.borrow().reset_all()?;
+        Ok(())
+    })
+}
+
+/// Implements the `help` command.
+pub fn cmd_help(
+    cvars: Rc<RefCell<CvarRegistry>>,
+    output: Rc<RefCell<ConsoleOutput>>,
+) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |args| {
+        if args.len() != 1 {
+            output.borrow_mut().println("help <cvar> : describe a cvar");
+            return Ok(());
+        }
+
+        let info = cvars.borrow().info(args[0])?;
+        let mut output = output.borrow_mut();
+        output.println(format!(
+            "\"{}\" is \"{}\" (default \"{}\")",
+            args[0], info.value, info.default
+        ));
+        output.println(format!("    value: {}", info.kind));
+        if let Some(description) = info.description {
+            output.println(format!("    {}", description));
+        }
+        if info.archive {
+            output.println("    saved to config.cfg");
+        }
+
+        Ok(())
+    })
+}
+
+/// Implements the `cmd` command, which sends its arguments to the server as a string command.
+pub fn cmd_cmd(
+    forward_to_server: Rc<Cell<bool>>,
+    forwarded: Rc<RefCell<Vec<String>>>,
+) -> Box<dyn Fn(&[&str]) -> Result<(), Error>> {
+    Box::new(move |args| {
+        if !forward_to_server.get() {
+            bail!("Can't \"cmd\", not connected");
+        }
+
+        if !args.is_empty() {
+            forwarded.borrow_mut().push(join_args(args));
+        }
+
+        Ok(())
+    })
+}
+
+/// Joins command arguments into a line that parses back into the same arguments.
+pub fn join_args<S>(args: &[S]) -> String
+where
+    S: AsRef<str>,
+{
+    args.iter()
+        .map(|a| {
+            let a = a.as_ref();
+            if a.is_empty() || a.contains(|c: char| c.is_whitespace() || c == ';' || c == '/') {
+                format!("\"{}\"", a)
+            } else {
+                a.to_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Matches `name` against a glob pattern.
+///
+/// `?` matches any one character and `*` matches any number of characters, neither crossing a
+/// `/`. `**` matches any number of characters including `/`.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // matched[n] is true if the pattern read so far matches the first n characters of the name
+    let mut matched = vec![false; name.len() + 1];
+    matched[0] = true;
+
+    let mut p = 0;
+    while p < pattern.len() {
+        let mut next = vec![false; name.len() + 1];
+
+        match pattern[p] {
+            '*' if pattern.get(p + 1) == Some(&'*') => {
+                for n in 0..=name.len() {
+                    next[n] = matched[n] || (n > 0 && next[n - 1]);
+                }
+                p += 2;
+            }
+
+            '*' => {
+                for n in 0..=name.len() {
+                    next[n] = matched[n] || (n > 0 && next[n - 1] && name[n - 1] != '/');
+                }
+                p += 1;
+            }
+
+            c => {
+                for n in 1..=name.len() {
+                    next[n] = matched[n - 1]
+                        && match c {
+                            '?' => name[n - 1] != '/',
+                            c => name[n - 1] == c,
+                        };
+                }
+                p += 1;
+            }
+        }
+
+        matched = next;
+    }
+
+    matched[name.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", "cl_bob"));
+        assert!(glob_match("cl_*", "cl_bob"));
+        assert!(glob_match("*bob*", "cl_bobcycle"));
+        assert!(glob_match("cl_bo?", "cl_bob"));
+        assert!(!glob_match("cl_bo?", "cl_bobup"));
+        assert!(!glob_match("sv_*", "cl_bob"));
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "cl_bob"));
+
+        assert!(glob_match("maps/*.bsp", "maps/e1m1.bsp"));
+        assert!(glob_match("maps/e?m?.bsp", "maps/e1m1.bsp"));
+        assert!(glob_match("maps/*", "maps/e1m1.bsp"));
+        assert!(!glob_match("maps/*.bsp", "maps/e1m1.bsp.bak"));
+        assert!(!glob_match("maps/*.bsp", "maps/b_models/b_bh10.bsp"));
+        assert!(glob_match("maps/**.bsp", "maps/b_models/b_bh10.bsp"));
+        assert!(glob_match("**", "gfx/pop.lmp"));
+        assert!(!glob_match("*", "gfx/pop.lmp"));
+        assert!(!glob_match("gfx/?", "gfx/"));
+
+        // patterns with many stars don't take exponential time
+        let name = "a".repeat(100);
+        assert!(!glob_match(&format!("{}b", "*a".repeat(20)), &name));
+        assert!(!glob_match(&format!("{}b", "**a".repeat(20)), &name));
+    }
+
+    #[test]
+    fn test_join_args() {
+        assert_eq!(join_args(&["say", "hello world"]), "say \"hello world\"");
+        assert_eq!(join_args(&["name", "player"]), "name player");
+        assert_eq!(join_args(&["echo", ""]), "echo \"\"");
+    }
+}
@@ -47,6 +47,17 @@ impl Display for ConsoleError {
 
 #[derive(Clone, Eq, PartialEq, Debug, Fail)]
 pub enum ConsoleErrorKind {
+    #[fail(display = "Alias recursion depth exceeded: {}", name)]
+    AliasRecursion { name: String },
+    #[fail(
+        display = "Invalid value for {}: \"{}\" (expected {})",
+        name, value, expected
+    )]
+    CvarInvalidValue {
+        name: String,
+        value: String,
+        expected: String,
+    },
     #[fail(display = "Failed to parse cvar as a number: {} = {}", name, value)]
     CvarParseFailed { name: String, value: String },
     #[fail(display = "Command already registered: {}", name)]
@@ -57,4 +68,6 @@ pub enum ConsoleErrorKind {
     NoSuchCommand { name: String },
     #[fail(display = "No such cvar: {}", name)]
     NoSuchCvar { name: String },
+    #[fail(display = "Parse error at column {}: {}", column, line)]
+    ParseFailed { line: String, column: usize },
 }
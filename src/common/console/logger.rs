@@ -0,0 +1,174 @@
+//! A `log` backend that copies log records to the console.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+use crate::common::console::ConsoleOutput;
+
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+// records logged since the console was last flushed
+type Records = Arc<Mutex<Vec<(Level, String)>>>;
+
+/// Returns the most verbose level shown in the console for a value of the `developer` cvar.
+pub fn developer_level(developer: usize) -> LevelFilter {
+    match developer {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+struct ConsoleLogger {
+    stderr: env_logger::Logger,
+    console_level: Arc<AtomicUsize>,
+    records: Records,
+}
+
+impl ConsoleLogger {
+    fn console_enabled(&self, level: Level) -> bool {
+        level as usize <= self.console_level.load(Ordering::Relaxed)
+    }
+}
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.stderr.enabled(metadata) || self.console_enabled(metadata.level())
+    }
+
+    fn log(&self, record: &Record) {
+        if self.stderr.matches(record) {
+            self.stderr.log(record);
+        }
+
+        if self.console_enabled(record.level()) {
+            if let Ok(mut records) = self.records.lock() {
+                records.push((record.level(), record.args().to_string()));
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.stderr.flush();
+    }
+}
+
+/// A handle to the console logger.
+///
+/// Log records may come from any thread, so they're queued until the console is flushed on the
+/// main thread.
+#[derive(Clone)]
+pub struct ConsoleLog {
+    stderr_level: LevelFilter,
+    console_level: Arc<AtomicUsize>,
+    records: Records,
+}
+
+impl ConsoleLog {
+    /// Installs the console logger as the global logger.
+    ///
+    /// Records are also written to stderr as filtered by the `RUST_LOG` environment variable.
+    pub fn init() -> Result<ConsoleLog, SetLoggerError> {
+        let stderr = env_logger::Builder::from_env(env_logger::Env::default()).build();
+        let stderr_level = stderr.filter();
+        let console_level = Arc::new(AtomicUsize::new(developer_level(0) as usize));
+        let records = Arc::new(Mutex::new(Vec::new()));
+
+        log::set_boxed_logger(Box::new(ConsoleLogger {
+            stderr,
+            console_level: console_level.clone(),
+            records: records.clone(),
+        }))?;
+
+        let log = ConsoleLog {
+            stderr_level,
+            console_level,
+            records,
+        };
+        log.set_developer(0);
+
+        Ok(log)
+    }
+
+    /// Sets the verbosity of the console log from the value of the `developer` cvar.
+    pub fn set_developer(&self, developer: usize) {
+        let level = developer_level(developer);
+        self.console_level.store(level as usize, Ordering::Relaxed);
+        log::set_max_level(level.max(self.stderr_level));
+    }
+
+    /// Prints the records logged since the last flush to `output`.
+    ///
+    /// Errors and warnings are highlighted.
+    pub fn flush(&self, output: &mut ConsoleOutput) {
+        let records = match self.records.lock() {
+            Ok(mut r) => std::mem::replace(&mut *r, Vec::new()),
+            Err(_) => return,
+        };
+
+        for (level, msg) in records {
+            let (prefix, highlight) = match level {
+                Level::Error => ("Error: ", true),
+                Level::Warn => ("Warning: ", true),
+                _ => ("", false),
+            };
+
+            for (i, line) in msg.lines().enumerate() {
+                let line = if i == 0 {
+                    format!("{}{}", prefix, line)
+                } else {
+                    line.to_owned()
+                };
+
+                if highlight {
+                    output.println_highlighted(line);
+                } else {
+                    output.println(line);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_flush() {
+        let log = ConsoleLog {
+            stderr_level: LevelFilter::Off,
+            console_level: Arc::new(AtomicUsize::new(LevelFilter::Warn as usize)),
+            records: Arc::new(Mutex::new(vec![
+                (Level::Warn, "low on memory".to_owned()),
+                (Level::Info, "first\nsecond".to_owned()),
+            ])),
+        };
+
+        let mut output = ConsoleOutput::new();
+        log.flush(&mut output);
+
+        let lines: Vec<(String, bool)> = output
+            .entries()
+            .map(|l| (l.text().iter().collect(), l.highlight()))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                ("second".to_owned(), false),
+                ("first".to_owned(), false),
+                ("Warning: low on memory".to_owned(), true),
+            ]
+        );
+        assert!(log.records.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_developer_level() {
+        assert_eq!(developer_level(0), LevelFilter::Warn);
+        assert_eq!(developer_level(1), LevelFilter::Debug);
+        assert_eq!(developer_level(2), LevelFilter::Trace);
+    }
+}
@@ -18,36 +18,54 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+mod cmds;
 mod error;
-pub use self::error::{ConsoleError, ConsoleErrorKind};
+mod logger;
+pub use self::{
+    cmds::{glob_match, join_args},
+    error::{ConsoleError, ConsoleErrorKind},
+    logger::ConsoleLog,
+};
 
 use std::{
-    cell::{Ref, RefCell},
+    cell::{Cell, Ref, RefCell, RefMut},
     collections::{HashMap, VecDeque},
+    fmt,
+    io::{self, BufRead, Write},
     iter::FromIterator,
     rc::Rc,
 };
 
-use crate::common::parse;
+use crate::common::{parse, vfs::Vfs};
 
-use failure::{Error, ResultExt};
+use chrono::{DateTime, Local};
+use failure::Error;
 
 /// Stores console commands.
 pub struct CmdRegistry {
-    cmds: HashMap<String, Box<dyn Fn(&[&str])>>,
+    // commands are reference-counted so they can run without the registry being borrowed
+    cmds: HashMap<String, Rc<dyn Fn(&[&str]) -> Result<(), Error>>>,
+
+    // argument completers, keyed by command name
+    completers: HashMap<String, Box<dyn Fn(usize) -> Vec<String>>>,
 }
 
 impl CmdRegistry {
     pub fn new() -> CmdRegistry {
         CmdRegistry {
             cmds: HashMap::new(),
+            completers: HashMap::new(),
         }
     }
 
     /// Registers a new command with the given name.
     ///
     /// Returns an error if a command with the specified name already exists.
-    pub fn insert<S>(&mut self, name: S, cmd: Box<dyn Fn(&[&str])>) -> Result<(), ConsoleError>
+    pub fn insert<S>(
+        &mut self,
+        name: S,
+        cmd: Box<dyn Fn(&[&str]) -> Result<(), Error>>,
+    ) -> Result<(), ConsoleError>
     where
         S: AsRef<str>,
     {
@@ -56,7 +74,7 @@ impl CmdRegistry {
                 name: name.as_ref().to_string(),
             })?,
             None => {
-                self.cmds.insert(name.as_ref().to_owned(), cmd);
+                self.cmds.insert(name.as_ref().to_owned(), Rc::from(cmd));
             }
         }
 
@@ -64,11 +82,11 @@ impl CmdRegistry {
     }
 
     /// Registers a new command with the given name, or replaces one if the name is in use.
-    pub fn insert_or_replace<S>(&mut self, name: S, cmd: Box<dyn Fn(&[&str])>)
+    pub fn insert_or_replace<S>(&mut self, name: S, cmd: Box<dyn Fn(&[&str]) -> Result<(), Error>>)
     where
         S: AsRef<str>,
     {
-        self.cmds.insert(name.as_ref().to_owned(), cmd);
+        self.cmds.insert(name.as_ref().to_owned(), Rc::from(cmd));
     }
 
     /// Removes the command with the given name.
@@ -88,8 +106,8 @@ impl CmdRegistry {
 
     /// Executes a command.
     ///
-    /// Returns an error if no command with the specified name exists.
-    pub fn exec<S>(&mut self, name: S, args: &[&str]) -> Result<(), ConsoleError>
+    /// Returns an error if no command with the specified name exists or if the command fails.
+    pub fn exec<S>(&self, name: S, args: &[&str]) -> Result<(), Error>
     where
         S: AsRef<str>,
     {
@@ -99,8 +117,18 @@ impl CmdRegistry {
             .ok_or(ConsoleErrorKind::NoSuchCommand {
                 name: name.as_ref().to_owned(),
             })?;
-        cmd(args);
-        Ok(())
+        cmd(args)
+    }
+
+    /// Returns the command with the given name, if it exists.
+    ///
+    /// Unlike `exec`, this allows the command to be called once the registry is no longer
+    /// borrowed, so that it may add or remove commands itself.
+    pub fn get<S>(&self, name: S) -> Option<Rc<dyn Fn(&[&str]) -> Result<(), Error>>>
+    where
+        S: AsRef<str>,
+    {
+        self.cmds.get(name.as_ref()).cloned()
     }
 
     pub fn contains<S>(&self, name: S) -> bool
@@ -109,6 +137,118 @@ impl CmdRegistry {
     {
         self.cmds.contains_key(name.as_ref())
     }
+
+    /// Returns an iterator over the names of all registered commands.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.cmds.keys().map(|k| k.as_str())
+    }
+
+    /// Registers an argument completer for the command with the given name.
+    ///
+    /// The completer is called with the index of the argument being completed and returns all
+    /// valid values for that argument. Completers are independent of the commands themselves, so
+    /// they persist if the command is removed or replaced.
+    pub fn insert_completer<S>(&mut self, name: S, completer: Box<dyn Fn(usize) -> Vec<String>>)
+    where
+        S: AsRef<str>,
+    {
+        self.completers.insert(name.as_ref().to_owned(), completer);
+    }
+
+    /// Returns all valid values for argument `arg_id` of the named command.
+    ///
+    /// If the command has no completer, returns an empty `Vec`.
+    pub fn complete_arg<S>(&self, name: S, arg_id: usize) -> Vec<String>
+    where
+        S: AsRef<str>,
+    {
+        match self.completers.get(name.as_ref()) {
+            Some(completer) => completer(arg_id),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// The type of value held by a cvar.
+///
+/// Values assigned to a cvar are validated against its kind.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CvarKind {
+    /// Any string.
+    String,
+
+    /// Either `0` or `1`.
+    Bool,
+
+    /// An integer, optionally restricted to a range.
+    Int { min: Option<i32>, max: Option<i32> },
+
+    /// A floating-point number, optionally restricted to a range.
+    Float { min: Option<f32>, max: Option<f32> },
+
+    /// One of a fixed set of values.
+    Enum(&'static [&'static str]),
+}
+
+// parses the numeric value of a cvar, which is stored so it doesn't have to be parsed on each use
+fn parse_number(value: &str) -> Option<f32> {
+    value.parse::<f32>().ok().filter(|v| v.is_finite())
+}
+
+// returns true if value lies within the (inclusive) range
+fn in_range<T>(value: T, min: Option<T>, max: Option<T>) -> bool
+where
+    T: PartialOrd,
+{
+    min.map_or(true, |m| value >= m) && max.map_or(true, |m| value <= m)
+}
+
+// describes the range in a form like "between 0 and 1"
+fn fmt_range<T>(f: &mut fmt::Formatter, min: Option<T>, max: Option<T>) -> fmt::Result
+where
+    T: fmt::Display,
+{
+    match (min, max) {
+        (Some(min), Some(max)) => write!(f, " between {} and {}", min, max),
+        (Some(min), None) => write!(f, " of at least {}", min),
+        (None, Some(max)) => write!(f, " of at most {}", max),
+        (None, None) => Ok(()),
+    }
+}
+
+impl CvarKind {
+    /// Returns true if `value` is a valid value for a cvar of this kind.
+    pub fn accepts(&self, value: &str) -> bool {
+        match *self {
+            CvarKind::String => true,
+            CvarKind::Bool => value == "0" || value == "1",
+            CvarKind::Int { min, max } => value
+                .parse::<i32>()
+                .map_or(false, |v| in_range(v, min, max)),
+            CvarKind::Float { min, max } => value
+                .parse::<f32>()
+                .map_or(false, |v| v.is_finite() && in_range(v, min, max)),
+            CvarKind::Enum(choices) => choices.contains(&value),
+        }
+    }
+}
+
+impl fmt::Display for CvarKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CvarKind::String => write!(f, "a string"),
+            CvarKind::Bool => write!(f, "0 or 1"),
+            CvarKind::Int { min, max } => {
+                write!(f, "an integer")?;
+                fmt_range(f, min, max)
+            }
+            CvarKind::Float { min, max } => {
+                write!(f, "a number")?;
+                fmt_range(f, min, max)
+            }
+            CvarKind::Enum(choices) => write!(f, "one of: {}", choices.join(", ")),
+        }
+    }
 }
 
 /// A configuration variable.
@@ -118,6 +258,10 @@ struct Cvar {
     // Value of this variable
     val: String,
 
+    // Numeric value of this variable, or None if it isn't a number. Bool, Int and Float cvars
+    // always have one, since their values are validated on assignment.
+    num: Option<f32>,
+
     // If true, this variable should be archived in vars.rc
     archive: bool,
 
@@ -128,10 +272,88 @@ struct Cvar {
 
     // The default value of this variable
     default: String,
+
+    // The type of this variable, used to validate assignments
+    kind: CvarKind,
+
+    // A short explanation of this variable, displayed by the `help` command
+    description: Option<String>,
+}
+
+/// Information about a registered cvar.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CvarInfo {
+    pub value: String,
+    pub default: String,
+    pub kind: CvarKind,
+    pub description: Option<String>,
+    pub archive: bool,
+}
+
+/// Builds a cvar definition to be added to a `CvarRegistry`.
+///
+/// Created by `CvarRegistry::define`.
+pub struct CvarBuilder<'a> {
+    registry: &'a CvarRegistry,
+    name: String,
+    cvar: Cvar,
+}
+
+impl<'a> CvarBuilder<'a> {
+    /// Sets the type of the cvar. Defaults to `CvarKind::String`.
+    pub fn kind(mut self, kind: CvarKind) -> CvarBuilder<'a> {
+        self.cvar.kind = kind;
+        self
+    }
+
+    /// Sets the description displayed by `help`.
+    pub fn description<S>(mut self, description: S) -> CvarBuilder<'a>
+    where
+        S: AsRef<str>,
+    {
+        self.cvar.description = Some(description.as_ref().to_owned());
+        self
+    }
+
+    /// Marks the cvar to be saved to `config.cfg`.
+    pub fn archive(mut self) -> CvarBuilder<'a> {
+        self.cvar.archive = true;
+        self
+    }
+
+    /// Marks the cvar as part of the userinfo or serverinfo.
+    pub fn notify(mut self) -> CvarBuilder<'a> {
+        self.cvar.notify = true;
+        self
+    }
+
+    /// Adds the cvar to the registry.
+    ///
+    /// Returns an error if a cvar with the same name already exists or if the default value is
+    /// invalid for the cvar's kind.
+    pub fn register(self) -> Result<(), ConsoleError> {
+        self.registry.insert(self.name, self.cvar)
+    }
+}
+
+/// Identifies a callback registered with `CvarRegistry::on_change` or `CvarRegistry::on_notify`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CvarCallbackId(usize);
+
+struct CvarCallback {
+    id: CvarCallbackId,
+
+    // the cvar this callback watches, or None to watch all notify cvars
+    cvar: Option<String>,
+
+    // called with the name and new value of the cvar
+    callback: Rc<dyn Fn(&str, &str)>,
 }
 
 pub struct CvarRegistry {
     cvars: RefCell<HashMap<String, Cvar>>,
+    callbacks: RefCell<Vec<CvarCallback>>,
+    next_callback_id: Cell<usize>,
 }
 
 impl CvarRegistry {
@@ -139,7 +361,51 @@ impl CvarRegistry {
     pub fn new() -> CvarRegistry {
         CvarRegistry {
             cvars: RefCell::new(HashMap::new()),
+            callbacks: RefCell::new(Vec::new()),
+            next_callback_id: Cell::new(0),
+        }
+    }
+
+    /// Begins the definition of a new cvar with the given name and default value.
+    ///
+    /// The cvar isn't registered until `CvarBuilder::register` is called.
+    pub fn define<S>(&self, name: S, default: S) -> CvarBuilder
+    where
+        S: AsRef<str>,
+    {
+        CvarBuilder {
+            registry: self,
+            name: name.as_ref().to_owned(),
+            cvar: Cvar {
+                val: default.as_ref().to_owned(),
+                num: parse_number(default.as_ref()),
+                archive: false,
+                notify: false,
+                default: default.as_ref().to_owned(),
+                kind: CvarKind::String,
+                description: None,
+            },
+        }
+    }
+
+    fn insert(&self, name: String, cvar: Cvar) -> Result<(), ConsoleError> {
+        if !cvar.kind.accepts(&cvar.default) {
+            Err(ConsoleErrorKind::CvarInvalidValue {
+                name: name.clone(),
+                value: cvar.default.clone(),
+                expected: cvar.kind.to_string(),
+            })?;
+        }
+
+        let mut cvars = self.cvars.borrow_mut();
+        match cvars.get(&name) {
+            Some(_) => Err(ConsoleErrorKind::DuplicateCvar { name })?,
+            None => {
+                cvars.insert(name, cvar);
+            }
         }
+
+        Ok(())
     }
 
     fn register_impl<S>(
@@ -152,28 +418,15 @@ impl CvarRegistry {
     where
         S: AsRef<str>,
     {
-        let name = name.as_ref();
-        let default = default.as_ref();
-
-        let mut cvars = self.cvars.borrow_mut();
-        match cvars.get(name) {
-            Some(_) => Err(ConsoleErrorKind::DuplicateCvar {
-                name: name.to_owned(),
-            })?,
-            None => {
-                cvars.insert(
-                    name.to_owned(),
-                    Cvar {
-                        val: default.to_owned(),
-                        archive,
-                        notify,
-                        default: default.to_owned(),
-                    },
-                );
-            }
+        let mut builder = self.define(name, default);
+        if archive {
+            builder = builder.archive();
+        }
+        if notify {
+            builder = builder.notify();
         }
 
-        Ok(())
+        builder.register()
     }
 
     /// Register a new `Cvar` with the given name.
@@ -200,6 +453,8 @@ impl CvarRegistry {
     /// When this `Cvar` is set:
     /// - If the host is a server, broadcast that the variable has been changed to all clients.
     /// - If the host is a client, update the clientinfo string.
+    ///
+    /// The host is informed of changes through callbacks registered with `on_notify`.
     pub fn register_notify<S>(&self, name: S, default: S) -> Result<(), ConsoleError>
     where
         S: AsRef<str>,
@@ -215,7 +470,7 @@ impl CvarRegistry {
     /// Additionally, when this `Cvar` is set:
     /// - If the host is a server, broadcast that the variable has been changed to all clients.
     /// - If the host is a client, update the clientinfo string.
-    pub fn register_archive_notify<S>(&mut self, name: S, default: S) -> Result<(), ConsoleError>
+    pub fn register_archive_notify<S>(&self, name: S, default: S) -> Result<(), ConsoleError>
     where
         S: AsRef<str>,
     {
@@ -237,60 +492,204 @@ impl CvarRegistry {
             .clone())
     }
 
+    /// Returns the numeric value of a cvar.
+    ///
+    /// Returns an error if the cvar's value isn't a number, which can only happen for cvars of
+    /// kind `CvarKind::String` or `CvarKind::Enum`.
     pub fn get_value<S>(&self, name: S) -> Result<f32, ConsoleError>
     where
         S: AsRef<str>,
     {
         let name = name.as_ref();
-        let mut cvars = self.cvars.borrow_mut();
-        let cvar = cvars.get_mut(name).ok_or(ConsoleErrorKind::NoSuchCvar {
+        let cvars = self.cvars.borrow();
+        let cvar = cvars.get(name).ok_or(ConsoleErrorKind::NoSuchCvar {
             name: name.to_owned(),
         })?;
 
-        // try parsing as f32
-        let val_string = cvar.val.clone();
-        let val = match val_string.parse::<f32>() {
-            Ok(v) => Ok(v),
-            // if parse fails, reset to default valud and try again
-            Err(_) => {
-                cvar.val = cvar.default.clone();
-                cvar.val.parse::<f32>()
+        match cvar.num {
+            Some(num) => Ok(num),
+            None => Err(ConsoleErrorKind::CvarParseFailed {
+                name: name.to_owned(),
+                value: cvar.val.clone(),
+            })?,
+        }
+    }
+
+    /// Sets the value of a cvar.
+    ///
+    /// Returns an error if the value isn't valid for the cvar's kind. If the value changes, any
+    /// callbacks registered for the cvar with `on_change` are called with the new value, as are
+    /// any registered with `on_notify` if it is a notify cvar.
+    pub fn set<S>(&self, name: S, value: S) -> Result<(), ConsoleError>
+    where
+        S: AsRef<str>,
+    {
+        let name = name.as_ref();
+        let value = value.as_ref();
+        trace!("cvar assignment: {} {}", name, value);
+
+        let (changed, notify) = {
+            let mut cvars = self.cvars.borrow_mut();
+            let mut cvar = cvars.get_mut(name).ok_or(ConsoleErrorKind::NoSuchCvar {
+                name: name.to_owned(),
+            })?;
+
+            if !cvar.kind.accepts(value) {
+                Err(ConsoleErrorKind::CvarInvalidValue {
+                    name: name.to_owned(),
+                    value: value.to_owned(),
+                    expected: cvar.kind.to_string(),
+                })?;
+            }
+
+            let changed = cvar.val != value;
+            cvar.val = value.to_owned();
+            cvar.num = parse_number(value);
+            (changed, cvar.notify)
+        };
+
+        if changed {
+            // clone the callbacks so they're free to access the registry
+            let callbacks: Vec<Rc<dyn Fn(&str, &str)>> = self
+                .callbacks
+                .borrow()
+                .iter()
+                .filter(|c| match c.cvar {
+                    Some(ref cvar) => cvar == name,
+                    None => notify,
+                })
+                .map(|c| c.callback.clone())
+                .collect();
+
+            for callback in callbacks {
+                callback(name, value);
             }
         }
-        .context(ConsoleErrorKind::CvarParseFailed {
-            name: name.to_owned(),
-            value: cvar.val.clone(),
-        })?;
 
-        Ok(val)
+        Ok(())
     }
 
-    pub fn set<S>(&self, name: S, value: S) -> Result<(), ConsoleError>
+    fn add_callback(
+        &self,
+        cvar: Option<String>,
+        callback: Rc<dyn Fn(&str, &str)>,
+    ) -> CvarCallbackId {
+        let id = CvarCallbackId(self.next_callback_id.get());
+        self.next_callback_id.set(id.0 + 1);
+        self.callbacks
+            .borrow_mut()
+            .push(CvarCallback { id, cvar, callback });
+        id
+    }
+
+    /// Registers a callback to be called with the new value whenever the named cvar changes.
+    pub fn on_change<S>(
+        &self,
+        name: S,
+        callback: Box<dyn Fn(&str)>,
+    ) -> Result<CvarCallbackId, ConsoleError>
     where
         S: AsRef<str>,
     {
-        trace!("cvar assignment: {} {}", name.as_ref(), value.as_ref());
-        let mut cvars = self.cvars.borrow_mut();
-        let mut cvar = cvars
-            .get_mut(name.as_ref())
-            .ok_or(ConsoleErrorKind::NoSuchCvar {
-                name: name.as_ref().to_owned(),
+        let name = name.as_ref();
+        if !self.contains(name) {
+            Err(ConsoleErrorKind::NoSuchCvar {
+                name: name.to_owned(),
             })?;
-        cvar.val = value.as_ref().to_owned();
-        if cvar.notify {
-            // TODO: update userinfo/serverinfo
-            unimplemented!();
+        }
+
+        Ok(self.add_callback(
+            Some(name.to_owned()),
+            Rc::new(move |_, value| callback(value)),
+        ))
+    }
+
+    /// Registers a callback to be called with the name and new value whenever any notify cvar
+    /// changes.
+    pub fn on_notify(&self, callback: Box<dyn Fn(&str, &str)>) -> CvarCallbackId {
+        self.add_callback(None, Rc::from(callback))
+    }
+
+    /// Removes a callback registered with `on_change` or `on_notify`.
+    pub fn remove_callback(&self, id: CvarCallbackId) {
+        self.callbacks.borrow_mut().retain(|c| c.id != id);
+    }
+
+    /// Resets a cvar to its default value.
+    pub fn reset<S>(&self, name: S) -> Result<(), ConsoleError>
+    where
+        S: AsRef<str>,
+    {
+        let default = self.info(name.as_ref())?.default;
+        self.set(name.as_ref(), default.as_str())
+    }
+
+    /// Resets all cvars to their default values.
+    pub fn reset_all(&self) -> Result<(), ConsoleError> {
+        for name in self.names() {
+            self.reset(name)?;
         }
 
         Ok(())
     }
 
+    /// Returns information about the named cvar.
+    pub fn info<S>(&self, name: S) -> Result<CvarInfo, ConsoleError>
+    where
+        S: AsRef<str>,
+    {
+        let cvars = self.cvars.borrow();
+        let cvar = cvars
+            .get(name.as_ref())
+            .ok_or(ConsoleErrorKind::NoSuchCvar {
+                name: name.as_ref().to_owned(),
+            })?;
+
+        Ok(CvarInfo {
+            value: cvar.val.clone(),
+            default: cvar.default.clone(),
+            kind: cvar.kind.clone(),
+            description: cvar.description.clone(),
+            archive: cvar.archive,
+        })
+    }
+
     pub fn contains<S>(&self, name: S) -> bool
     where
         S: AsRef<str>,
     {
         self.cvars.borrow().contains_key(name.as_ref())
     }
+
+    /// Writes the values of all archived cvars as a console script.
+    ///
+    /// Values are quoted, and quotes can't be escaped, so cvars whose values contain quotes or line
+    /// breaks are left out with a warning.
+    pub fn write_archived<W>(&self, writer: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let cvars = self.cvars.borrow();
+        let mut archived: Vec<(&String, &Cvar)> =
+            cvars.iter().filter(|(_, cvar)| cvar.archive).collect();
+        archived.sort_by(|a, b| a.0.cmp(b.0));
+
+        for (name, cvar) in archived {
+            if cvar.val.contains(&['"', '\n'][..]) {
+                warn!("Not saving {}: its value can't be quoted", name);
+                continue;
+            }
+
+            writeln!(writer, "{} \"{}\"", name, cvar.val)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the names of all registered cvars.
+    pub fn names(&self) -> Vec<String> {
+        self.cvars.borrow().keys().cloned().collect()
+    }
 }
 
 /// The line of text currently being edited in the console.
@@ -385,6 +784,9 @@ impl ConsoleInput {
     }
 }
 
+/// The maximum number of lines kept in the command history.
+pub const MAX_HISTORY: usize = 512;
+
 pub struct History {
     lines: VecDeque<Vec<char>>,
     curs: usize,
@@ -398,14 +800,22 @@ impl History {
         }
     }
 
+    /// Adds a line to the history.
+    ///
+    /// Empty lines and lines identical to the previous one aren't added.
     pub fn add_line(&mut self, line: Vec<char>) {
-        self.lines.push_front(line);
         self.curs = 0;
+
+        if line.is_empty() || self.lines.front() == Some(&line) {
+            return;
+        }
+
+        self.lines.push_front(line);
+        self.lines.truncate(MAX_HISTORY);
     }
 
-    // TODO: handle case where history is empty
     pub fn line_up(&mut self) -> Option<Vec<char>> {
-        if self.lines.len() == 0 || self.curs >= self.lines.len() {
+        if self.curs >= self.lines.len() {
             None
         } else {
             self.curs += 1;
@@ -421,112 +831,528 @@ impl History {
         if self.curs > 0 {
             Some(self.lines[self.curs - 1].clone())
         } else {
-            Some(Vec::new().clone())
+            Some(Vec::new())
         }
     }
-}
 
-pub struct ConsoleOutput {
-    lines: VecDeque<Vec<char>>,
-}
+    /// Returns the index and text of the newest line containing `query`, starting from the line
+    /// at `start` and searching back.
+    pub fn find(&self, query: &str, start: usize) -> Option<(usize, Vec<char>)> {
+        self.lines
+            .iter()
+            .enumerate()
+            .skip(start)
+            .find(|(_, line)| String::from_iter(line.iter()).contains(query))
+            .map(|(i, line)| (i, line.clone()))
+    }
 
-impl ConsoleOutput {
-    pub fn new() -> ConsoleOutput {
-        ConsoleOutput {
-            lines: VecDeque::new(),
+    /// Reads history from a file written by `write`, replacing the current history.
+    pub fn read<R>(&mut self, reader: R) -> io::Result<()>
+    where
+        R: BufRead,
+    {
+        self.lines.clear();
+        self.curs = 0;
+
+        for line in reader.lines() {
+            self.add_line(line?.chars().collect());
         }
-    }
 
-    pub fn push(&mut self, chars: Vec<char>) {
-        self.lines.push_front(chars);
-        // TODO: set maximum capacity and pop_back when we reach it
+        Ok(())
     }
 
-    pub fn lines(&self) -> impl Iterator<Item = &[char]> {
-        self.lines.iter().map(|v| v.as_slice())
+    /// Writes the history, oldest line first.
+    ///
+    /// Lines which may set a password (e.g. `rcon_password hunter2`) are left out.
+    pub fn write<W>(&self, writer: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        for line in self.lines.iter().rev() {
+            let line = String::from_iter(line.iter());
+            if !sets_password(&line) {
+                writeln!(writer, "{}", line)?;
+            }
+        }
+
+        Ok(())
     }
 }
 
-pub struct Console {
-    cmds: Rc<RefCell<CmdRegistry>>,
-    cvars: Rc<RefCell<CvarRegistry>>,
-    aliases: Rc<RefCell<HashMap<String, String>>>,
+// returns true if any word of `line` names a password cvar and is followed by a possible value
+fn sets_password(line: &str) -> bool {
+    let mut words = line
+        .split(|c: char| c.is_whitespace() || c == ';' || c == '"')
+        .filter(|w| !w.is_empty());
 
-    input: ConsoleInput,
-    hist: History,
-    buffer: RefCell<String>,
-    output: Rc<RefCell<ConsoleOutput>>,
+    words.any(|w| w.to_lowercase().ends_with("password")) && words.next().is_some()
 }
 
-impl Console {
-    pub fn new(cmds: Rc<RefCell<CmdRegistry>>, cvars: Rc<RefCell<CvarRegistry>>) -> Console {
-        let output = Rc::new(RefCell::new(ConsoleOutput::new()));
-        let echo_output = output.clone();
-        cmds.borrow_mut()
-            .insert(
-                "echo",
-                Box::new(move |args| {
-                    let msg = match args.len() {
-                        0 => "",
-                        _ => args[0],
-                    };
+/// The default number of lines kept in the console scrollback.
+pub const DEFAULT_SCROLLBACK: usize = 1024;
 
-                    echo_output.borrow_mut().push(msg.chars().collect());
-                }),
-            )
-            .unwrap();
+/// A line of console output.
+pub struct OutputLine {
+    text: Vec<char>,
+    time: DateTime<Local>,
+    highlight: bool,
+}
 
-        let aliases: Rc<RefCell<HashMap<String, String>>> = Rc::new(RefCell::new(HashMap::new()));
-        let cmd_aliases = aliases.clone();
-        cmds.borrow_mut()
-            .insert(
-                "alias",
-                Box::new(move |args| match args.len() {
-                    0 => {
-                        for (name, script) in cmd_aliases.borrow().iter() {
-                            println!("    {}: {}", name, script);
-                        }
-                        println!("{} alias command(s)", cmd_aliases.borrow().len());
-                    }
+impl OutputLine {
+    pub fn text(&self) -> &[char] {
+        &self.text
+    }
 
-                    2 => {
-                        let name = args[0].to_string();
-                        let script = args[1].to_string();
-                        let _ = cmd_aliases.borrow_mut().insert(name, script);
-                    }
+    /// Returns true if the line should be drawn with the high-bit conchars, which appear in a
+    /// different color.
+    pub fn highlight(&self) -> bool {
+        self.highlight
+    }
 
-                    _ => (),
-                }),
-            )
-            .unwrap();
+    /// Returns the time at which the line was printed.
+    pub fn time(&self) -> DateTime<Local> {
+        self.time
+    }
 
-        Console {
-            cmds,
-            cvars,
-            aliases: aliases.clone(),
-            input: ConsoleInput::new(),
-            hist: History::new(),
-            buffer: RefCell::new(String::new()),
-            output: output.clone(),
-        }
+    /// Returns the line's timestamp in the form displayed by the console.
+    pub fn timestamp(&self) -> String {
+        self.time.format("[%H:%M:%S] ").to_string()
     }
+}
 
-    pub fn send_char(&mut self, c: char) -> Result<(), Error> {
-        match c {
-            // ignore grave and escape keys
-            '`' | '\x1b' => (),
+/// The console scrollback.
+///
+/// Lines are stored newest first. Once the scrollback is full, the oldest line is discarded for
+/// each new line.
+pub struct ConsoleOutput {
+    lines: VecDeque<OutputLine>,
+    capacity: usize,
 
-            '\r' => {
-                // cap with a newline and push to the execution buffer
-                let mut entered = self.get_string();
-                entered.push('\n');
-                self.buffer.borrow_mut().push_str(&entered);
+    // the number of lines the view is scrolled back from the newest line
+    scroll: usize,
 
-                // add the current input to the history
-                self.hist.add_line(self.input.get_text());
+    // whether lines should be displayed with the time they were printed
+    timestamps: bool,
 
-                // echo the input to console output
-                let mut input_echo: Vec<char> = vec![']'];
+    // true if the newest line wasn't terminated by a newline, so `print` should continue it
+    partial: bool,
+
+    // if Some, text printed to the output is also collected here
+    capture: Option<String>,
+}
+
+impl ConsoleOutput {
+    pub fn new() -> ConsoleOutput {
+        ConsoleOutput::with_capacity(DEFAULT_SCROLLBACK)
+    }
+
+    pub fn with_capacity(capacity: usize) -> ConsoleOutput {
+        ConsoleOutput {
+            lines: VecDeque::new(),
+            capacity,
+            scroll: 0,
+            timestamps: false,
+            partial: false,
+            capture: None,
+        }
+    }
+
+    pub fn push(&mut self, chars: Vec<char>) {
+        self.push_line(chars, false);
+    }
+
+    fn push_line(&mut self, chars: Vec<char>, highlight: bool) {
+        if let Some(ref mut capture) = self.capture {
+            capture.extend(chars.iter());
+            capture.push('\n');
+        }
+
+        self.lines.push_front(OutputLine {
+            text: chars,
+            time: Local::now(),
+            highlight,
+        });
+        self.partial = false;
+
+        // keep the view on the same line while scrolled back
+        if self.scroll > 0 {
+            self.scroll += 1;
+        }
+
+        self.truncate();
+    }
+
+    /// Pushes a line of text to the output.
+    pub fn println<S>(&mut self, text: S)
+    where
+        S: AsRef<str>,
+    {
+        self.push(text.as_ref().chars().collect());
+    }
+
+    /// Pushes a line of text to the output to be drawn in the highlight color.
+    pub fn println_highlighted<S>(&mut self, text: S)
+    where
+        S: AsRef<str>,
+    {
+        self.push_line(text.as_ref().chars().collect(), true);
+    }
+
+    /// Prints text to the output, only ending the current line at a newline.
+    ///
+    /// This matches the behavior of Quake's console, where a message may be printed in pieces.
+    pub fn print<S>(&mut self, text: S)
+    where
+        S: AsRef<str>,
+    {
+        // capture the text as printed, rather than line by line
+        let capture = self.capture.take().map(|mut c| {
+            c.push_str(text.as_ref());
+            c
+        });
+
+        let mut segments = text.as_ref().split('\n').peekable();
+        while let Some(segment) = segments.next() {
+            let last = segments.peek().is_none();
+            let partial = self.partial;
+
+            if partial {
+                if let Some(line) = self.lines.front_mut() {
+                    line.text.extend(segment.chars());
+                }
+            } else if !last || !segment.is_empty() {
+                self.push(segment.chars().collect());
+            }
+
+            // the line stays open unless the segment ended with a newline
+            self.partial = last && (partial || !segment.is_empty());
+        }
+
+        self.capture = capture;
+    }
+
+    /// Starts collecting text printed to the output.
+    pub fn begin_capture(&mut self) {
+        self.capture = Some(String::new());
+    }
+
+    /// Stops collecting text printed to the output and returns the text printed since
+    /// `begin_capture` was called.
+    pub fn end_capture(&mut self) -> String {
+        self.capture.take().unwrap_or_default()
+    }
+
+    /// Returns the text of each line, newest first.
+    pub fn lines(&self) -> impl Iterator<Item = &[char]> {
+        self.lines.iter().map(|l| l.text())
+    }
+
+    /// Returns each line, newest first.
+    pub fn entries(&self) -> impl Iterator<Item = &OutputLine> {
+        self.lines.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Sets the maximum number of lines to keep, discarding the oldest lines if necessary.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.truncate();
+    }
+
+    fn truncate(&mut self) {
+        self.lines.truncate(self.capacity);
+        self.scroll = self.scroll.min(self.max_scroll());
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.lines.len().saturating_sub(1)
+    }
+
+    /// Returns the number of lines the view is scrolled back from the newest line.
+    pub fn scroll(&self) -> usize {
+        self.scroll
+    }
+
+    /// Scrolls the view back by `lines` lines, stopping at the oldest line.
+    pub fn scroll_up(&mut self, lines: usize) {
+        self.scroll = (self.scroll + lines).min(self.max_scroll());
+    }
+
+    /// Scrolls the view forward by `lines` lines, stopping at the newest line.
+    pub fn scroll_down(&mut self, lines: usize) {
+        self.scroll = self.scroll.saturating_sub(lines);
+    }
+
+    /// Scrolls the view so that the line at `index` (counting back from the newest) is at the
+    /// bottom.
+    pub fn scroll_to(&mut self, index: usize) {
+        self.scroll = index.min(self.max_scroll());
+    }
+
+    pub fn timestamps(&self) -> bool {
+        self.timestamps
+    }
+
+    pub fn set_timestamps(&mut self, timestamps: bool) {
+        self.timestamps = timestamps;
+    }
+
+    /// Returns the index of the newest line containing `query`, ignoring case, starting from the
+    /// line at `start` and searching back.
+    pub fn find(&self, query: &str, start: usize) -> Option<usize> {
+        let query = query.to_lowercase();
+        self.lines
+            .iter()
+            .enumerate()
+            .skip(start)
+            .find(|(_, line)| {
+                String::from_iter(line.text.iter())
+                    .to_lowercase()
+                    .contains(&query)
+            })
+            .map(|(i, _)| i)
+    }
+
+    /// Writes the contents of the scrollback, oldest first.
+    pub fn write_to<W>(&self, writer: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        for line in self.lines.iter().rev() {
+            if self.timestamps {
+                write!(writer, "{}", line.timestamp())?;
+            }
+            writeln!(writer, "{}", String::from_iter(line.text.iter()))?;
+        }
+
+        Ok(())
+    }
+}
+
+// maximum nesting depth of alias expansions within a single frame
+const MAX_ALIAS_DEPTH: usize = 64;
+
+// number of lines scrolled by PageUp and PageDown
+const PAGE_LINES: usize = 8;
+
+// an incremental search through the console scrollback
+struct Search {
+    query: String,
+
+    // the scroll position when the search began
+    origin: usize,
+}
+
+// a reverse search through the command history
+struct HistorySearch {
+    query: String,
+
+    // the index and text of the current match
+    found: Option<(usize, Vec<char>)>,
+}
+
+pub struct Console {
+    cmds: Rc<RefCell<CmdRegistry>>,
+    cvars: Rc<RefCell<CvarRegistry>>,
+    aliases: Rc<RefCell<HashMap<String, String>>>,
+
+    input: ConsoleInput,
+    hist: History,
+    buffer: Rc<RefCell<String>>,
+    output: Rc<RefCell<ConsoleOutput>>,
+
+    // set by the `wait` command to defer the rest of the buffer to the next frame
+    wait: Rc<Cell<bool>>,
+
+    // if true, unrecognized commands are queued to be sent to the server
+    forward_to_server: Rc<Cell<bool>>,
+    forwarded: Rc<RefCell<Vec<String>>>,
+
+    // for each alias being expanded, the length of the buffer following its text
+    alias_tails: RefCell<Vec<usize>>,
+
+    // the same for each script being run by `exec`
+    exec_tails: Rc<RefCell<Vec<usize>>>,
+
+    search: Option<Search>,
+    hist_search: Option<HistorySearch>,
+}
+
+impl Console {
+    pub fn new(
+        cmds: Rc<RefCell<CmdRegistry>>,
+        cvars: Rc<RefCell<CvarRegistry>>,
+        vfs: Rc<Vfs>,
+    ) -> Console {
+        let output = Rc::new(RefCell::new(ConsoleOutput::new()));
+        let aliases: Rc<RefCell<HashMap<String, String>>> = Rc::new(RefCell::new(HashMap::new()));
+        let buffer = Rc::new(RefCell::new(String::new()));
+        let wait = Rc::new(Cell::new(false));
+        let forward_to_server = Rc::new(Cell::new(false));
+        let forwarded = Rc::new(RefCell::new(Vec::new()));
+        let exec_tails = Rc::new(RefCell::new(Vec::new()));
+
+        {
+            let mut c = cmds.borrow_mut();
+            c.insert("echo", cmds::cmd_echo(output.clone())).unwrap();
+            c.insert("alias", cmds::cmd_alias(aliases.clone(), output.clone()))
+                .unwrap();
+            c.insert(
+                "exec",
+                cmds::cmd_exec(vfs, buffer.clone(), exec_tails.clone(), output.clone()),
+            )
+            .unwrap();
+            c.insert("wait", cmds::cmd_wait(wait.clone())).unwrap();
+            c.insert("cmdlist", cmds::cmd_cmdlist(cmds.clone(), output.clone()))
+                .unwrap();
+            c.insert(
+                "cvarlist",
+                cmds::cmd_cvarlist(cvars.clone(), output.clone()),
+            )
+            .unwrap();
+            c.insert("toggle", cmds::cmd_toggle(cvars.clone(), output.clone()))
+                .unwrap();
+            c.insert(
+                "inc",
+                cmds::cmd_inc("inc", 1.0, cvars.clone(), output.clone()),
+            )
+            .unwrap();
+            c.insert(
+                "dec",
+                cmds::cmd_inc("dec", -1.0, cvars.clone(), output.clone()),
+            )
+            .unwrap();
+            c.insert(
+                "cmd",
+                cmds::cmd_cmd(forward_to_server.clone(), forwarded.clone()),
+            )
+            .unwrap();
+
+            c.insert("reset", cmds::cmd_reset(cvars.clone(), output.clone()))
+                .unwrap();
+            c.insert("resetall", cmds::cmd_resetall(cvars.clone()))
+                .unwrap();
+            c.insert("help", cmds::cmd_help(cvars.clone(), output.clone()))
+                .unwrap();
+
+            for name in &["toggle", "inc", "dec", "reset", "help"] {
+                let cvars = cvars.clone();
+                c.insert_completer(
+                    name,
+                    Box::new(move |arg_id| match arg_id {
+                        0 => cvars.borrow().names(),
+                        _ => Vec::new(),
+                    }),
+                );
+            }
+        }
+
+        {
+            let cvars = cvars.borrow();
+            cvars
+                .define("con_scrollback", DEFAULT_SCROLLBACK.to_string().as_str())
+                .kind(CvarKind::Int {
+                    min: Some(1),
+                    max: None,
+                })
+                .description("number of lines kept in the console scrollback")
+                .archive()
+                .register()
+                .unwrap();
+            cvars
+                .define("con_timestamps", "0")
+                .kind(CvarKind::Bool)
+                .description("show the time each line of console output was printed")
+                .archive()
+                .register()
+                .unwrap();
+            cvars
+                .define("developer", "0")
+                .kind(CvarKind::Int {
+                    min: Some(0),
+                    max: Some(2),
+                })
+                .description("print warnings only (0), debug messages (1) or all messages (2)")
+                .register()
+                .unwrap();
+
+            let scrollback_output = output.clone();
+            cvars
+                .on_change(
+                    "con_scrollback",
+                    Box::new(move |value| {
+                        // the cvar kind guarantees a positive integer
+                        scrollback_output
+                            .borrow_mut()
+                            .set_capacity(value.parse().unwrap())
+                    }),
+                )
+                .unwrap();
+            let timestamps_output = output.clone();
+            cvars
+                .on_change(
+                    "con_timestamps",
+                    Box::new(move |value| {
+                        timestamps_output.borrow_mut().set_timestamps(value == "1")
+                    }),
+                )
+                .unwrap();
+        }
+
+        Console {
+            cmds,
+            cvars,
+            aliases,
+            input: ConsoleInput::new(),
+            hist: History::new(),
+            buffer,
+            output,
+            wait,
+            forward_to_server,
+            forwarded,
+            alias_tails: RefCell::new(Vec::new()),
+            exec_tails,
+            search: None,
+            hist_search: None,
+        }
+    }
+
+    pub fn send_char(&mut self, c: char) -> Result<(), Error> {
+        if self.search.is_some() {
+            self.search_char(c);
+            return Ok(());
+        }
+
+        if self.hist_search.is_some() && !self.hist_search_char(c) {
+            return Ok(());
+        }
+
+        match c {
+            // ignore grave and escape keys
+            '`' | '\x1b' => (),
+
+            '\r' => {
+                // cap with a newline and push to the execution buffer
+                let mut entered = self.get_string();
+                entered.push('\n');
+                self.buffer.borrow_mut().push_str(&entered);
+
+                // add the current input to the history
+                self.hist.add_line(self.input.get_text());
+
+                // echo the input to console output
+                let mut input_echo: Vec<char> = vec![']'];
                 input_echo.append(&mut self.input.get_text());
                 self.output.borrow_mut().push(input_echo);
 
@@ -537,7 +1363,10 @@ impl Console {
             '\x08' => self.input.backspace(),
             '\x7f' => self.input.delete(),
 
-            '\t' => warn!("Tab completion not implemented"), // TODO: tab completion
+            '\t' => self.complete(),
+
+            // ignore other control characters (e.g. from Ctrl+R)
+            c if c.is_control() => (),
 
             // TODO: we should probably restrict what characters are allowed
             c => self.input.insert(c),
@@ -550,80 +1379,437 @@ impl Console {
         self.input.curs
     }
 
+    // handles a character typed during a scrollback search
+    fn search_char(&mut self, c: char) {
+        let search = match self.search {
+            Some(ref mut s) => s,
+            None => return,
+        };
+
+        match c {
+            '`' => return,
+
+            // cancel the search, restoring the view
+            '\x1b' => {
+                self.output.borrow_mut().scroll_to(search.origin);
+                self.search = None;
+                return;
+            }
+
+            // finish the search, leaving the view at the match
+            '\r' => {
+                self.search = None;
+                return;
+            }
+
+            // find the next older match
+            '\t' => {
+                self.scrollback_search();
+                return;
+            }
+
+            '\x08' => {
+                // erasing the whole query cancels the search
+                if search.query.pop().is_none() {
+                    self.output.borrow_mut().scroll_to(search.origin);
+                    self.search = None;
+                    return;
+                }
+            }
+
+            // ignore other control characters (e.g. from Ctrl+F)
+            c if c.is_control() => return,
+
+            c => search.query.push(c),
+        }
+
+        let mut output = self.output.borrow_mut();
+        match output.find(&search.query, search.origin) {
+            Some(i) => output.scroll_to(i),
+            None => output.scroll_to(search.origin),
+        }
+    }
+
+    // handles a character typed during a history search, returning true if it should also be
+    // handled as normal input
+    fn hist_search_char(&mut self, c: char) -> bool {
+        let search = match self.hist_search {
+            Some(ref mut s) => s,
+            None => return true,
+        };
+
+        // a longer query can only match the current line or older ones
+        let start = search.found.as_ref().map_or(0, |(i, _)| *i);
+
+        let start = match c {
+            // run the matching line
+            '\r' => {
+                self.accept_hist_search();
+                return true;
+            }
+
+            '\x08' => {
+                search.query.pop();
+                0
+            }
+
+            // cancel the search, leaving the input as it was
+            '\x1b' => {
+                self.hist_search = None;
+                return false;
+            }
+
+            '`' => return false,
+            c if c.is_control() => return false,
+
+            c => {
+                search.query.push(c);
+                start
+            }
+        };
+
+        search.found = if search.query.is_empty() {
+            None
+        } else {
+            self.hist.find(&search.query, start)
+        };
+
+        false
+    }
+
+    // ends the history search, leaving the matching line in the input
+    fn accept_hist_search(&mut self) {
+        if let Some(search) = self.hist_search.take() {
+            if let Some((_, line)) = search.found {
+                self.input.set_text(&line);
+            }
+        }
+    }
+
+    /// Starts a reverse search through the command history, or finds the next older match if a
+    /// search is already in progress.
+    pub fn reverse_search(&mut self) {
+        if self.search.is_some() {
+            return;
+        }
+
+        match self.hist_search {
+            Some(ref mut search) => {
+                if search.query.is_empty() {
+                    return;
+                }
+
+                let start = search.found.as_ref().map_or(0, |(i, _)| i + 1);
+                if let Some(found) = self.hist.find(&search.query, start) {
+                    search.found = Some(found);
+                }
+            }
+
+            None => {
+                self.hist_search = Some(HistorySearch {
+                    query: String::new(),
+                    found: None,
+                })
+            }
+        }
+    }
+
+    /// Starts an incremental search through the console scrollback, or finds the next older match
+    /// if a search is already in progress.
+    pub fn scrollback_search(&mut self) {
+        if self.hist_search.is_some() {
+            return;
+        }
+
+        match self.search {
+            Some(ref search) => {
+                let mut output = self.output.borrow_mut();
+                let start = output.scroll() + 1;
+                if let Some(i) = output.find(&search.query, start) {
+                    output.scroll_to(i);
+                }
+            }
+
+            None => {
+                self.search = Some(Search {
+                    query: String::new(),
+                    origin: self.output.borrow().scroll(),
+                })
+            }
+        }
+    }
+
+    /// Returns the query and matching line of the history search in progress, if any.
+    pub fn history_search(&self) -> Option<(&str, Option<String>)> {
+        self.hist_search.as_ref().map(|s| {
+            let found = s
+                .found
+                .as_ref()
+                .map(|(_, line)| String::from_iter(line.iter()));
+            (s.query.as_str(), found)
+        })
+    }
+
+    /// Returns the query of the scrollback search in progress, if any.
+    pub fn search_query(&self) -> Option<&str> {
+        self.search.as_ref().map(|s| s.query.as_str())
+    }
+
+    pub fn page_up(&mut self) {
+        self.output.borrow_mut().scroll_up(PAGE_LINES);
+    }
+
+    pub fn page_down(&mut self) {
+        self.output.borrow_mut().scroll_down(PAGE_LINES);
+    }
+
+    /// Completes the word before the cursor.
+    ///
+    /// The first word on the line is completed from the names of commands, cvars and aliases.
+    /// Later words are completed using the argument completer registered for the command, if any.
+    ///
+    /// If there is exactly one candidate, the word is replaced with it. Otherwise the word is
+    /// extended to the longest prefix shared by all candidates and the candidates are printed to
+    /// the console output.
+    fn complete(&mut self) {
+        let line: String = self.input.text[..self.input.curs].iter().collect();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let new_word = line.is_empty() || line.ends_with(char::is_whitespace);
+
+        let (partial, arg_count) = if new_word {
+            ("", words.len())
+        } else {
+            (words[words.len() - 1], words.len() - 1)
+        };
+
+        let mut candidates = match arg_count {
+            0 => {
+                let mut names: Vec<String> = self.cmds.borrow().names().map(String::from).collect();
+                names.extend(self.cvars.borrow().names());
+                names.extend(self.aliases.borrow().keys().cloned());
+                names
+            }
+
+            n => self.cmds.borrow().complete_arg(words[0], n - 1),
+        };
+
+        let partial_lower = partial.to_lowercase();
+        candidates.retain(|c| c.to_lowercase().starts_with(&partial_lower));
+        candidates.sort();
+        candidates.dedup();
+
+        let replacement = match candidates.len() {
+            0 => return,
+            1 => format!("{} ", candidates[0]),
+            _ => {
+                let mut echo: Vec<char> = vec![']'];
+                echo.extend(self.input.text.iter());
+                self.output.borrow_mut().push(echo);
+                for candidate in candidates.iter() {
+                    self.output
+                        .borrow_mut()
+                        .push(format!("    {}", candidate).chars().collect());
+                }
+
+                common_prefix(&candidates)
+            }
+        };
+
+        // don't shorten the word if the candidates differ only in case
+        if replacement.chars().count() < partial.chars().count() {
+            return;
+        }
+
+        let start = self.input.curs - partial.chars().count();
+        let tail = self.input.text.split_off(self.input.curs);
+        self.input.text.truncate(start);
+        self.input.text.extend(replacement.chars());
+        self.input.curs = self.input.text.len();
+        self.input.text.extend(tail);
+    }
+
     pub fn cursor_right(&mut self) {
+        self.accept_hist_search();
         self.input.cursor_right()
     }
 
     pub fn cursor_left(&mut self) {
+        self.accept_hist_search();
         self.input.cursor_left()
     }
 
     pub fn history_up(&mut self) {
+        self.accept_hist_search();
         if let Some(line) = self.hist.line_up() {
             self.input.set_text(&line);
         }
     }
 
     pub fn history_down(&mut self) {
+        self.accept_hist_search();
         if let Some(line) = self.hist.line_down() {
             self.input.set_text(&line);
         }
     }
 
     /// Interprets the contents of the execution buffer.
+    ///
+    /// Commands are removed from the front of the buffer and executed one at a time, so text
+    /// inserted by a command (e.g. by `exec` or an alias) runs before the rest of the buffer. If a
+    /// command issues `wait`, the remainder of the buffer is left for the next call.
+    ///
+    /// Errors are printed to the console output and don't stop execution of later commands.
     pub fn execute(&self) {
-        let text = self.buffer.borrow().to_owned();
-        self.buffer.borrow_mut().clear();
+        // alias and exec depth are only tracked within a frame, so scripts may loop using `wait`
+        self.alias_tails.borrow_mut().clear();
+        self.exec_tails.borrow_mut().clear();
+
+        loop {
+            // forget aliases and scripts whose text has been completely executed
+            {
+                let remaining = self.buffer.borrow().len();
+                for tails in &[&self.alias_tails, &*self.exec_tails] {
+                    let mut tails = tails.borrow_mut();
+                    while tails.last().map_or(false, |t| remaining <= *t) {
+                        tails.pop();
+                    }
+                }
+            }
+
+            let result = match self.next_command() {
+                Ok(Some(args)) => {
+                    debug!("{:?}", args);
+                    self.execute_command(&args)
+                }
 
-        let (_remaining, commands) = parse::commands(text.as_str()).unwrap();
+                // nothing left to execute
+                Ok(None) => break,
 
-        for command in commands.iter() {
-            debug!("{:?}", command);
+                Err(e) => Err(e.into()),
+            };
+
+            if let Err(e) = result {
+                self.output.borrow_mut().println(e.to_string());
+            }
+
+            if self.wait.replace(false) {
+                break;
+            }
         }
+    }
 
-        for args in commands {
-            if let Some(arg_0) = args.get(0) {
-                let maybe_alias = self.aliases.borrow().get(*arg_0).map(|a| a.to_owned());
-                match maybe_alias {
-                    Some(a) => {
-                        self.stuff_text(a);
-                        self.execute();
-                    }
+    /// Removes the next command from the front of the execution buffer.
+    ///
+    /// If the command can't be parsed, the rest of the line containing it is discarded.
+    fn next_command(&self) -> Result<Option<Vec<String>>, ConsoleError> {
+        let mut buffer = self.buffer.borrow_mut();
+
+        let (consumed, result) = match parse::console::next_command(buffer.as_str()) {
+            Ok((rest, args)) => {
+                let args = args.map(|a| a.iter().map(|s| s.to_string()).collect());
+                (buffer.len() - rest.len(), Ok(args))
+            }
 
-                    None => {
-                        let tail_args: Vec<&str> =
-                            args.iter().map(|s| s.as_ref()).skip(1).collect();
-
-                        if self.cmds.borrow().contains(arg_0) {
-                            self.cmds.borrow_mut().exec(arg_0, &tail_args).unwrap();
-                        } else if self.cvars.borrow().contains(arg_0) {
-                            // TODO error handling on cvar set
-                            match args.get(1) {
-                                Some(arg_1) => self.cvars.borrow_mut().set(arg_0, arg_1).unwrap(),
-                                None => {
-                                    let msg = format!(
-                                        "\"{}\" is \"{}\"",
-                                        arg_0,
-                                        self.cvars.borrow().get(arg_0).unwrap()
-                                    );
-                                    self.output
-                                        .borrow_mut()
-                                        .push(msg.as_str().chars().collect());
-                                }
-                            }
-                        } else {
-                            // TODO: try sending to server first
-                            self.output.borrow_mut().push(
-                                format!("Unrecognized command \"{}\"", arg_0)
-                                    .as_str()
-                                    .chars()
-                                    .collect(),
-                            );
-                        }
+            Err(e) => {
+                // point at the offending argument rather than the whitespace before it
+                let pos = match e {
+                    nom::Err::Error((rest, _)) | nom::Err::Failure((rest, _)) => {
+                        buffer.len() - rest.trim_start_matches(&[' ', '\t'][..]).len()
                     }
+                    nom::Err::Incomplete(_) => 0,
+                };
+
+                let line_start = buffer[..pos].rfind('\n').map_or(0, |i| i + 1);
+                let line_end = buffer[pos..]
+                    .find('\n')
+                    .map_or(buffer.len(), |i| pos + i + 1);
+                let line = buffer[line_start..line_end].trim_end().to_owned();
+                let column = buffer[line_start..pos].chars().count() + 1;
+
+                (
+                    line_end,
+                    Err(ConsoleErrorKind::ParseFailed { line, column }.into()),
+                )
+            }
+        };
+
+        buffer.replace_range(..consumed, "");
+        result
+    }
+
+    fn execute_command(&self, args: &[String]) -> Result<(), Error> {
+        let arg_0 = match args.get(0) {
+            Some(a) => a.as_str(),
+            None => return Ok(()),
+        };
+
+        let maybe_alias = self.aliases.borrow().get(arg_0).map(|a| a.to_owned());
+        if let Some(script) = maybe_alias {
+            self.expand_alias(arg_0, &script)?;
+            return Ok(());
+        }
+
+        let tail_args: Vec<&str> = args.iter().map(|s| s.as_ref()).skip(1).collect();
+
+        // don't hold the registry borrowed while the command runs
+        let cmd = self.cmds.borrow().get(arg_0);
+        if let Some(cmd) = cmd {
+            cmd(&tail_args)?;
+        } else if self.cvars.borrow().contains(arg_0) {
+            match args.get(1) {
+                Some(arg_1) => self.cvars.borrow().set(arg_0, arg_1.as_str())?,
+                None => {
+                    let msg = format!("\"{}\" is \"{}\"", arg_0, self.cvars.borrow().get(arg_0)?);
+                    self.output.borrow_mut().println(msg);
                 }
             }
+        } else if self.forward_to_server.get() {
+            // the server may recognize it (e.g. "say", "kill", "god")
+            self.forwarded.borrow_mut().push(cmds::join_args(args));
+        } else {
+            self.output
+                .borrow_mut()
+                .println(format!("Unrecognized command \"{}\"", arg_0));
         }
+
+        Ok(())
+    }
+
+    // inserts the text of an alias at the front of the execution buffer
+    fn expand_alias(&self, name: &str, script: &str) -> Result<(), ConsoleError> {
+        let mut buffer = self.buffer.borrow_mut();
+        let mut tails = self.alias_tails.borrow_mut();
+
+        if tails.len() >= MAX_ALIAS_DEPTH {
+            // discard the rest of the outermost alias
+            let end = buffer.len() - tails[0];
+            buffer.replace_range(..end, "");
+            tails.clear();
+
+            Err(ConsoleErrorKind::AliasRecursion {
+                name: name.to_owned(),
+            })?;
+        }
+
+        tails.push(buffer.len());
+        buffer.insert_str(0, &format!("{}\n", script));
+
+        Ok(())
+    }
+
+    /// Sets whether unrecognized commands should be forwarded to the server.
+    ///
+    /// This should be enabled while connected to a server.
+    pub fn set_forward_to_server(&self, forward: bool) {
+        self.forward_to_server.set(forward);
+        if !forward {
+            self.forwarded.borrow_mut().clear();
+        }
+    }
+
+    /// Returns the commands queued to be sent to the server since the last call.
+    pub fn take_forwarded(&self) -> Vec<String> {
+        self.forwarded.replace(Vec::new())
     }
 
     pub fn get_string(&self) -> String {
@@ -647,9 +1833,725 @@ impl Console {
 
         // in case the last line doesn't end with a newline
         self.buffer.borrow_mut().push_str("\n");
+
+        // appended text follows the text of any aliases or scripts being executed
+        for tails in &[&self.alias_tails, &*self.exec_tails] {
+            for tail in tails.borrow_mut().iter_mut() {
+                *tail += text.as_ref().len() + 1;
+            }
+        }
     }
 
     pub fn output(&self) -> Ref<ConsoleOutput> {
         self.output.borrow()
     }
+
+    pub fn output_mut(&self) -> RefMut<ConsoleOutput> {
+        self.output.borrow_mut()
+    }
+
+    /// Prints text to the console output.
+    ///
+    /// See `ConsoleOutput::print`.
+    pub fn print<S>(&self, text: S)
+    where
+        S: AsRef<str>,
+    {
+        self.output.borrow_mut().print(text);
+    }
+
+    /// Reads the command history from a file written by `write_history`.
+    pub fn read_history<R>(&mut self, reader: R) -> io::Result<()>
+    where
+        R: BufRead,
+    {
+        self.hist.read(reader)
+    }
+
+    /// Writes the command history, one line per command.
+    pub fn write_history<W>(&self, writer: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.hist.write(writer)
+    }
+
+    /// Writes all aliases as a console script that restores them when executed.
+    pub fn write_aliases<W>(&self, writer: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let aliases = self.aliases.borrow();
+        let mut names: Vec<&String> = aliases.keys().collect();
+        names.sort();
+
+        for name in names {
+            // `alias` rejects these, since they couldn't be quoted here
+            if aliases[name].contains('"') {
+                continue;
+            }
+
+            writeln!(writer, "alias {} \"{}\"", name, aliases[name])?;
+        }
+
+        Ok(())
+    }
+}
+
+// returns the longest prefix shared by all of the given strings
+fn common_prefix(strings: &[String]) -> String {
+    let first = match strings.first() {
+        Some(f) => f,
+        None => return String::new(),
+    };
+
+    let mut len = first.len();
+    for s in strings[1..].iter() {
+        len = first
+            .char_indices()
+            .zip(s.chars())
+            .take_while(|((_, a), b)| a == b)
+            .map(|((i, a), _)| i + a.len_utf8())
+            .last()
+            .unwrap_or(0)
+            .min(len);
+    }
+
+    first[..len].to_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_console() -> Console {
+        let cmds = Rc::new(RefCell::new(CmdRegistry::new()));
+        let cvars = Rc::new(RefCell::new(CvarRegistry::new()));
+
+        cmds.borrow_mut().insert("playdemo", Box::new(|_| Ok(()))).unwrap();
+        cmds.borrow_mut().insert_completer(
+            "playdemo",
+            Box::new(|_| vec!["demo1.dem".into(), "demo2.dem".into()]),
+        );
+        cmds.borrow_mut().insert("impulse", Box::new(|_| Ok(()))).unwrap();
+        cmds.borrow_mut().insert("fail", Box::new(|_| bail!("command failed"))).unwrap();
+        cvars.borrow().register("cl_bob", "0.02").unwrap();
+        cvars.borrow().register("cl_bobcycle", "0.6").unwrap();
+
+        Console::new(cmds, cvars, Rc::new(Vfs::new()))
+    }
+
+    fn output_lines(console: &Console) -> Vec<String> {
+        console
+            .output()
+            .lines()
+            .map(|l| l.iter().collect())
+            .collect()
+    }
+
+    fn type_str(console: &mut Console, s: &str) {
+        for c in s.chars() {
+            console.send_char(c).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_write_archived() {
+        let cvars = CvarRegistry::new();
+        cvars.register_archive("sensitivity", "3").unwrap();
+        cvars.register_archive("_cl_name", "player").unwrap();
+        cvars.register("cl_bob", "0.02").unwrap();
+        cvars.set("sensitivity", "5").unwrap();
+
+        let mut script = Vec::new();
+        cvars.write_archived(&mut script).unwrap();
+        assert_eq!(
+            String::from_utf8(script).unwrap(),
+            "_cl_name \"player\"\nsensitivity \"5\"\n"
+        );
+
+        // this would be saved as `_cl_name "a"; rcon_password "b"`
+        cvars.set("_cl_name", "a\"; rcon_password \"b").unwrap();
+        let mut script = Vec::new();
+        cvars.write_archived(&mut script).unwrap();
+        assert_eq!(String::from_utf8(script).unwrap(), "sensitivity \"5\"\n");
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        let strings = vec![
+            "cl_bob".to_owned(),
+            "cl_bobcycle".to_owned(),
+            "cl_bobup".to_owned(),
+        ];
+        assert_eq!(common_prefix(&strings), "cl_bob");
+
+        let strings = vec!["abc".to_owned(), "xyz".to_owned()];
+        assert_eq!(common_prefix(&strings), "");
+    }
+
+    #[test]
+    fn test_complete_unique_command() {
+        let mut console = test_console();
+        type_str(&mut console, "imp\t");
+        assert_eq!(console.get_string(), "impulse ");
+    }
+
+    #[test]
+    fn test_complete_ambiguous_cvar() {
+        let mut console = test_console();
+        type_str(&mut console, "cl_\t");
+        assert_eq!(console.get_string(), "cl_bob");
+
+        // candidates are listed in the output
+        let lines = output_lines(&console);
+        assert!(lines.contains(&"    cl_bobcycle".to_owned()));
+    }
+
+    #[test]
+    fn test_complete_argument() {
+        let mut console = test_console();
+        type_str(&mut console, "playdemo DEMO2\t");
+        assert_eq!(console.get_string(), "playdemo demo2.dem ");
+
+        let mut console = test_console();
+        type_str(&mut console, "playdemo \t");
+        assert_eq!(console.get_string(), "playdemo demo");
+    }
+
+    #[test]
+    fn test_wait_defers_remaining_commands() {
+        let console = test_console();
+        console.stuff_text("cl_bob 1; wait; cl_bob 2");
+        console.execute();
+        assert_eq!(console.cvars.borrow().get("cl_bob").unwrap(), "1");
+        console.execute();
+        assert_eq!(console.cvars.borrow().get("cl_bob").unwrap(), "2");
+    }
+
+    #[test]
+    fn test_alias_runs_before_remaining_commands() {
+        let console = test_console();
+        console.stuff_text("alias bob \"cl_bob 1; cl_bobcycle 2\"\nbob; cl_bob 3");
+        console.execute();
+        assert_eq!(console.cvars.borrow().get("cl_bob").unwrap(), "3");
+        assert_eq!(console.cvars.borrow().get("cl_bobcycle").unwrap(), "2");
+    }
+
+    #[test]
+    fn test_toggle_inc_dec() {
+        let console = test_console();
+        console.stuff_text("toggle cl_bob");
+        console.execute();
+        assert_eq!(console.cvars.borrow().get_value("cl_bob").unwrap(), 0.0);
+
+        console.stuff_text("inc cl_bob 2; dec cl_bob");
+        console.execute();
+        assert_eq!(console.cvars.borrow().get_value("cl_bob").unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_forward_to_server() {
+        let console = test_console();
+        console.stuff_text("say hello");
+        console.execute();
+        assert!(console.take_forwarded().is_empty());
+        assert!(output_lines(&console).contains(&"Unrecognized command \"say\"".to_owned()));
+
+        console.set_forward_to_server(true);
+        console.stuff_text("say \"hello world\"; cmd kill");
+        console.execute();
+        assert_eq!(
+            console.take_forwarded(),
+            vec!["say \"hello world\"".to_owned(), "kill".to_owned()]
+        );
+    }
+
+    #[test]
+    fn test_parse_error_skips_line() {
+        let console = test_console();
+        console.stuff_text("cl_bob \"1\ncl_bobcycle 2");
+        console.execute();
+        assert_eq!(console.cvars.borrow().get("cl_bobcycle").unwrap(), "2");
+        assert!(output_lines(&console).contains(&"Parse error at column 8: cl_bob \"1".to_owned()));
+    }
+
+    #[test]
+    fn test_command_error_continues() {
+        let console = test_console();
+        console.stuff_text("fail; cl_bob 1");
+        console.execute();
+        assert_eq!(console.cvars.borrow().get("cl_bob").unwrap(), "1");
+        assert!(output_lines(&console).contains(&"command failed".to_owned()));
+    }
+
+    #[test]
+    fn test_command_modifies_registry() {
+        let console = test_console();
+        let cmds = console.cmds.clone();
+        console
+            .cmds
+            .borrow_mut()
+            .insert(
+                "add_cmd",
+                Box::new(move |args| {
+                    cmds.borrow_mut()
+                        .insert_or_replace(args[0], Box::new(|_| bail!("added")));
+                    Ok(())
+                }),
+            )
+            .unwrap();
+
+        console.stuff_text("add_cmd added; added");
+        console.execute();
+        assert!(output_lines(&console).contains(&"added".to_owned()));
+    }
+
+    #[test]
+    fn test_alias_recursion() {
+        let console = test_console();
+        console.stuff_text("alias loop \"cl_bob 1; loop\"\nloop; cl_bobcycle 2");
+        console.execute();
+        assert_eq!(console.cvars.borrow().get("cl_bobcycle").unwrap(), "2");
+        assert!(output_lines(&console).contains(&"Alias recursion depth exceeded: loop".to_owned()));
+
+        // sequential aliases don't count towards the depth
+        let script: String = (0..2 * MAX_ALIAS_DEPTH).map(|_| "bob\n").collect();
+        console.stuff_text(format!("alias bob \"inc cl_bobcycle\"\n{}", script));
+        console.execute();
+        assert_eq!(
+            console.cvars.borrow().get_value("cl_bobcycle").unwrap(),
+            2.0 + 2.0 * MAX_ALIAS_DEPTH as f32
+        );
+    }
+
+    #[test]
+    fn test_exec_recursion() {
+        let dir = crate::common::util::TempDir::new();
+        dir.write("self.cfg", "inc cl_bob\nexec self.cfg\n");
+        dir.write("a.cfg", "exec b.cfg\n");
+        dir.write("b.cfg", "inc cl_bobcycle\nexec a.cfg\n");
+        dir.write("nested.cfg", "exec a.cfg\ncl_bob 5\n");
+
+        let mut vfs = Vfs::new();
+        vfs.add_directory(dir.path()).unwrap();
+
+        let cmds = Rc::new(RefCell::new(CmdRegistry::new()));
+        let cvars = Rc::new(RefCell::new(CvarRegistry::new()));
+        cvars.borrow().register("cl_bob", "0").unwrap();
+        cvars.borrow().register("cl_bobcycle", "0").unwrap();
+        let console = Console::new(cmds, cvars.clone(), Rc::new(vfs));
+
+        // commands following the outermost exec still run
+        console.stuff_text("exec self.cfg; cl_bobcycle 2");
+        console.execute();
+        assert!(cvars.borrow().get_value("cl_bob").unwrap() > 1.0);
+        assert_eq!(cvars.borrow().get("cl_bobcycle").unwrap(), "2");
+        assert!(output_lines(&console).contains(&"exec: script nesting too deep".to_owned()));
+
+        // the rest of the outermost script is discarded
+        console.stuff_text("exec nested.cfg");
+        console.execute();
+        assert_ne!(cvars.borrow().get("cl_bob").unwrap(), "5");
+
+        // sequential scripts don't count towards the depth
+        cvars.borrow().set("cl_bob", "0").unwrap();
+        let script: String = (0..64).map(|_| "exec self.cfg\n").collect();
+        dir.write("self.cfg", "inc cl_bob\n");
+        console.stuff_text(script);
+        console.execute();
+        assert_eq!(cvars.borrow().get_value("cl_bob").unwrap(), 64.0);
+    }
+
+    #[test]
+    fn test_write_aliases() {
+        let console = test_console();
+        console.stuff_text("alias bob \"cl_bob 1; cl_bobcycle 2\"");
+        console.execute();
+
+        // the parser never produces quotes inside arguments, but other callers might
+        let cmds = console.cmds.clone();
+        assert!(cmds
+            .borrow()
+            .exec("alias", &["quote", "say \"hi\""])
+            .is_err());
+
+        let mut script = Vec::new();
+        console.write_aliases(&mut script).unwrap();
+        assert_eq!(
+            String::from_utf8(script).unwrap(),
+            "alias bob \"cl_bob 1; cl_bobcycle 2\"\n"
+        );
+    }
+
+    #[test]
+    fn test_cvar_validation() {
+        let cvars = CvarRegistry::new();
+        cvars
+            .define("r_lightmap", "0")
+            .kind(CvarKind::Bool)
+            .register()
+            .unwrap();
+        cvars
+            .define("r_msaa_samples", "4")
+            .kind(CvarKind::Enum(&["2", "4"]))
+            .register()
+            .unwrap();
+        cvars
+            .define("fov", "90")
+            .kind(CvarKind::Float {
+                min: Some(10.0),
+                max: Some(170.0),
+            })
+            .register()
+            .unwrap();
+
+        assert!(cvars.set("r_lightmap", "1").is_ok());
+        assert!(cvars.set("r_lightmap", "yes").is_err());
+        assert!(cvars.set("r_msaa_samples", "2").is_ok());
+        assert!(cvars.set("r_msaa_samples", "3").is_err());
+        assert!(cvars.set("fov", "110.5").is_ok());
+        assert!(cvars.set("fov", "180").is_err());
+
+        // rejected values don't change the cvar
+        assert_eq!(cvars.get("fov").unwrap(), "110.5");
+        assert_eq!(cvars.get_value("fov").unwrap(), 110.5);
+
+        // invalid defaults are rejected at registration
+        assert!(cvars
+            .define("cl_shownet", "3")
+            .kind(CvarKind::Int {
+                min: Some(0),
+                max: Some(2),
+            })
+            .register()
+            .is_err());
+    }
+
+    #[test]
+    fn test_cvar_get_value_not_number() {
+        let cvars = CvarRegistry::new();
+        cvars.register("_cl_name", "player").unwrap();
+        cvars.register("cl_bob", "0.02").unwrap();
+        assert!(cvars.get_value("_cl_name").is_err());
+
+        // a failed lookup doesn't reset the cvar
+        cvars.set("cl_bob", "bob").unwrap();
+        assert!(cvars.get_value("cl_bob").is_err());
+        assert_eq!(cvars.get("cl_bob").unwrap(), "bob");
+
+        cvars.set("cl_bob", "inf").unwrap();
+        assert!(cvars.get_value("cl_bob").is_err());
+
+        cvars.set("cl_bob", "0.5").unwrap();
+        assert_eq!(cvars.get_value("cl_bob").unwrap(), 0.5);
+    }
+
+    #[test]
+    fn test_cvar_kind_display() {
+        let kind = CvarKind::Int {
+            min: Some(0),
+            max: Some(2),
+        };
+        assert_eq!(kind.to_string(), "an integer between 0 and 2");
+
+        let kind = CvarKind::Float {
+            min: Some(0.0),
+            max: None,
+        };
+        assert_eq!(kind.to_string(), "a number of at least 0");
+
+        assert_eq!(CvarKind::Enum(&["2", "4"]).to_string(), "one of: 2, 4");
+    }
+
+    #[test]
+    fn test_cvar_on_change() {
+        let cvars = CvarRegistry::new();
+        cvars.register("sensitivity", "3").unwrap();
+
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let cb_changes = changes.clone();
+        cvars
+            .on_change(
+                "sensitivity",
+                Box::new(move |value| cb_changes.borrow_mut().push(value.to_owned())),
+            )
+            .unwrap();
+
+        cvars.set("sensitivity", "5").unwrap();
+        cvars.set("sensitivity", "5").unwrap();
+        cvars.reset("sensitivity").unwrap();
+        assert_eq!(*changes.borrow(), vec!["5".to_owned(), "3".to_owned()]);
+
+        assert!(cvars.on_change("no_such_cvar", Box::new(|_| ())).is_err());
+    }
+
+    #[test]
+    fn test_cvar_on_notify() {
+        let cvars = CvarRegistry::new();
+        cvars.register("cl_bob", "0.02").unwrap();
+        cvars.register_notify("sv_gravity", "800").unwrap();
+
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let cb_changes = changes.clone();
+        let id = cvars.on_notify(Box::new(move |name, value| {
+            cb_changes.borrow_mut().push(format!("{} {}", name, value))
+        }));
+
+        cvars.set("cl_bob", "1").unwrap();
+        cvars.set("sv_gravity", "100").unwrap();
+        cvars.set("sv_gravity", "100").unwrap();
+        assert_eq!(*changes.borrow(), vec!["sv_gravity 100".to_owned()]);
+
+        cvars.remove_callback(id);
+        cvars.set("sv_gravity", "800").unwrap();
+        assert_eq!(changes.borrow().len(), 1);
+    }
+
+    #[test]
+    fn test_output_capacity() {
+        let mut output = ConsoleOutput::with_capacity(3);
+        for i in 0..5 {
+            output.println(i.to_string());
+        }
+
+        let lines: Vec<String> = output.lines().map(|l| l.iter().collect()).collect();
+        assert_eq!(lines, vec!["4", "3", "2"]);
+
+        output.set_capacity(1);
+        assert_eq!(output.len(), 1);
+    }
+
+    #[test]
+    fn test_output_scroll() {
+        let mut output = ConsoleOutput::new();
+        for i in 0..10 {
+            output.println(i.to_string());
+        }
+
+        output.scroll_up(3);
+        assert_eq!(output.scroll(), 3);
+
+        // new output doesn't move the view
+        output.println("10");
+        assert_eq!(output.scroll(), 4);
+
+        output.scroll_up(100);
+        assert_eq!(output.scroll(), 10);
+        output.scroll_down(100);
+        assert_eq!(output.scroll(), 0);
+    }
+
+    #[test]
+    fn test_output_find() {
+        let mut output = ConsoleOutput::new();
+        for line in &[
+            "Couldn't exec foo.cfg",
+            "cl_bob is 0.02",
+            "couldn't exec bar.cfg",
+        ] {
+            output.println(line);
+        }
+
+        assert_eq!(output.find("COULDN'T", 0), Some(0));
+        assert_eq!(output.find("couldn't", 1), Some(2));
+        assert_eq!(output.find("sv_gravity", 0), None);
+    }
+
+    #[test]
+    fn test_output_write_to() {
+        let mut output = ConsoleOutput::new();
+        output.println("first");
+        output.println("second");
+
+        let mut dump = Vec::new();
+        output.write_to(&mut dump).unwrap();
+        assert_eq!(String::from_utf8(dump).unwrap(), "first\nsecond\n");
+    }
+
+    #[test]
+    fn test_output_print() {
+        let mut output = ConsoleOutput::new();
+        output.print("Player entered ");
+        output.print("the game\nsecond line\n");
+        output.print("\n");
+        output.print("third");
+
+        let lines: Vec<String> = output.lines().map(|l| l.iter().collect()).collect();
+        assert_eq!(
+            lines,
+            vec!["third", "", "second line", "Player entered the game"]
+        );
+
+        // println always starts a new line
+        output.println("fourth");
+        output.print("fifth");
+        assert_eq!(output.len(), 6);
+    }
+
+    #[test]
+    fn test_history_dedup() {
+        let mut hist = History::new();
+        for line in &["map e1m1", "map e1m1", "", "god", "map e1m1"] {
+            hist.add_line(line.chars().collect());
+        }
+
+        let mut script = Vec::new();
+        hist.write(&mut script).unwrap();
+        assert_eq!(
+            String::from_utf8(script.clone()).unwrap(),
+            "map e1m1\ngod\nmap e1m1\n"
+        );
+
+        let mut read = History::new();
+        read.read(script.as_slice()).unwrap();
+        assert_eq!(read.line_up(), Some("map e1m1".chars().collect()));
+        assert_eq!(read.line_up(), Some("god".chars().collect()));
+    }
+
+    #[test]
+    fn test_history_passwords() {
+        let mut hist = History::new();
+        for line in &[
+            "rcon_password hunter2",
+            "rcon_password",
+            "rcon rcon_password \"hunter 2\"",
+            "map e1m1; rcon_password hunter2",
+            "god",
+        ] {
+            hist.add_line(line.chars().collect());
+        }
+
+        let mut script = Vec::new();
+        hist.write(&mut script).unwrap();
+        assert_eq!(String::from_utf8(script).unwrap(), "rcon_password\ngod\n");
+    }
+
+    #[test]
+    fn test_history_empty() {
+        let mut hist = History::new();
+        assert_eq!(hist.line_up(), None);
+        assert_eq!(hist.line_down(), Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_reverse_search() {
+        let mut console = test_console();
+        for line in &["map e1m1", "cl_bob 1", "map e2m1"] {
+            type_str(&mut console, line);
+            console.send_char('\r').unwrap();
+        }
+
+        console.reverse_search();
+        type_str(&mut console, "map");
+        assert_eq!(
+            console.history_search(),
+            Some(("map", Some("map e2m1".to_owned())))
+        );
+
+        // find the next older match
+        console.reverse_search();
+        assert_eq!(
+            console.history_search(),
+            Some(("map", Some("map e1m1".to_owned())))
+        );
+
+        // accepting the match leaves it in the input line
+        console.cursor_left();
+        assert_eq!(console.history_search(), None);
+        assert_eq!(console.get_string(), "map e1m1");
+
+        // cancelling the search leaves the input unchanged
+        console.reverse_search();
+        type_str(&mut console, "cl_");
+        assert_eq!(
+            console.history_search(),
+            Some(("cl_", Some("cl_bob 1".to_owned())))
+        );
+        console.send_char('\x1b').unwrap();
+        assert_eq!(console.history_search(), None);
+        assert_eq!(console.get_string(), "map e1m1");
+    }
+
+    #[test]
+    fn test_output_capture() {
+        let mut output = ConsoleOutput::new();
+        output.println("before");
+        output.begin_capture();
+        output.println("\"sv_gravity\" is \"800\"");
+        output.print("partial ");
+        output.print("line\n");
+        assert_eq!(
+            output.end_capture(),
+            "\"sv_gravity\" is \"800\"\npartial line\n"
+        );
+
+        output.println("after");
+        assert_eq!(output.end_capture(), "");
+    }
+
+    #[test]
+    fn test_con_scrollback() {
+        let console = test_console();
+        console.stuff_text("con_scrollback 2; echo a; echo b; echo c");
+        console.execute();
+        assert_eq!(output_lines(&console), vec!["c", "b"]);
+    }
+
+    #[test]
+    fn test_search() {
+        let mut console = test_console();
+        console.stuff_text("echo apple; echo banana; echo cherry; echo apricot");
+        console.execute();
+
+        console.scrollback_search();
+        type_str(&mut console, "\x06ap");
+        assert_eq!(console.search_query(), Some("ap"));
+        assert_eq!(console.output().scroll(), 0);
+
+        // jump to the next older match
+        console.send_char('\t').unwrap();
+        assert_eq!(console.output().scroll(), 3);
+        console.scrollback_search();
+        assert_eq!(console.output().scroll(), 3);
+
+        console.send_char('\r').unwrap();
+        assert_eq!(console.search_query(), None);
+        assert_eq!(console.output().scroll(), 3);
+
+        // erasing the query cancels the search and restores the view
+        console.page_down();
+        console.scrollback_search();
+        type_str(&mut console, "b");
+        assert_eq!(console.output().scroll(), 2);
+        console.send_char('\x08').unwrap();
+        console.send_char('\x08').unwrap();
+        assert_eq!(console.search_query(), None);
+        assert_eq!(console.output().scroll(), 0);
+
+        // as does escape
+        console.scrollback_search();
+        type_str(&mut console, "cherry");
+        assert_eq!(console.output().scroll(), 1);
+        console.send_char('\x1b').unwrap();
+        assert_eq!(console.search_query(), None);
+        assert_eq!(console.output().scroll(), 0);
+
+        // a slash starts a command as usual
+        type_str(&mut console, "/echo");
+        assert_eq!(console.search_query(), None);
+        assert_eq!(console.get_string(), "/echo");
+    }
+
+    #[test]
+    fn test_resetall_and_help() {
+        let console = test_console();
+        console.stuff_text("cl_bob 1; cl_bobcycle 2; resetall; help cl_bob");
+        console.execute();
+        assert_eq!(console.cvars.borrow().get("cl_bob").unwrap(), "0.02");
+        assert_eq!(console.cvars.borrow().get("cl_bobcycle").unwrap(), "0.6");
+
+        let lines = output_lines(&console);
+        assert!(lines.contains(&"\"cl_bob\" is \"0.02\" (default \"0.02\")".to_owned()));
+    }
 }
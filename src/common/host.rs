@@ -91,7 +91,6 @@ where
             Event::Suspended | Event::Resumed => unimplemented!(),
             Event::LoopDestroyed => {
                 // TODO:
-                // - host_writeconfig
                 // - others...
             }
 
@@ -28,6 +28,7 @@ pub mod mdl;
 pub mod model;
 pub mod net;
 pub mod pak;
+pub mod pk3;
 pub mod parse;
 pub mod sprite;
 pub mod util;
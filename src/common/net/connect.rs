@@ -19,15 +19,12 @@
 // SOFTWARE.
 
 use std::{
-    io::{BufReader, Cursor, ErrorKind},
+    io::{BufRead, BufReader, Cursor, ErrorKind},
     mem::size_of,
     net::{SocketAddr, ToSocketAddrs, UdpSocket},
 };
 
-use crate::common::{
-    net::{NetError, QSocket, MAX_MESSAGE},
-    util,
-};
+use crate::common::net::{NetError, QSocket, MAX_MESSAGE};
 
 use byteorder::{LittleEndian, NetworkEndian, ReadBytesExt, WriteBytesExt};
 use chrono::Duration;
@@ -37,6 +34,20 @@ pub const CONNECT_PROTOCOL_VERSION: u8 = 3;
 const CONNECT_CONTROL: i32 = 1 << 31;
 const CONNECT_LENGTH_MASK: i32 = 0x0000FFFF;
 
+// reads a zero-terminated string, failing on malformed packets rather than panicking
+fn read_cstring<R>(reader: &mut R) -> Result<String, NetError>
+where
+    R: BufRead,
+{
+    let mut bytes = Vec::new();
+    reader.read_until(0, &mut bytes)?;
+    if bytes.pop() != Some(0) {
+        return Err(NetError::InvalidData(String::from("unterminated string")));
+    }
+
+    String::from_utf8(bytes).map_err(|e| NetError::InvalidData(format!("string: {}", e)))
+}
+
 pub trait ConnectPacket {
     /// Returns the numeric value of this packet's code.
     fn code(&self) -> u8;
@@ -86,6 +97,7 @@ pub enum RequestCode {
     ServerInfo = 2,
     PlayerInfo = 3,
     RuleInfo = 4,
+    Rcon = 5,
 }
 
 #[derive(Debug)]
@@ -196,6 +208,44 @@ impl ConnectPacket for RequestRuleInfo {
     }
 }
 
+/// A request to execute a command on the server's console.
+///
+/// This isn't part of the original protocol.
+#[derive(Debug)]
+pub struct RequestRcon {
+    pub password: String,
+    pub command: String,
+}
+
+impl ConnectPacket for RequestRcon {
+    fn code(&self) -> u8 {
+        RequestCode::Rcon as u8
+    }
+
+    fn content_len(&self) -> usize {
+        let mut len = 0;
+
+        // password and terminating zero byte
+        len += self.password.len() + size_of::<u8>();
+
+        // command and terminating zero byte
+        len += self.command.len() + size_of::<u8>();
+
+        len
+    }
+
+    fn write_content<W>(&self, writer: &mut W) -> Result<(), NetError>
+    where
+        W: WriteBytesExt,
+    {
+        writer.write(self.password.as_bytes())?;
+        writer.write_u8(0)?;
+        writer.write(self.command.as_bytes())?;
+        writer.write_u8(0)?;
+        Ok(())
+    }
+}
+
 /// A request from a client to retrieve information from or connect to the server.
 #[derive(Debug)]
 pub enum Request {
@@ -203,6 +253,7 @@ pub enum Request {
     ServerInfo(RequestServerInfo),
     PlayerInfo(RequestPlayerInfo),
     RuleInfo(RequestRuleInfo),
+    Rcon(RequestRcon),
 }
 
 impl Request {
@@ -237,6 +288,16 @@ impl Request {
             prev_cvar: prev_cvar.as_ref().to_string(),
         })
     }
+
+    pub fn rcon<S>(password: S, command: S) -> Request
+    where
+        S: AsRef<str>,
+    {
+        Request::Rcon(RequestRcon {
+            password: password.as_ref().to_owned(),
+            command: command.as_ref().to_owned(),
+        })
+    }
 }
 
 impl ConnectPacket for Request {
@@ -247,6 +308,7 @@ impl ConnectPacket for Request {
             ServerInfo(ref s) => s.code(),
             PlayerInfo(ref p) => p.code(),
             RuleInfo(ref r) => r.code(),
+            Rcon(ref r) => r.code(),
         }
     }
 
@@ -257,6 +319,7 @@ impl ConnectPacket for Request {
             ServerInfo(ref s) => s.content_len(),
             PlayerInfo(ref p) => p.content_len(),
             RuleInfo(ref r) => r.content_len(),
+            Rcon(ref r) => r.content_len(),
         }
     }
 
@@ -270,6 +333,7 @@ impl ConnectPacket for Request {
             ServerInfo(ref s) => s.write_content(writer),
             PlayerInfo(ref p) => p.write_content(writer),
             RuleInfo(ref r) => r.write_content(writer),
+            Rcon(ref r) => r.write_content(writer),
         }
     }
 }
@@ -281,6 +345,7 @@ pub enum ResponseCode {
     ServerInfo = 0x83,
     PlayerInfo = 0x84,
     RuleInfo = 0x85,
+    Rcon = 0x86,
 }
 
 #[derive(Debug)]
@@ -478,6 +543,37 @@ impl ConnectPacket for ResponseRuleInfo {
     }
 }
 
+/// The output of a command executed with `RequestRcon`.
+#[derive(Debug)]
+pub struct ResponseRcon {
+    pub output: String,
+}
+
+impl ResponseRcon {
+    /// The maximum length in bytes of the output.
+    pub const MAX_OUTPUT: usize = MAX_MESSAGE - 2 * size_of::<u8>() - size_of::<i32>();
+}
+
+impl ConnectPacket for ResponseRcon {
+    fn code(&self) -> u8 {
+        ResponseCode::Rcon as u8
+    }
+
+    fn content_len(&self) -> usize {
+        // output and terminating zero byte
+        self.output.len() + size_of::<u8>()
+    }
+
+    fn write_content<W>(&self, writer: &mut W) -> Result<(), NetError>
+    where
+        W: WriteBytesExt,
+    {
+        writer.write(self.output.as_bytes())?;
+        writer.write_u8(0)?;
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub enum Response {
     Accept(ResponseAccept),
@@ -485,6 +581,7 @@ pub enum Response {
     ServerInfo(ResponseServerInfo),
     PlayerInfo(ResponsePlayerInfo),
     RuleInfo(ResponseRuleInfo),
+    Rcon(ResponseRcon),
 }
 
 impl ConnectPacket for Response {
@@ -496,6 +593,7 @@ impl ConnectPacket for Response {
             ServerInfo(ref s) => s.code(),
             PlayerInfo(ref p) => p.code(),
             RuleInfo(ref r) => r.code(),
+            Rcon(ref r) => r.code(),
         }
     }
 
@@ -507,6 +605,7 @@ impl ConnectPacket for Response {
             ServerInfo(ref s) => s.content_len(),
             PlayerInfo(ref p) => p.content_len(),
             RuleInfo(ref r) => r.content_len(),
+            Rcon(ref r) => r.content_len(),
         }
     }
 
@@ -521,6 +620,7 @@ impl ConnectPacket for Response {
             ServerInfo(ref s) => s.write_content(writer),
             PlayerInfo(ref p) => p.write_content(writer),
             RuleInfo(ref r) => r.write_content(writer),
+            Rcon(ref r) => r.write_content(writer),
         }
     }
 }
@@ -541,6 +641,25 @@ impl ConnectListener {
         Ok(ConnectListener { socket })
     }
 
+    /// Returns the address this listener is bound to.
+    pub fn local_addr(&self) -> Result<SocketAddr, NetError> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Receives a request if one is waiting, without blocking.
+    ///
+    /// Returns `None` if no request is waiting.
+    pub fn try_recv_request(&self) -> Result<Option<(Request, SocketAddr)>, NetError> {
+        self.socket.set_nonblocking(true)?;
+        let result = self.recv_request();
+        self.socket.set_nonblocking(false)?;
+
+        match result {
+            Err(NetError::Io(ref e)) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+            r => r.map(Some),
+        }
+    }
+
     /// Receives a request and returns it along with its remote address.
     pub fn recv_request(&self) -> Result<(Request, SocketAddr), NetError> {
         // Original engine receives connection requests in `net_message`,
@@ -587,7 +706,7 @@ impl ConnectListener {
 
         let request = match request_code {
             RequestCode::Connect => {
-                let game_name = util::read_cstring(&mut reader).unwrap();
+                let game_name = read_cstring(&mut reader)?;
                 let proto_ver = reader.read_u8()?;
                 Request::Connect(RequestConnect {
                     game_name,
@@ -596,7 +715,7 @@ impl ConnectListener {
             }
 
             RequestCode::ServerInfo => {
-                let game_name = util::read_cstring(&mut reader).unwrap();
+                let game_name = read_cstring(&mut reader)?;
                 Request::ServerInfo(RequestServerInfo { game_name })
             }
 
@@ -606,9 +725,15 @@ impl ConnectListener {
             }
 
             RequestCode::RuleInfo => {
-                let prev_cvar = util::read_cstring(&mut reader).unwrap();
+                let prev_cvar = read_cstring(&mut reader)?;
                 Request::RuleInfo(RequestRuleInfo { prev_cvar })
             }
+
+            RequestCode::Rcon => {
+                let password = read_cstring(&mut reader)?;
+                let command = read_cstring(&mut reader)?;
+                Request::Rcon(RequestRcon { password, command })
+            }
         };
 
         Ok((request, remote))
@@ -710,14 +835,14 @@ impl ConnectSocket {
             }
 
             ResponseCode::Reject => {
-                let message = util::read_cstring(&mut reader).unwrap();
+                let message = read_cstring(&mut reader)?;
                 Response::Reject(ResponseReject { message })
             }
 
             ResponseCode::ServerInfo => {
-                let address = util::read_cstring(&mut reader).unwrap();
-                let hostname = util::read_cstring(&mut reader).unwrap();
-                let levelname = util::read_cstring(&mut reader).unwrap();
+                let address = read_cstring(&mut reader)?;
+                let hostname = read_cstring(&mut reader)?;
+                let levelname = read_cstring(&mut reader)?;
                 let client_count = reader.read_u8()?;
                 let client_max = reader.read_u8()?;
                 let protocol_version = reader.read_u8()?;
@@ -734,6 +859,11 @@ impl ConnectSocket {
 
             ResponseCode::PlayerInfo => unimplemented!(),
             ResponseCode::RuleInfo => unimplemented!(),
+
+            ResponseCode::Rcon => {
+                let output = read_cstring(&mut reader)?;
+                Response::Rcon(ResponseRcon { output })
+            }
         };
 
         Ok(Some((response, remote)))
@@ -836,6 +966,87 @@ mod test {
         assert_eq!(packet_len, packet.len());
     }
 
+    #[test]
+    fn test_request_rcon_packet_len() {
+        let request_rcon = RequestRcon {
+            password: String::from("hunter2"),
+            command: String::from("map e1m1"),
+        };
+        let packet_len = request_rcon.packet_len() as usize;
+        let packet = request_rcon.to_bytes().unwrap();
+        assert_eq!(packet_len, packet.len());
+    }
+
+    #[test]
+    fn test_response_rcon_packet_len() {
+        let response_rcon = ResponseRcon {
+            output: String::from("\"sv_gravity\" is \"800\"\n"),
+        };
+        let packet_len = response_rcon.packet_len() as usize;
+        let packet = response_rcon.to_bytes().unwrap();
+        assert_eq!(packet_len, packet.len());
+    }
+
+    #[test]
+    fn test_rcon_round_trip() {
+        let listener = ConnectListener::bind("127.0.0.1:0").unwrap();
+        let listener_addr = listener.socket.local_addr().unwrap();
+        let mut socket = ConnectSocket::bind("127.0.0.1:0").unwrap();
+
+        socket
+            .send_request(Request::rcon("hunter2", "status"), listener_addr)
+            .unwrap();
+        let (request, remote) = listener.recv_request().unwrap();
+        match request {
+            Request::Rcon(r) => {
+                assert_eq!(r.password, "hunter2");
+                assert_eq!(r.command, "status");
+            }
+            r => panic!("unexpected request {:?}", r),
+        }
+
+        let response = Response::Rcon(ResponseRcon {
+            output: String::from("map: e1m1\n"),
+        });
+        listener.send_response(response, remote).unwrap();
+        match socket
+            .recv_response(Some(Duration::seconds(5)))
+            .unwrap()
+            .map(|(r, _)| r)
+        {
+            Some(Response::Rcon(r)) => assert_eq!(r.output, "map: e1m1\n"),
+            r => panic!("unexpected response {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_malformed_rcon() {
+        let listener = ConnectListener::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        // password with no terminating zero byte and no command
+        let mut packet = Vec::new();
+        packet
+            .write_i32::<NetworkEndian>(CONNECT_CONTROL | 9)
+            .unwrap();
+        packet.write_u8(RequestCode::Rcon as u8).unwrap();
+        packet.extend_from_slice(b"abcd");
+        socket
+            .send_to(&packet, listener.local_addr().unwrap())
+            .unwrap();
+
+        match listener.recv_request() {
+            Err(NetError::InvalidData(_)) => (),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_try_recv_request_empty() {
+        let listener = ConnectListener::bind("127.0.0.1:0").unwrap();
+        assert!(listener.try_recv_request().unwrap().is_none());
+    }
+
     #[test]
     fn test_connect_listener_bind() {
         let _listener = ConnectListener::bind("127.0.0.1:26000").unwrap();
@@ -46,7 +46,7 @@ pub const PROTOCOL_VERSION: u8 = 15;
 
 const NAME_LEN: usize = 64;
 
-const FAST_UPDATE_FLAG: u8 = 0x80;
+pub const FAST_UPDATE_FLAG: u8 = 0x80;
 
 const VELOCITY_READ_FACTOR: f32 = 16.0;
 const VELOCITY_WRITE_FACTOR: f32 = 1.0 / VELOCITY_READ_FACTOR;
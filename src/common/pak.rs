@@ -18,17 +18,20 @@
 //! Quake PAK archive manipulation.
 
 use std::{
-    collections::{hash_map::Iter, HashMap},
+    cell::RefCell,
+    collections::HashMap,
     fs,
-    io::{self, Read, Seek, SeekFrom},
+    io::{self, Read, Seek, SeekFrom, Write},
     path::Path,
 };
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use thiserror::Error;
 
 const PAK_MAGIC: [u8; 4] = [b'P', b'A', b'C', b'K'];
+const PAK_HEADER_SIZE: usize = 12;
 const PAK_ENTRY_SIZE: usize = 64;
+const PAK_PATH_SIZE: usize = 56;
 
 #[derive(Error, Debug)]
 pub enum PakError {
@@ -50,22 +53,66 @@ pub enum PakError {
     NonUtf8FileName(#[from] std::string::FromUtf8Error),
     #[error("No such file in PAK archive: {0}")]
     NoSuchFile(String),
+    #[error("Invalid file name: {0}")]
+    InvalidFileName(String),
+    #[error("Archive too large")]
+    ArchiveTooLarge,
+    #[error("{0} overlaps {1}")]
+    Overlap(String, String),
+    #[error("{0} overlaps the header")]
+    OverlapsHeader(String),
+    #[error("{0} overlaps the file table")]
+    OverlapsTable(String),
+    #[error("{path}: {source}")]
+    BadEntry { path: String, source: Box<PakError> },
+}
+
+/// The location of a file within a PAK archive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PakEntry {
+    offset: u64,
+    size: u64,
+}
+
+impl PakEntry {
+    /// Returns the offset of the file from the start of the archive.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the size of the file in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
 }
 
 /// An open Pak archive.
-pub struct Pak(HashMap<String, Box<[u8]>>);
+///
+/// Only the file table is read when the archive is opened. File contents are read from disk when
+/// the file is opened.
+pub struct Pak {
+    file: RefCell<fs::File>,
+    table_offset: u64,
+    table_size: u64,
 
-impl Pak {
-    // TODO: rename to from_path or similar
-    pub fn new<P>(path: P) -> Result<Pak, PakError>
-    where
-        P: AsRef<Path>,
-    {
-        debug!("Opening {}", path.as_ref().to_str().unwrap());
+    // entries in file table order, and the index of each path in `entries`
+    entries: Vec<(String, PakEntry)>,
+    indices: HashMap<String, usize>,
+}
 
-        let mut infile = fs::File::open(path)?;
+// the header and file table of an archive, before the entries are checked
+struct PakTable {
+    file_len: u64,
+    offset: u64,
+    size: u64,
+    entries: Vec<RawPakEntry>,
+}
+
+impl PakTable {
+    fn read_from(infile: &mut fs::File) -> Result<PakTable, PakError> {
+        let file_len = infile.metadata()?.len();
         let mut magic = [0u8; 4];
-        infile.read(&mut magic)?;
+        infile.read_exact(&mut magic)?;
 
         if magic != PAK_MAGIC {
             Err(PakError::InvalidMagicNumber(magic))?;
@@ -78,50 +125,186 @@ impl Pak {
         };
 
         let table_size = match infile.read_i32::<LittleEndian>()? {
-            s if s <= 0 || s as usize % PAK_ENTRY_SIZE != 0 => Err(PakError::InvalidTableSize(s))?,
+            s if s < 0 || s as usize % PAK_ENTRY_SIZE != 0 => Err(PakError::InvalidTableSize(s))?,
             s => s as u32,
         };
 
-        let mut map = HashMap::new();
+        if table_offset as u64 + table_size as u64 > file_len {
+            Err(PakError::InvalidTableSize(table_size as i32))?;
+        }
 
-        for i in 0..(table_size as usize / PAK_ENTRY_SIZE) {
-            let entry_offset = table_offset as u64 + (i * PAK_ENTRY_SIZE) as u64;
-            infile.seek(SeekFrom::Start(entry_offset))?;
+        // read the whole table at once rather than seeking for each entry
+        let mut table = vec![0u8; table_size as usize];
+        infile.seek(SeekFrom::Start(table_offset as u64))?;
+        infile.read_exact(&mut table)?;
 
-            let mut path_bytes = [0u8; 56];
-            infile.read(&mut path_bytes)?;
+        let mut entries = Vec::new();
+        for mut entry in table.chunks(PAK_ENTRY_SIZE) {
+            let mut path = [0u8; PAK_PATH_SIZE];
+            entry.read_exact(&mut path)?;
+            let offset = entry.read_i32::<LittleEndian>()?;
+            let size = entry.read_i32::<LittleEndian>()?;
+            entries.push(RawPakEntry { path, offset, size });
+        }
+
+        Ok(PakTable {
+            file_len,
+            offset: table_offset as u64,
+            size: table_size as u64,
+            entries,
+        })
+    }
+}
+
+// a file table entry as stored on disk
+struct RawPakEntry {
+    path: [u8; PAK_PATH_SIZE],
+    offset: i32,
+    size: i32,
+}
+
+impl RawPakEntry {
+    // the path up to the first NUL, for reporting problems with the entry
+    fn lossy_path(&self) -> String {
+        let last = self
+            .path
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(PAK_PATH_SIZE);
+        String::from_utf8_lossy(&self.path[..last]).into_owned()
+    }
+
+    // checks the entry against the length of the archive
+    fn parse(&self, file_len: u64) -> Result<(String, PakEntry), PakError> {
+        let file_offset = match self.offset {
+            o if o <= 0 || o as u64 > file_len => Err(PakError::InvalidFileOffset(o))?,
+            o => o as u64,
+        };
+
+        let file_size = match self.size {
+            s if s < 0 || file_offset + s as u64 > file_len => Err(PakError::InvalidFileSize(s))?,
+            s => s as u64,
+        };
+
+        let last = self
+            .path
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or(PakError::FileNameTooLong(
+                String::from_utf8_lossy(&self.path).into_owned(),
+            ))?;
+        let path = String::from_utf8(self.path[0..last].to_vec())?;
+
+        Ok((
+            path,
+            PakEntry {
+                offset: file_offset,
+                size: file_size,
+            },
+        ))
+    }
+}
+
+// finds files whose contents overlap each other, the header or the file table
+fn find_overlaps<'a, I>(entries: I, table_offset: u64, table_size: u64) -> Vec<PakError>
+where
+    I: Iterator<Item = &'a (String, PakEntry)>,
+{
+    let mut problems = Vec::new();
+
+    let mut sorted: Vec<&(String, PakEntry)> = entries.filter(|(_, e)| e.size > 0).collect();
+    sorted.sort_by_key(|(_, e)| e.offset);
+
+    // the entry with the furthest end so far
+    let mut furthest: Option<&(String, PakEntry)> = None;
+    for item in sorted {
+        let (path, entry) = item;
 
-            let file_offset = match infile.read_i32::<LittleEndian>()? {
-                o if o <= 0 => Err(PakError::InvalidFileOffset(o))?,
-                o => o as u32,
-            };
+        if entry.offset < PAK_HEADER_SIZE as u64 {
+            problems.push(PakError::OverlapsHeader(path.to_owned()));
+        }
+
+        if entry.offset < table_offset + table_size && table_offset < entry.offset + entry.size {
+            problems.push(PakError::OverlapsTable(path.to_owned()));
+        }
+
+        if let Some((prev_path, prev)) = furthest {
+            if entry.offset < prev.offset + prev.size {
+                problems.push(PakError::Overlap(prev_path.to_owned(), path.to_owned()));
+            }
+        }
+
+        if furthest.map_or(true, |(_, f)| entry.offset + entry.size > f.offset + f.size) {
+            furthest = Some(item);
+        }
+    }
+
+    problems
+}
+
+impl Pak {
+    // TODO: rename to from_path or similar
+    pub fn new<P>(path: P) -> Result<Pak, PakError>
+    where
+        P: AsRef<Path>,
+    {
+        debug!("Opening {}", path.as_ref().to_str().unwrap());
+
+        let mut infile = fs::File::open(path)?;
+        let table = PakTable::read_from(&mut infile)?;
 
-            let file_size = match infile.read_i32::<LittleEndian>()? {
-                s if s <= 0 => Err(PakError::InvalidFileSize(s))?,
-                s => s as u32,
-            };
+        let mut entries = Vec::new();
+        let mut indices = HashMap::new();
 
-            let last = path_bytes
-                .iter()
-                .position(|b| *b == 0)
-                .ok_or(PakError::FileNameTooLong(
-                    String::from_utf8_lossy(&path_bytes).into_owned(),
-                ))?;
-            let path = String::from_utf8(path_bytes[0..last].to_vec())?;
-            infile.seek(SeekFrom::Start(file_offset as u64))?;
+        for raw in table.entries.iter() {
+            let (path, entry) = raw.parse(table.file_len)?;
 
-            let mut data: Vec<u8> = Vec::with_capacity(file_size as usize);
-            (&mut infile)
-                .take(file_size as u64)
-                .read_to_end(&mut data)?;
+            // like the original engine, the first entry with a given path wins
+            if !indices.contains_key(&path) {
+                indices.insert(path.clone(), entries.len());
+            }
 
-            map.insert(path, data.into_boxed_slice());
+            entries.push((path, entry));
         }
 
-        Ok(Pak(map))
+        Ok(Pak {
+            file: RefCell::new(infile),
+            table_offset: table.offset,
+            table_size: table.size,
+            entries,
+            indices,
+        })
     }
 
-    /// Opens a file in the file tree for reading.
+    /// Checks the archive at `path` without opening it.
+    ///
+    /// Unlike `Pak::new`, entries with out-of-range offsets or sizes or invalid names are reported
+    /// rather than rejected, and the remaining entries are checked as in `verify`. Only an
+    /// unreadable header or file table is an error.
+    pub fn verify_path<P>(path: P) -> Result<Vec<PakError>, PakError>
+    where
+        P: AsRef<Path>,
+    {
+        let mut infile = fs::File::open(path)?;
+        let table = PakTable::read_from(&mut infile)?;
+
+        let mut problems = Vec::new();
+        let mut entries = Vec::new();
+        for raw in table.entries.iter() {
+            match raw.parse(table.file_len) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => problems.push(PakError::BadEntry {
+                    path: raw.lossy_path(),
+                    source: Box::new(e),
+                }),
+            }
+        }
+
+        problems.extend(find_overlaps(entries.iter(), table.offset, table.size));
+        Ok(problems)
+    }
+
+    /// Reads a file in the file tree.
     ///
     /// # Examples
     /// ```no_run
@@ -133,18 +316,313 @@ impl Pak {
     /// let progs_dat = pak.open("progs.dat").unwrap();
     /// # }
     /// ```
-    pub fn open<S>(&self, path: S) -> Result<&[u8], PakError>
+    pub fn open<S>(&self, path: S) -> Result<Box<[u8]>, PakError>
+    where
+        S: AsRef<str>,
+    {
+        let path = path.as_ref();
+        let entry = match self.indices.get(path) {
+            Some(&i) => self.entries[i].1,
+            None => Err(PakError::NoSuchFile(path.to_owned()))?,
+        };
+
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(entry.offset))?;
+
+        let mut data = vec![0u8; entry.size as usize];
+        file.read_exact(&mut data)?;
+
+        Ok(data.into_boxed_slice())
+    }
+
+    /// Iterates over the paths and entries of all files in the archive, in file table order.
+    ///
+    /// File contents can be read with `open`.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &PakEntry)> {
+        self.entries
+            .iter()
+            .map(|(path, entry)| (path.as_str(), entry))
+    }
+
+    /// Checks the file table for files whose contents overlap each other or the table itself.
+    ///
+    /// Offsets outside the archive are already rejected by `Pak::new`; use `Pak::verify_path` to
+    /// report them instead. Returns every problem found, or an empty `Vec` if the archive is
+    /// consistent.
+    pub fn verify(&self) -> Vec<PakError> {
+        find_overlaps(self.entries.iter(), self.table_offset, self.table_size)
+    }
+}
+
+/// Builds a PAK archive in memory.
+///
+/// Archives are written in the same layout as id's tools: the header, then the contents of each
+/// file in the order they were added, then the file table.
+pub struct PakWriter {
+    files: Vec<(String, Box<[u8]>)>,
+}
+
+impl PakWriter {
+    pub fn new() -> PakWriter {
+        PakWriter { files: Vec::new() }
+    }
+
+    /// Reads every file in `pak` into a new writer, keeping the file table order.
+    pub fn from_pak(pak: &Pak) -> Result<PakWriter, PakError> {
+        let mut writer = PakWriter::new();
+        for (path, _) in pak.iter() {
+            writer.add(path, pak.open(path)?)?;
+        }
+
+        Ok(writer)
+    }
+
+    /// Adds a file to the archive.
+    ///
+    /// If a file already exists at `path`, its contents are replaced but it keeps its position.
+    /// Paths must be ASCII and shorter than 56 bytes.
+    pub fn add<S, D>(&mut self, path: S, data: D) -> Result<(), PakError>
     where
         S: AsRef<str>,
+        D: Into<Box<[u8]>>,
     {
         let path = path.as_ref();
-        self.0
-            .get(path)
-            .map(|s| s.as_ref())
-            .ok_or(PakError::NoSuchFile(path.to_owned()))
+        if path.is_empty() || !path.is_ascii() || path.contains('\0') {
+            Err(PakError::InvalidFileName(path.to_owned()))?;
+        }
+
+        if path.len() >= PAK_PATH_SIZE {
+            Err(PakError::FileNameTooLong(path.to_owned()))?;
+        }
+
+        let data = data.into();
+        match self.files.iter_mut().find(|(p, _)| p == path) {
+            Some(file) => file.1 = data,
+            None => self.files.push((path.to_owned(), data)),
+        }
+
+        Ok(())
+    }
+
+    /// Removes a file from the archive, returning whether it was present.
+    pub fn remove<S>(&mut self, path: S) -> bool
+    where
+        S: AsRef<str>,
+    {
+        let len = self.files.len();
+        self.files.retain(|(p, _)| p != path.as_ref());
+        self.files.len() != len
+    }
+
+    /// Iterates over the paths of the files in the archive, in the order they'll be written.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.files.iter().map(|(path, _)| path.as_str())
+    }
+
+    /// Writes the archive to `writer`.
+    pub fn write_to<W>(&self, mut writer: W) -> Result<(), PakError>
+    where
+        W: Write,
+    {
+        let data_size: u64 = self.files.iter().map(|(_, d)| d.len() as u64).sum();
+        let table_offset = PAK_HEADER_SIZE as u64 + data_size;
+        let table_size = (self.files.len() * PAK_ENTRY_SIZE) as u64;
+
+        // offsets and sizes are stored as i32
+        if table_offset + table_size > i32::max_value() as u64 {
+            Err(PakError::ArchiveTooLarge)?;
+        }
+
+        writer.write_all(&PAK_MAGIC)?;
+        writer.write_i32::<LittleEndian>(table_offset as i32)?;
+        writer.write_i32::<LittleEndian>(table_size as i32)?;
+
+        for (_, data) in self.files.iter() {
+            writer.write_all(data)?;
+        }
+
+        let mut offset = PAK_HEADER_SIZE as i32;
+        for (path, data) in self.files.iter() {
+            let mut path_bytes = [0u8; PAK_PATH_SIZE];
+            path_bytes[..path.len()].copy_from_slice(path.as_bytes());
+            writer.write_all(&path_bytes)?;
+            writer.write_i32::<LittleEndian>(offset)?;
+            writer.write_i32::<LittleEndian>(data.len() as i32)?;
+            offset += data.len() as i32;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::util::TempDir;
+
+    // builds a PAK archive containing the given files
+    fn build_pak(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut table = Vec::new();
+
+        for (name, contents) in files {
+            let offset = 12 + data.len() as i32;
+            data.extend_from_slice(contents);
+
+            let mut path_bytes = [0u8; 56];
+            path_bytes[..name.len()].copy_from_slice(name.as_bytes());
+            table.extend_from_slice(&path_bytes);
+            table.write_i32::<LittleEndian>(offset).unwrap();
+            table
+                .write_i32::<LittleEndian>(contents.len() as i32)
+                .unwrap();
+        }
+
+        let mut pak = PAK_MAGIC.to_vec();
+        pak.write_i32::<LittleEndian>(12 + data.len() as i32)
+            .unwrap();
+        pak.write_i32::<LittleEndian>(table.len() as i32).unwrap();
+        pak.extend(data);
+        pak.extend(table);
+        pak
+    }
+
+    #[test]
+    fn test_open() {
+        let dir = TempDir::new();
+        let path = dir.write(
+            "test_open.pak",
+            &build_pak(&[
+                ("progs.dat", &b"progs"[..]),
+                ("maps/e1m1.bsp", &b"bsp data"[..]),
+            ]),
+        );
+        let pak = Pak::new(&path).unwrap();
+
+        assert_eq!(&*pak.open("maps/e1m1.bsp").unwrap(), b"bsp data");
+        assert_eq!(&*pak.open("progs.dat").unwrap(), b"progs");
+        assert!(pak.open("gfx.wad").is_err());
+
+        let mut sizes: Vec<(&str, u64)> = pak.iter().map(|(p, e)| (p, e.size())).collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![("maps/e1m1.bsp", 8), ("progs.dat", 5)]);
     }
 
-    pub fn iter<'a>(&self) -> Iter<String, impl AsRef<[u8]>> {
-        self.0.iter()
+    #[test]
+    fn test_truncated() {
+        let mut data = build_pak(&[("progs.dat", &b"progs"[..])]);
+
+        // point the file past the end of the archive
+        let len = data.len();
+        data[len - 4..].copy_from_slice(&1000i32.to_le_bytes());
+
+        let dir = TempDir::new();
+        let path = dir.write("test_truncated.pak", &data);
+        match Pak::new(&path) {
+            Err(PakError::InvalidFileSize(1000)) => (),
+            r => panic!("unexpected result {:?}", r.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_writer() {
+        let files: &[(&str, &[u8])] = &[
+            ("progs.dat", &b"progs"[..]),
+            ("maps/e1m1.bsp", &b"bsp data"[..]),
+            ("gfx/pop.lmp", &b""[..]),
+        ];
+
+        let mut writer = PakWriter::new();
+        for (path, data) in files {
+            writer.add(path, *data).unwrap();
+        }
+        writer.add("gfx.wad", &b"wad"[..]).unwrap();
+        writer.add("progs.dat", &b"progs"[..]).unwrap();
+        assert!(writer.remove("gfx.wad"));
+        assert!(!writer.remove("gfx.wad"));
+
+        // output should match the layout of id's tools exactly
+        let mut data = Vec::new();
+        writer.write_to(&mut data).unwrap();
+        assert_eq!(data, build_pak(files));
+
+        let dir = TempDir::new();
+        let path = dir.write("test_writer.pak", &data);
+        let pak = Pak::new(&path).unwrap();
+        let paths: Vec<&str> = pak.iter().map(|(p, _)| p).collect();
+        assert_eq!(paths, vec!["progs.dat", "maps/e1m1.bsp", "gfx/pop.lmp"]);
+        assert!(pak.verify().is_empty());
+
+        let mut copy = Vec::new();
+        PakWriter::from_pak(&pak)
+            .unwrap()
+            .write_to(&mut copy)
+            .unwrap();
+        assert_eq!(copy, data);
+    }
+
+    #[test]
+    fn test_writer_bad_name() {
+        let mut writer = PakWriter::new();
+        match writer.add(&"a".repeat(56), &b""[..]) {
+            Err(PakError::FileNameTooLong(_)) => (),
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert!(writer.add("", &b""[..]).is_err());
+        assert!(writer.add("maps/\u{e9}.bsp", &b""[..]).is_err());
+        assert_eq!(writer.paths().count(), 0);
+    }
+
+    #[test]
+    fn test_verify() {
+        let mut data = build_pak(&[("a.txt", &b"aaaa"[..]), ("b.txt", &b"bbbb"[..])]);
+
+        // point b.txt into the middle of a.txt
+        let len = data.len();
+        data[len - 8..len - 4].copy_from_slice(&14i32.to_le_bytes());
+
+        let dir = TempDir::new();
+        let path = dir.write("test_verify.pak", &data);
+        let problems: Vec<String> = Pak::new(&path)
+            .unwrap()
+            .verify()
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(problems, vec!["a.txt overlaps b.txt".to_owned()]);
+    }
+
+    #[test]
+    fn test_verify_out_of_range() {
+        let mut data = build_pak(&[
+            ("a.txt", &b"aaaa"[..]),
+            ("b.txt", &b"bbbb"[..]),
+            ("c.txt", &b"cccc"[..]),
+        ]);
+
+        // point a.txt past the end of the archive and make c.txt overlap b.txt
+        let table_offset = data.len() - 3 * PAK_ENTRY_SIZE;
+        data[table_offset + 56..table_offset + 60].copy_from_slice(&5000i32.to_le_bytes());
+        let c_offset = table_offset + 2 * PAK_ENTRY_SIZE + 56;
+        data[c_offset..c_offset + 4].copy_from_slice(&18i32.to_le_bytes());
+
+        let dir = TempDir::new();
+        let path = dir.write("test_verify_out_of_range.pak", &data);
+        assert!(Pak::new(&path).is_err());
+
+        let problems: Vec<String> = Pak::verify_path(&path)
+            .unwrap()
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                "a.txt: Invalid file offset: 5000".to_owned(),
+                "b.txt overlaps c.txt".to_owned(),
+            ]
+        );
     }
 }
@@ -118,6 +118,19 @@ pub fn command(input: &str) -> nom::IResult<&str, Vec<&str>> {
     terminated(many1(preceded(space0, arg)), command_terminator)(input)
 }
 
+/// Match the next command, skipping any preceding empty lines.
+///
+/// Returns `None` if only whitespace remains.
+pub fn next_command(input: &str) -> nom::IResult<&str, Option<Vec<&str>>> {
+    let (input, _) = many0(empty_line)(input)?;
+    if input.trim().is_empty() {
+        return Ok(("", None));
+    }
+
+    let (input, args) = command(input)?;
+    Ok((input, Some(args)))
+}
+
 pub fn commands(input: &str) -> nom::IResult<&str, Vec<Vec<&str>>> {
     delimited(
         many0(empty_line),
@@ -190,6 +203,15 @@ mod test {
         assert_eq!(result, Ok(("\n", vec!["bind", "space", "+jump"])));
     }
 
+    #[test]
+    fn test_next_command() {
+        let result = next_command("\n// comment\nwait; echo hi\n");
+        assert_eq!(result, Ok((" echo hi\n", Some(vec!["wait"]))));
+
+        let result = next_command("// comment\n  \n");
+        assert_eq!(result, Ok(("", None)));
+    }
+
     #[test]
     fn test_commands_quake_rc() {
         let script = "
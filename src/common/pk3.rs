@@ -0,0 +1,162 @@
+// Copyright © 2018 Cormac O'Brien
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this software
+// and associated documentation files (the "Software"), to deal in the Software without
+// restriction, including without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all copies or
+// substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING
+// BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! PK3 (zip) archive support.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use thiserror::Error;
+use zip::{result::ZipError, ZipArchive};
+
+#[derive(Error, Debug)]
+pub enum Pk3Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Zip error: {0}")]
+    Zip(#[from] ZipError),
+    #[error("No such file in PK3 archive: {0}")]
+    NoSuchFile(String),
+}
+
+/// The location of a file within a PK3 archive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pk3Entry {
+    index: usize,
+    size: u64,
+}
+
+impl Pk3Entry {
+    /// Returns the uncompressed size of the file in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// An open PK3 archive.
+///
+/// Like `Pak`, only the central directory is read when the archive is opened. Files are read and
+/// decompressed when they're opened.
+pub struct Pk3 {
+    archive: RefCell<ZipArchive<fs::File>>,
+    entries: HashMap<String, Pk3Entry>,
+}
+
+impl Pk3 {
+    pub fn new<P>(path: P) -> Result<Pk3, Pk3Error>
+    where
+        P: AsRef<Path>,
+    {
+        debug!("Opening {}", path.as_ref().display());
+
+        let mut archive = ZipArchive::new(fs::File::open(path)?)?;
+        let mut entries = HashMap::new();
+
+        for index in 0..archive.len() {
+            let file = archive.by_index(index)?;
+
+            // directories are implied by the paths of the files they contain
+            if file.is_dir() {
+                continue;
+            }
+
+            entries.insert(
+                file.name().to_owned(),
+                Pk3Entry {
+                    index,
+                    size: file.size(),
+                },
+            );
+        }
+
+        Ok(Pk3 {
+            archive: RefCell::new(archive),
+            entries,
+        })
+    }
+
+    /// Reads and decompresses a file in the archive.
+    pub fn open<S>(&self, path: S) -> Result<Box<[u8]>, Pk3Error>
+    where
+        S: AsRef<str>,
+    {
+        let path = path.as_ref();
+        let entry = self
+            .entries
+            .get(path)
+            .ok_or(Pk3Error::NoSuchFile(path.to_owned()))?;
+
+        let mut archive = self.archive.borrow_mut();
+        let mut file = archive.by_index(entry.index)?;
+
+        let mut data = Vec::with_capacity(entry.size as usize);
+        file.read_to_end(&mut data)?;
+
+        Ok(data.into_boxed_slice())
+    }
+
+    /// Iterates over the paths and entries of all files in the archive.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Pk3Entry)> {
+        self.entries
+            .iter()
+            .map(|(path, entry)| (path.as_str(), entry))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::util::TempDir;
+
+    use std::io::{Cursor, Write};
+
+    use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+    #[test]
+    fn test_open() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        writer.add_directory("maps/", deflated).unwrap();
+        writer.start_file("maps/e1m1.bsp", deflated).unwrap();
+        writer.write_all(&[7u8; 1000]).unwrap();
+        writer
+            .start_file(
+                "progs.dat",
+                FileOptions::default().compression_method(CompressionMethod::Stored),
+            )
+            .unwrap();
+        writer.write_all(b"progs").unwrap();
+        let data = writer.finish().unwrap().into_inner();
+
+        let dir = TempDir::new();
+        let path = dir.write("test.pk3", &data);
+        let pk3 = Pk3::new(&path).unwrap();
+
+        assert_eq!(&*pk3.open("maps/e1m1.bsp").unwrap(), &[7u8; 1000][..]);
+        assert_eq!(&*pk3.open("progs.dat").unwrap(), b"progs");
+        assert!(pk3.open("maps/").is_err());
+
+        let mut sizes: Vec<(&str, u64)> = pk3.iter().map(|(p, e)| (p, e.size())).collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![("maps/e1m1.bsp", 1000), ("progs.dat", 5)]);
+    }
+}
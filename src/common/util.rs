@@ -16,6 +16,12 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 
 use std::mem::size_of;
+#[cfg(test)]
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use byteorder::{LittleEndian, ReadBytesExt};
 
@@ -81,3 +87,51 @@ where
         size_of::<T>() / size_of::<u32>(),
     )
 }
+
+/// A directory for tests that need real files, which is deleted when it goes out of scope.
+#[cfg(test)]
+pub struct TempDir {
+    path: PathBuf,
+}
+
+#[cfg(test)]
+impl TempDir {
+    /// Creates a new empty directory under the system's temporary directory.
+    pub fn new() -> TempDir {
+        // tests run in parallel, so each directory needs a distinct name
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+
+        let path = std::env::temp_dir().join(format!("richter-{}-{}", std::process::id(), id));
+        fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes a file at `path` relative to the directory, creating any missing parent directories.
+    ///
+    /// Returns the full path of the file.
+    pub fn write<P, C>(&self, path: P, contents: C) -> PathBuf
+    where
+        P: AsRef<Path>,
+        C: AsRef<[u8]>,
+    {
+        let path = self.path.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        // don't panic while a failed test is already unwinding
+        if let Err(e) = fs::remove_dir_all(&self.path) {
+            eprintln!("Couldn't remove {}: {}", self.path.display(), e);
+        }
+    }
+}
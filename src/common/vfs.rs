@@ -16,12 +16,20 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 
 use std::{
-    fs::File,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fs::{self, File},
     io::{self, Cursor, Read, Seek, SeekFrom},
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
+    time::SystemTime,
 };
 
-use crate::common::pak::{Pak, PakError};
+use crate::common::{
+    console::glob_match,
+    pak::{Pak, PakError},
+    pk3::{Pk3, Pk3Error},
+    DEFAULT_BASEDIR, MAX_PAKFILES,
+};
 
 use thiserror::Error;
 
@@ -29,32 +37,184 @@ use thiserror::Error;
 pub enum VfsError {
     #[error("Couldn't load pakfile: {0}")]
     Pak(#[from] PakError),
+    #[error("Couldn't load PK3 archive: {0}")]
+    Pk3(#[from] Pk3Error),
     #[error("File does not exist: {0}")]
     NoSuchFile(String),
+    #[error("No such game directory: {0}")]
+    NoSuchGame(String),
 }
 
+// maps folded virtual paths (see `fold`) to the names actually stored in a component
+type FoldedIndex = HashMap<String, String>;
+
+// the modification time and size of each file in the directory components, keyed by component
+// index and virtual path
+type Snapshot = HashMap<(usize, String), (Option<SystemTime>, u64)>;
+
 enum VfsComponent {
-    Pak(Pak),
-    Directory(PathBuf),
+    Pak {
+        path: PathBuf,
+        pak: Pak,
+        index: FoldedIndex,
+    },
+    Pk3 {
+        path: PathBuf,
+        pk3: Pk3,
+        index: FoldedIndex,
+    },
+    Directory {
+        path: PathBuf,
+
+        // built the first time a path isn't found as given, and rebuilt by `Vfs::refresh`
+        index: RefCell<Option<FoldedIndex>>,
+    },
+}
+
+impl VfsComponent {
+    // describes the component for the `path` command
+    fn describe(&self) -> String {
+        match self {
+            VfsComponent::Pak { path, pak, .. } => {
+                format!("{} ({} files)", path.display(), pak.iter().count())
+            }
+            VfsComponent::Pk3 { path, pk3, .. } => {
+                format!("{} ({} files)", path.display(), pk3.iter().count())
+            }
+            VfsComponent::Directory { path, .. } => format!("{}/", path.display()),
+        }
+    }
+
+    // returns the name stored in the component for a folded virtual path
+    fn resolve(&self, folded: &str) -> Option<String> {
+        match self {
+            VfsComponent::Pak { index, .. } | VfsComponent::Pk3 { index, .. } => {
+                index.get(folded).cloned()
+            }
+            VfsComponent::Directory { path, index } => index
+                .borrow_mut()
+                .get_or_insert_with(|| {
+                    let mut found = Vec::new();
+                    walk_directory(path, "", &mut |name, _| found.push(name));
+                    build_index(found.iter().map(String::as_str))
+                })
+                .get(folded)
+                .cloned(),
+        }
+    }
+}
+
+/// Normalizes a virtual path.
+///
+/// Backslashes are treated as separators, and empty and `.` components are removed. `..` removes
+/// the preceding component. Returns `None` if the path is empty or leaves the root.
+pub fn normalize(path: &str) -> Option<String> {
+    let mut components = Vec::new();
+    for c in path.split(|c| c == '/' || c == '\\') {
+        match c {
+            "" | "." => (),
+            ".." => {
+                components.pop()?;
+            }
+            c => components.push(c),
+        }
+    }
+
+    if components.is_empty() {
+        return None;
+    }
+
+    Some(components.join("/"))
+}
+
+// normalizes and case-folds a virtual path, so paths differing only in case compare equal
+fn fold(path: &str) -> Option<String> {
+    normalize(path).map(|p| p.to_lowercase())
+}
+
+// indexes the given stored names by folded path. the first name wins if several fold together
+fn build_index<'a, I>(names: I) -> FoldedIndex
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut index = HashMap::new();
+    for name in names {
+        if let Some(folded) = fold(name) {
+            index.entry(folded).or_insert_with(|| name.to_owned());
+        }
+    }
+
+    index
 }
 
 pub struct Vfs {
-    components: Vec<VfsComponent>,
+    components: RefCell<Vec<VfsComponent>>,
+
+    // the directory containing the game directories
+    basedir: PathBuf,
+
+    // the number of components belonging to the base game. these are kept when the mod changes
+    base_len: usize,
+
+    // the mod layered over the base game, if any
+    game: RefCell<Option<String>>,
+
+    // the state of the directory components at the last call to `poll_changes`
+    snapshot: RefCell<Option<Snapshot>>,
 }
 
 impl Vfs {
     pub fn new() -> Vfs {
         Vfs {
-            components: Vec::new(),
+            components: RefCell::new(Vec::new()),
+            basedir: PathBuf::new(),
+            base_len: 0,
+            game: RefCell::new(None),
+            snapshot: RefCell::new(None),
         }
     }
 
+    /// Constructs a `Vfs` for the base game in `basedir`, with the mod `game` layered on top.
+    pub fn with_game<P>(basedir: P, game: Option<&str>) -> Result<Vfs, VfsError>
+    where
+        P: AsRef<Path>,
+    {
+        let mut vfs = Vfs::new();
+        vfs.basedir = basedir.as_ref().to_path_buf();
+        vfs.add_game_dir(vfs.basedir.join(DEFAULT_BASEDIR))?;
+        vfs.base_len = vfs.components.get_mut().len();
+        vfs.set_game(game)?;
+
+        Ok(vfs)
+    }
+
     pub fn add_pakfile<P>(&mut self, path: P) -> Result<(), VfsError>
     where
         P: AsRef<Path>,
     {
         let path = path.as_ref();
-        self.components.push(VfsComponent::Pak(Pak::new(path)?));
+        let pak = Pak::new(path)?;
+        let index = build_index(pak.iter().map(|(name, _)| name));
+        self.components.get_mut().push(VfsComponent::Pak {
+            path: path.to_path_buf(),
+            pak,
+            index,
+        });
+        Ok(())
+    }
+
+    pub fn add_pk3file<P>(&mut self, path: P) -> Result<(), VfsError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let pk3 = Pk3::new(path)?;
+        let index = build_index(pk3.iter().map(|(name, _)| name));
+        self.components.get_mut().push(VfsComponent::Pk3 {
+            path: path.to_path_buf(),
+            pk3,
+            index,
+        });
         Ok(())
     }
 
@@ -62,47 +222,336 @@ impl Vfs {
     where
         P: AsRef<Path>,
     {
-        self.components
-            .push(VfsComponent::Directory(path.as_ref().to_path_buf()));
+        self.components.get_mut().push(VfsComponent::Directory {
+            path: path.as_ref().to_path_buf(),
+            index: RefCell::new(None),
+        });
+        Ok(())
+    }
+
+    /// Adds a game directory and the archives it contains.
+    ///
+    /// The directory itself is added first, then `pak0.pak`, `pak1.pak` and so on until one is
+    /// missing, then any `.pk3` archives in alphabetical order.
+    pub fn add_game_dir<P>(&mut self, dir: P) -> Result<(), VfsError>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref();
+        self.add_directory(dir)?;
+
+        // keep adding PAKs until we don't find one or we hit MAX_PAKFILES
+        for pak_id in 0..MAX_PAKFILES {
+            let path = dir.join(format!("pak{}.pak", pak_id));
+            if !path.exists() {
+                break;
+            }
+
+            self.add_pakfile(path)?;
+        }
+
+        let mut pk3_paths: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(read_dir) => read_dir
+                .filter_map(Result::ok)
+                .map(|e| e.path())
+                .filter(|p| {
+                    p.extension()
+                        .and_then(|e| e.to_str())
+                        .map_or(false, |e| e.eq_ignore_ascii_case("pk3"))
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        pk3_paths.sort();
+
+        for path in pk3_paths {
+            self.add_pk3file(path)?;
+        }
+
         Ok(())
     }
 
+    /// Replaces the current mod with `game`, or removes it if `game` is `None`.
+    ///
+    /// `game` must name a directory in the base directory. Selecting the base game is the same as
+    /// selecting no mod.
+    pub fn set_game(&self, game: Option<&str>) -> Result<(), VfsError> {
+        let game = game.filter(|g| !g.eq_ignore_ascii_case(DEFAULT_BASEDIR));
+
+        // build the new layer before touching the current one, so a bad mod leaves it intact
+        let mut layer = Vfs::new();
+        if let Some(g) = game {
+            let mut components = Path::new(g).components();
+            let dir = match (components.next(), components.next()) {
+                (Some(Component::Normal(_)), None) => self.basedir.join(g),
+                _ => return Err(VfsError::NoSuchGame(g.to_owned())),
+            };
+
+            if !dir.is_dir() {
+                return Err(VfsError::NoSuchGame(g.to_owned()));
+            }
+
+            layer.add_game_dir(dir)?;
+        }
+
+        let mut components = self.components.borrow_mut();
+        components.truncate(self.base_len);
+        components.extend(layer.components.into_inner());
+        self.game.replace(game.map(str::to_owned));
+
+        // component indices may have changed, so don't report the new layer's files as modified
+        self.snapshot.replace(None);
+
+        Ok(())
+    }
+
+    /// Returns the name of the current mod, if any.
+    pub fn game(&self) -> Option<String> {
+        self.game.borrow().clone()
+    }
+
+    /// Returns the directory of the current mod, or of the base game if no mod is active.
+    ///
+    /// Files written by the game, like `config.cfg`, go here.
+    pub fn game_dir(&self) -> PathBuf {
+        match *self.game.borrow() {
+            Some(ref g) => self.basedir.join(g),
+            None => self.basedir.join(DEFAULT_BASEDIR),
+        }
+    }
+
+    /// Describes each component in the order they are searched.
+    pub fn search_path(&self) -> Vec<String> {
+        self.components
+            .borrow()
+            .iter()
+            .rev()
+            .map(VfsComponent::describe)
+            .collect()
+    }
+
     pub fn open<S>(&self, virtual_path: S) -> Result<VirtualFile, VfsError>
     where
         S: AsRef<str>,
     {
         let vp = virtual_path.as_ref();
+        let (normalized, folded) = match (normalize(vp), fold(vp)) {
+            (Some(n), Some(f)) => (n, f),
+            _ => return Err(VfsError::NoSuchFile(vp.to_owned())),
+        };
 
         // iterate in reverse so later PAKs overwrite earlier ones
-        for c in self.components.iter().rev() {
+        for c in self.components.borrow().iter().rev() {
             match c {
-                VfsComponent::Pak(pak) => {
-                    if let Ok(f) = pak.open(vp) {
-                        return Ok(VirtualFile::PakBacked(Cursor::new(f)));
+                VfsComponent::Pak { pak, .. } => {
+                    if let Some(name) = c.resolve(&folded) {
+                        return Ok(VirtualFile::PakBacked(Cursor::new(pak.open(name)?)));
                     }
                 }
 
-                VfsComponent::Directory(path) => {
-                    let mut full_path = path.to_owned();
-                    full_path.push(vp);
+                VfsComponent::Pk3 { pk3, .. } => {
+                    if let Some(name) = c.resolve(&folded) {
+                        return Ok(VirtualFile::PakBacked(Cursor::new(pk3.open(name)?)));
+                    }
+                }
 
-                    if let Ok(f) = File::open(full_path) {
+                VfsComponent::Directory { path, .. } => {
+                    // try the path as given first, so the index is only built if needed
+                    if let Ok(f) = File::open(path.join(&normalized)) {
                         return Ok(VirtualFile::FileBacked(f));
                     }
+
+                    if let Some(name) = c.resolve(&folded) {
+                        if let Ok(f) = File::open(path.join(name)) {
+                            return Ok(VirtualFile::FileBacked(f));
+                        }
+                    }
                 }
             }
         }
 
         Err(VfsError::NoSuchFile(vp.to_owned()))
     }
+
+    /// Discards the cached indices of directory contents.
+    ///
+    /// Files added to directories after their index was built can otherwise only be opened by
+    /// their exact path.
+    pub fn refresh(&self) {
+        for c in self.components.borrow().iter() {
+            if let VfsComponent::Directory { index, .. } = c {
+                index.replace(None);
+            }
+        }
+    }
+
+    /// Returns the virtual paths of files in directory components which were added or modified
+    /// since the last call, sorted and without duplicates.
+    ///
+    /// This walks every directory component, so it should be called at most every second or so.
+    /// The first call only records the current state. Archives aren't watched.
+    pub fn poll_changes(&self) -> Vec<String> {
+        let mut current = Snapshot::new();
+        for (i, c) in self.components.borrow().iter().enumerate() {
+            if let VfsComponent::Directory { path, .. } = c {
+                walk_directory(path, "", &mut |name, metadata| {
+                    current.insert((i, name), (metadata.modified().ok(), metadata.len()));
+                });
+            }
+        }
+
+        let previous = match self.snapshot.replace(Some(current)) {
+            Some(p) => p,
+            None => return Vec::new(),
+        };
+
+        let snapshot = self.snapshot.borrow();
+        let mut added = false;
+        let mut changed: Vec<String> = snapshot
+            .iter()
+            .flatten()
+            .filter(|(key, stamp)| match previous.get(*key) {
+                Some(prev) => prev != *stamp,
+                None => {
+                    added = true;
+                    true
+                }
+            })
+            .map(|((_, name), _)| name.clone())
+            .collect();
+        changed.sort();
+        changed.dedup();
+
+        // new files may differ in case from the paths used to open them
+        if added {
+            self.refresh();
+        }
+
+        changed
+    }
+
+    // returns every visible file, sorted by virtual path. if a path is present in more than one
+    // component, only the file which would be returned by `open` is listed
+    fn entries(&self) -> Vec<VfsEntry> {
+        let mut seen = HashSet::new();
+        let mut entries = Vec::new();
+
+        for c in self.components.borrow().iter().rev() {
+            let mut found = Vec::new();
+            match c {
+                VfsComponent::Pak { pak, .. } => {
+                    for (path, entry) in pak.iter() {
+                        found.push(VfsEntry {
+                            path: path.to_owned(),
+                            size: entry.size(),
+                        });
+                    }
+                }
+
+                VfsComponent::Pk3 { pk3, .. } => {
+                    for (path, entry) in pk3.iter() {
+                        found.push(VfsEntry {
+                            path: path.to_owned(),
+                            size: entry.size(),
+                        });
+                    }
+                }
+
+                VfsComponent::Directory { path, .. } => {
+                    walk_directory(path, "", &mut |name, metadata| {
+                        found.push(VfsEntry {
+                            path: name,
+                            size: metadata.len(),
+                        })
+                    })
+                }
+            }
+
+            for entry in found {
+                if let Some(folded) = fold(&entry.path) {
+                    if seen.insert(folded) {
+                        entries.push(entry);
+                    }
+                }
+            }
+        }
+
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        entries
+    }
+
+    /// Returns all files whose virtual paths begin with `prefix`, sorted by virtual path.
+    ///
+    /// If a path is present in more than one component, only the file which would be returned by
+    /// `open` is listed. Like `open`, the comparison ignores case.
+    pub fn list<S>(&self, prefix: S) -> Vec<VfsEntry>
+    where
+        S: AsRef<str>,
+    {
+        let prefix = prefix.as_ref().replace('\\', "/").to_lowercase();
+        let mut entries = self.entries();
+        entries.retain(|e| e.path.to_lowercase().starts_with(&prefix));
+        entries
+    }
+
+    /// Returns all files whose virtual paths match `pattern`, sorted by virtual path.
+    ///
+    /// `?` matches any one character and `*` matches any number of characters, neither crossing a
+    /// `/`. `**` matches any number of characters including `/`, so `**.dem` matches every demo.
+    /// Overridden files are omitted and case is ignored as with `list`.
+    pub fn glob<S>(&self, pattern: S) -> Vec<VfsEntry>
+    where
+        S: AsRef<str>,
+    {
+        let pattern = pattern.as_ref().to_lowercase();
+        let mut entries = self.entries();
+        entries.retain(|e| glob_match(&pattern, &e.path.to_lowercase()));
+        entries
+    }
 }
 
-pub enum VirtualFile<'a> {
-    PakBacked(Cursor<&'a [u8]>),
+/// Recursively visits the files under `dir`, naming them relative to the component root.
+fn walk_directory(dir: &Path, prefix: &str, f: &mut dyn FnMut(String, &fs::Metadata)) {
+    let read_dir = match fs::read_dir(dir) {
+        Ok(r) => r,
+        Err(e) => {
+            warn!("Couldn't read directory {}: {}", dir.display(), e);
+            return;
+        }
+    };
+
+    for dir_entry in read_dir.filter_map(Result::ok) {
+        let name = match dir_entry.file_name().into_string() {
+            Ok(n) => n,
+            Err(_) => continue,
+        };
+        let path = format!("{}{}", prefix, name);
+
+        match dir_entry.metadata() {
+            Ok(m) if m.is_dir() => walk_directory(&dir_entry.path(), &format!("{}/", path), f),
+            Ok(m) => f(path, &m),
+            Err(_) => (),
+        }
+    }
+}
+
+/// A file visible through the virtual filesystem.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VfsEntry {
+    /// The virtual path of the file.
+    pub path: String,
+
+    /// The size of the file in bytes.
+    pub size: u64,
+}
+
+pub enum VirtualFile {
+    /// A file read from a PAK or PK3 archive when it was opened.
+    PakBacked(Cursor<Box<[u8]>>),
     FileBacked(File),
 }
 
-impl<'a> Read for VirtualFile<'a> {
+impl Read for VirtualFile {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         match self {
             VirtualFile::PakBacked(curs) => curs.read(buf),
@@ -111,7 +560,7 @@ impl<'a> Read for VirtualFile<'a> {
     }
 }
 
-impl<'a> Seek for VirtualFile<'a> {
+impl Seek for VirtualFile {
     fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
         match self {
             VirtualFile::PakBacked(curs) => curs.seek(pos),
@@ -119,3 +568,154 @@ impl<'a> Seek for VirtualFile<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::util::TempDir;
+
+    fn read_to_string(vfs: &Vfs, path: &str) -> String {
+        let mut contents = String::new();
+        vfs.open(path)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        contents
+    }
+
+    #[test]
+    fn test_set_game() {
+        let tmp = TempDir::new();
+        let basedir = tmp.path();
+        for (path, contents) in &[
+            ("id1/autoexec.cfg", "base"),
+            ("id1/default.cfg", "base"),
+            ("hipnotic/autoexec.cfg", "hipnotic"),
+        ] {
+            tmp.write(path, contents);
+        }
+
+        let vfs = Vfs::with_game(&basedir, Some("hipnotic")).unwrap();
+        assert_eq!(vfs.game().as_deref(), Some("hipnotic"));
+        assert_eq!(vfs.game_dir(), basedir.join("hipnotic"));
+        assert_eq!(read_to_string(&vfs, "autoexec.cfg"), "hipnotic");
+        assert_eq!(read_to_string(&vfs, "default.cfg"), "base");
+        assert_eq!(vfs.search_path().len(), 2);
+
+        assert!(vfs.set_game(Some("rogue")).is_err());
+        assert!(vfs.set_game(Some("../hipnotic")).is_err());
+        assert_eq!(vfs.game().as_deref(), Some("hipnotic"));
+
+        vfs.set_game(None).unwrap();
+        assert_eq!(vfs.game(), None);
+        assert_eq!(vfs.game_dir(), basedir.join("id1"));
+        assert_eq!(read_to_string(&vfs, "autoexec.cfg"), "base");
+        assert_eq!(vfs.search_path().len(), 1);
+    }
+
+    #[test]
+    fn test_list_and_glob() {
+        let tmp = TempDir::new();
+        let basedir = tmp.path();
+        for (path, contents) in &[
+            ("id1/maps/e1m1.bsp", "base"),
+            ("id1/maps/e1m2.bsp", "base"),
+            ("id1/demo1.dem", "base"),
+            ("mod/maps/e1m1.bsp", "modded"),
+            ("mod/maps/b_models/b_bh10.bsp", "modded"),
+        ] {
+            tmp.write(path, contents);
+        }
+
+        let vfs = Vfs::with_game(&basedir, Some("mod")).unwrap();
+
+        let maps: Vec<(String, u64)> = vfs
+            .list("maps/")
+            .into_iter()
+            .map(|e| (e.path, e.size))
+            .collect();
+        assert_eq!(
+            maps,
+            vec![
+                ("maps/b_models/b_bh10.bsp".to_owned(), 6),
+                ("maps/e1m1.bsp".to_owned(), 6),
+                ("maps/e1m2.bsp".to_owned(), 4),
+            ]
+        );
+
+        let paths: Vec<String> = vfs.glob("maps/*.bsp").into_iter().map(|e| e.path).collect();
+        assert_eq!(paths, vec!["maps/e1m1.bsp", "maps/e1m2.bsp"]);
+
+        let paths: Vec<String> = vfs.glob("**.dem").into_iter().map(|e| e.path).collect();
+        assert_eq!(paths, vec!["demo1.dem"]);
+    }
+
+    #[test]
+    fn test_normalize() {
+        for path in &[
+            "progs/player.mdl",
+            "progs\\player.mdl",
+            "./progs//player.mdl",
+            "maps/../progs/player.mdl",
+        ] {
+            assert_eq!(normalize(path).as_deref(), Some("progs/player.mdl"));
+        }
+
+        assert_eq!(normalize("../progs/player.mdl"), None);
+        assert_eq!(normalize("./"), None);
+    }
+
+    #[test]
+    fn test_open_case_insensitive() {
+        use crate::common::pak::PakWriter;
+
+        let tmp = TempDir::new();
+        let basedir = tmp.path();
+        let game_dir = basedir.join("id1");
+        tmp.write("id1/progs/player.mdl", "player");
+
+        let mut pak = PakWriter::new();
+        pak.add("maps/E1M1.bsp", &b"e1m1"[..]).unwrap();
+        pak.write_to(File::create(game_dir.join("pak0.pak")).unwrap())
+            .unwrap();
+
+        let vfs = Vfs::with_game(&basedir, None).unwrap();
+        assert_eq!(read_to_string(&vfs, "progs/Player.mdl"), "player");
+        assert_eq!(read_to_string(&vfs, ".\\PROGS\\player.mdl"), "player");
+        assert_eq!(read_to_string(&vfs, "maps/e1m1.bsp"), "e1m1");
+        assert!(vfs.open("../id1/progs/player.mdl").is_err());
+
+        // new files are found by their exact path before the index is rebuilt
+        fs::write(game_dir.join("progs/eyes.mdl"), "eyes").unwrap();
+        assert_eq!(read_to_string(&vfs, "progs/eyes.mdl"), "eyes");
+        vfs.refresh();
+        assert_eq!(read_to_string(&vfs, "progs/EYES.mdl"), "eyes");
+
+        let paths: Vec<String> = vfs.glob("MAPS/*.BSP").into_iter().map(|e| e.path).collect();
+        assert_eq!(paths, vec!["maps/E1M1.bsp"]);
+    }
+
+    #[test]
+    fn test_poll_changes() {
+        let tmp = TempDir::new();
+        let basedir = tmp.path();
+        let game_dir = basedir.join("id1");
+        tmp.write("id1/progs/player.mdl", "player");
+
+        let vfs = Vfs::with_game(&basedir, None).unwrap();
+        assert_eq!(read_to_string(&vfs, "progs/PLAYER.mdl"), "player");
+        assert!(vfs.poll_changes().is_empty());
+        assert!(vfs.poll_changes().is_empty());
+
+        fs::write(game_dir.join("progs/player.mdl"), "new player").unwrap();
+        fs::write(game_dir.join("progs/Eyes.mdl"), "eyes").unwrap();
+        assert_eq!(
+            vfs.poll_changes(),
+            vec!["progs/Eyes.mdl", "progs/player.mdl"]
+        );
+        assert!(vfs.poll_changes().is_empty());
+
+        // the directory index is refreshed when files are added
+        assert_eq!(read_to_string(&vfs, "progs/eyes.mdl"), "eyes");
+    }
+}
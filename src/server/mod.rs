@@ -16,20 +16,70 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 
 pub mod progs;
+pub mod rcon;
 pub mod world;
 
 use std::{
-    io::{Cursor, Seek, SeekFrom},
+    cell::RefCell,
+    io::{Cursor, Seek, SeekFrom, Write as _},
+    net::ToSocketAddrs,
     rc::Rc,
 };
 
-use self::progs::{EntityId, StringId, StringTable};
+use self::{
+    progs::{EntityId, StringId, StringTable},
+    rcon::Rcon,
+};
+use crate::common::{
+    console::{Console, ConsoleError, CvarCallbackId, CvarRegistry},
+    net::{
+        connect::{ConnectListener, Request},
+        NetError, ServerCmd,
+    },
+};
 
 use byteorder::WriteBytesExt;
+use chrono::Duration;
 
 const MAX_DATAGRAM: usize = 1024;
 const MAX_LIGHTSTYLES: usize = 64;
 
+// server cvars whose changes are announced to clients, with their default values
+const NOTIFY_CVARS: &[(&str, &str)] = &[
+    ("fraglimit", "0"),
+    ("noexit", "0"),
+    ("sv_friction", "4"),
+    ("sv_gravity", "800"),
+    ("sv_maxspeed", "320"),
+    ("teamplay", "0"),
+    ("timelimit", "0"),
+];
+
+pub fn register_cvars(cvars: &CvarRegistry) -> Result<(), ConsoleError> {
+    for (name, default) in NOTIFY_CVARS {
+        cvars.register_notify(*name, *default)?;
+    }
+
+    rcon::register_cvars(cvars)?;
+
+    Ok(())
+}
+
+// writes a print command to the datagram, dropping it if there isn't room
+fn datagram_print(datagram: &mut Cursor<Box<[u8]>>, text: String) -> Result<(), NetError> {
+    let mut msg = Vec::new();
+    ServerCmd::Print { text }.serialize(&mut msg)?;
+
+    let remaining = datagram.get_ref().len() - datagram.position() as usize;
+    if msg.len() > remaining {
+        warn!("Datagram overflow, dropping broadcast print");
+        return Ok(());
+    }
+
+    datagram.write_all(&msg)?;
+    Ok(())
+}
+
 pub enum ClientSlot {
     Disconnected,
     InGame(ClientInGame),
@@ -64,23 +114,37 @@ pub struct Server {
     sound_precache: Vec<String>,
     model_precache: Vec<String>,
     lightstyles: [StringId; MAX_LIGHTSTYLES],
-    datagram: Cursor<Box<[u8]>>,
+
+    // shared with the notify cvar callback
+    datagram: Rc<RefCell<Cursor<Box<[u8]>>>>,
+
+    cvars: Rc<RefCell<CvarRegistry>>,
+    notify_callback: CvarCallbackId,
 }
 
 impl Server {
-    pub fn new(string_table: Rc<StringTable>) -> Server {
+    /// Creates a server that announces changes to the server cvars in `cvars` to its clients.
+    ///
+    /// The server cvars must have been registered with `register_cvars`.
+    pub fn new(string_table: Rc<StringTable>, cvars: Rc<RefCell<CvarRegistry>>) -> Server {
         let mut sound_precache = Vec::new();
         sound_precache.push(String::new()); // sound 0 is none
 
         let mut model_precache = Vec::new();
         model_precache.push(String::new()); // model 0 is none
 
+        let datagram: Box<[u8]> = Box::new([0; MAX_DATAGRAM]);
+        let datagram = Rc::new(RefCell::new(Cursor::new(datagram)));
+        let notify_callback = broadcast_notify_cvars(datagram.clone(), &cvars.borrow());
+
         Server {
             string_table,
             sound_precache,
             model_precache,
             lightstyles: [StringId(0); MAX_LIGHTSTYLES],
-            datagram: Cursor::new(Box::new([0; MAX_DATAGRAM])),
+            datagram,
+            cvars,
+            notify_callback,
         }
     }
 
@@ -139,14 +203,199 @@ impl Server {
     }
 
     pub fn clear_datagram(&mut self) {
-        self.datagram.seek(SeekFrom::Start(0)).unwrap();
-        for _ in 0..self.datagram.get_ref().len() {
-            self.datagram.write_u8(0).unwrap();
+        let mut datagram = self.datagram.borrow_mut();
+        datagram.seek(SeekFrom::Start(0)).unwrap();
+        for _ in 0..datagram.get_ref().len() {
+            datagram.write_u8(0).unwrap();
         }
-        self.datagram.seek(SeekFrom::Start(0)).unwrap();
+        datagram.seek(SeekFrom::Start(0)).unwrap();
+    }
+
+    /// Queues a message to be printed on the console of every client.
+    ///
+    /// If the datagram doesn't have room for the message, it is dropped.
+    pub fn broadcast_print<S>(&mut self, text: S) -> Result<(), NetError>
+    where
+        S: Into<String>,
+    {
+        datagram_print(&mut self.datagram.borrow_mut(), text.into())
     }
 
     pub fn set_lightstyle(&mut self, lightstyle_index: usize, lightstyle_val_id: StringId) {
         self.lightstyles[lightstyle_index] = lightstyle_val_id;
     }
 }
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.cvars.borrow().remove_callback(self.notify_callback);
+    }
+}
+
+// broadcasts changes to server notify cvars. A listen server shares its registry with the client,
+// whose userinfo cvars are also notify cvars, so those are filtered out.
+fn broadcast_notify_cvars(
+    datagram: Rc<RefCell<Cursor<Box<[u8]>>>>,
+    cvars: &CvarRegistry,
+) -> CvarCallbackId {
+    cvars.on_notify(Box::new(move |name, value| {
+        if !NOTIFY_CVARS.iter().any(|(n, _)| *n == name) {
+            return;
+        }
+
+        let text = format!("Server cvar \"{}\" changed to \"{}\"\n", name, value);
+        if let Err(e) = datagram_print(&mut datagram.borrow_mut(), text) {
+            error!("Couldn't broadcast cvar change: {}", e);
+        }
+    }))
+}
+
+/// The server's connect socket, which answers requests from clients that aren't connected.
+pub struct ServerListener {
+    listener: ConnectListener,
+    rcon: Rcon,
+}
+
+impl ServerListener {
+    pub fn bind<A>(addr: A) -> Result<ServerListener, NetError>
+    where
+        A: ToSocketAddrs,
+    {
+        Ok(ServerListener {
+            listener: ConnectListener::bind(addr)?,
+            rcon: Rcon::new(),
+        })
+    }
+
+    /// Answers every request waiting on the connect socket.
+    ///
+    /// This should be called once per server frame with the current server time. Malformed
+    /// requests are logged and skipped.
+    pub fn poll(
+        &mut self,
+        time: Duration,
+        cvars: &CvarRegistry,
+        console: &Console,
+    ) -> Result<(), NetError> {
+        loop {
+            let (request, remote) = match self.listener.try_recv_request() {
+                Ok(Some(r)) => r,
+                Ok(None) => return Ok(()),
+                Err(NetError::Io(e)) => return Err(NetError::Io(e)),
+                Err(e) => {
+                    warn!("Bad connect request: {}", e);
+                    continue;
+                }
+            };
+
+            match request {
+                Request::Rcon(r) => {
+                    let response = self.rcon.exec(&r, remote, time, cvars, console);
+                    self.listener.send_response(response, remote)?;
+                }
+
+                // TODO: accept connections and answer queries
+                r => debug!("Ignoring request from {}: {:?}", remote, r),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::{thread, time};
+
+    use crate::common::{
+        console::CmdRegistry,
+        net::connect::{ConnectSocket, Response},
+        vfs::Vfs,
+    };
+
+    // returns the commands written to the datagram
+    fn datagram_cmds(server: &Server) -> Vec<ServerCmd> {
+        let datagram = server.datagram.borrow();
+        let len = datagram.position() as usize;
+        let mut reader = &datagram.get_ref()[..len];
+
+        let mut cmds = Vec::new();
+        while let Some(cmd) = ServerCmd::deserialize(&mut reader).unwrap() {
+            cmds.push(cmd);
+        }
+        cmds
+    }
+
+    fn print_text(cmd: &ServerCmd) -> &str {
+        match cmd {
+            ServerCmd::Print { text } => text,
+            c => panic!("unexpected command {:?}", c),
+        }
+    }
+
+    #[test]
+    fn test_broadcast_notify_cvars() {
+        let cvars = Rc::new(RefCell::new(CvarRegistry::new()));
+        register_cvars(&cvars.borrow()).unwrap();
+
+        // a client userinfo cvar, as on a listen server
+        cvars
+            .borrow()
+            .register_notify("_cl_name", "player")
+            .unwrap();
+
+        let server = Server::new(Rc::new(StringTable::new(Vec::new())), cvars.clone());
+        cvars.borrow().set("sv_gravity", "100").unwrap();
+        cvars.borrow().set("_cl_name", "ranger").unwrap();
+
+        let cmds = datagram_cmds(&server);
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            print_text(&cmds[0]),
+            "Server cvar \"sv_gravity\" changed to \"100\"\n"
+        );
+
+        // the callback is removed with the server
+        drop(server);
+        cvars.borrow().set("sv_gravity", "800").unwrap();
+    }
+
+    #[test]
+    fn test_listener_rcon() {
+        let cmds = Rc::new(RefCell::new(CmdRegistry::new()));
+        let cvars = Rc::new(RefCell::new(CvarRegistry::new()));
+        register_cvars(&cvars.borrow()).unwrap();
+        let console = Console::new(cmds, cvars.clone(), Rc::new(Vfs::new()));
+        cvars.borrow().set("rcon_password", "hunter2").unwrap();
+
+        let mut server = ServerListener::bind("127.0.0.1:0").unwrap();
+        let server_addr = server.listener.local_addr().unwrap();
+        let mut client = ConnectSocket::bind("127.0.0.1:0").unwrap();
+        client
+            .send_request(
+                Request::rcon("hunter2", "sv_gravity 100; sv_gravity"),
+                server_addr,
+            )
+            .unwrap();
+
+        // the request may not be waiting on the first frame
+        for _ in 0..100 {
+            server
+                .poll(Duration::zero(), &cvars.borrow(), &console)
+                .unwrap();
+            if cvars.borrow().get("sv_gravity").unwrap() == "100" {
+                break;
+            }
+            thread::sleep(time::Duration::from_millis(10));
+        }
+
+        match client
+            .recv_response(Some(Duration::seconds(5)))
+            .unwrap()
+            .map(|(r, _)| r)
+        {
+            Some(Response::Rcon(r)) => assert_eq!(r.output, "\"sv_gravity\" is \"100\"\n"),
+            r => panic!("unexpected response {:?}", r),
+        }
+    }
+}
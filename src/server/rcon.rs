@@ -0,0 +1,391 @@
+//! Remote console support.
+//!
+//! Clients send `Request::Rcon` to the server's connect socket with the value of the server's
+//! `rcon_password` cvar. The command is run on the server's console and the output it printed is
+//! sent back in `Response::Rcon`.
+//!
+//! An address that sends `MAX_BAD_PASSWORDS` bad passwords in a row is locked out for
+//! `LOCKOUT_SECS` seconds, during which its requests are rejected without checking the password.
+//! Its count of bad passwords is forgotten after `BAD_PASSWORD_RESET_SECS` seconds without one.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+};
+
+use crate::common::{
+    console::{Console, ConsoleError, CvarRegistry},
+    net::connect::{RequestRcon, Response, ResponseRcon, ResponseReject},
+};
+
+use chrono::Duration;
+
+/// The number of bad passwords in a row after which an address is locked out.
+pub const MAX_BAD_PASSWORDS: u32 = 5;
+
+/// The length of a lockout in seconds.
+pub const LOCKOUT_SECS: i64 = 30;
+
+/// The number of seconds without a bad password after which an address's count is reset.
+pub const BAD_PASSWORD_RESET_SECS: i64 = 600;
+
+/// The maximum number of addresses whose bad passwords are tracked at once.
+///
+/// Source addresses are easily forged, so this bounds the memory used by a flood of requests.
+pub const MAX_TRACKED_ADDRESSES: usize = 1024;
+
+/// Registers the rcon cvars.
+///
+/// This is called by `server::register_cvars`. A listen server shares its registry with the
+/// client, which registers `rcon_password` for its own `rcon` command, so an existing cvar is
+/// left as it is.
+pub fn register_cvars(cvars: &CvarRegistry) -> Result<(), ConsoleError> {
+    if !cvars.contains("rcon_password") {
+        cvars
+            .define("rcon_password", "")
+            .description("password for remote console commands (rcon is disabled if empty)")
+            .register()?;
+    }
+
+    Ok(())
+}
+
+// compares passwords without returning early, so the response time doesn't leak a prefix match
+fn password_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn reject<S>(message: S) -> Response
+where
+    S: Into<String>,
+{
+    Response::Reject(ResponseReject {
+        message: message.into(),
+    })
+}
+
+// bad passwords sent from a single address
+struct BadPasswords {
+    count: u32,
+    last_time: Duration,
+    locked_until: Option<Duration>,
+}
+
+impl BadPasswords {
+    // the time at which this entry can be forgotten
+    fn expires(&self) -> Duration {
+        match self.locked_until {
+            Some(t) => t,
+            None => self.last_time + Duration::seconds(BAD_PASSWORD_RESET_SECS),
+        }
+    }
+}
+
+/// Runs rcon requests, locking out addresses that send too many bad passwords.
+#[derive(Default)]
+pub struct Rcon {
+    // keyed by IP rather than socket address so a client can't reset its count by changing ports
+    bad_passwords: HashMap<IpAddr, BadPasswords>,
+}
+
+impl Rcon {
+    pub fn new() -> Rcon {
+        Rcon::default()
+    }
+
+    /// Runs the command in an rcon request on `console` and returns the response to send back.
+    ///
+    /// `time` is the current server time, used to expire lockouts. The response contains the
+    /// output printed while the command ran. Commands deferred with `wait` run on a later frame,
+    /// so their output isn't included.
+    pub fn exec(
+        &mut self,
+        request: &RequestRcon,
+        remote: SocketAddr,
+        time: Duration,
+        cvars: &CvarRegistry,
+        console: &Console,
+    ) -> Response {
+        let password = cvars.get("rcon_password").unwrap_or_default();
+        if password.is_empty() {
+            return reject("rcon is disabled on this server");
+        }
+
+        let ip = remote.ip();
+        if let Some(bad) = self.bad_passwords.get(&ip) {
+            if time >= bad.expires() {
+                self.bad_passwords.remove(&ip);
+            } else if bad.locked_until.is_some() {
+                return reject("Too many bad rcon passwords, try again later");
+            }
+        }
+
+        if !password_eq(password.as_bytes(), request.password.as_bytes()) {
+            warn!("Bad rcon password from {}", remote);
+
+            if !self.bad_passwords.contains_key(&ip) {
+                self.make_room(time);
+            }
+
+            let bad = self.bad_passwords.entry(ip).or_insert(BadPasswords {
+                count: 0,
+                last_time: time,
+                locked_until: None,
+            });
+            bad.count += 1;
+            bad.last_time = time;
+            if bad.count >= MAX_BAD_PASSWORDS {
+                warn!("Locking out rcon from {} for {} seconds", ip, LOCKOUT_SECS);
+                bad.locked_until = Some(time + Duration::seconds(LOCKOUT_SECS));
+            }
+
+            return reject("Bad rcon password");
+        }
+
+        self.bad_passwords.remove(&ip);
+        info!("rcon from {}: {}", remote, request.command);
+
+        console.output_mut().begin_capture();
+        console.stuff_text(&request.command);
+        console.execute();
+        let mut output = console.output_mut().end_capture();
+
+        if output.len() > ResponseRcon::MAX_OUTPUT {
+            let mut end = ResponseRcon::MAX_OUTPUT;
+            while !output.is_char_boundary(end) {
+                end -= 1;
+            }
+            output.truncate(end);
+        }
+
+        Response::Rcon(ResponseRcon { output })
+    }
+
+    // makes room to track another address, first by forgetting expired entries, then by
+    // forgetting the entry closest to expiring
+    fn make_room(&mut self, time: Duration) {
+        if self.bad_passwords.len() < MAX_TRACKED_ADDRESSES {
+            return;
+        }
+
+        self.bad_passwords.retain(|_, bad| time < bad.expires());
+
+        if self.bad_passwords.len() >= MAX_TRACKED_ADDRESSES {
+            let oldest = self
+                .bad_passwords
+                .iter()
+                .min_by_key(|(_, bad)| bad.expires())
+                .map(|(ip, _)| *ip);
+            if let Some(ip) = oldest {
+                self.bad_passwords.remove(&ip);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::common::{console::CmdRegistry, vfs::Vfs};
+
+    fn test_console() -> (Rc<RefCell<CvarRegistry>>, Console) {
+        let cmds = Rc::new(RefCell::new(CmdRegistry::new()));
+        let cvars = Rc::new(RefCell::new(CvarRegistry::new()));
+        cvars.borrow().register("sv_gravity", "800").unwrap();
+        register_cvars(&cvars.borrow()).unwrap();
+        let console = Console::new(cmds, cvars.clone(), Rc::new(Vfs::new()));
+        (cvars, console)
+    }
+
+    fn rcon(password: &str, command: &str) -> RequestRcon {
+        RequestRcon {
+            password: password.to_owned(),
+            command: command.to_owned(),
+        }
+    }
+
+    fn remote() -> SocketAddr {
+        "127.0.0.1:27001".parse().unwrap()
+    }
+
+    // runs a request from `remote()` at `secs` seconds of server time
+    fn exec(
+        rcon_state: &mut Rcon,
+        request: &RequestRcon,
+        secs: i64,
+        cvars: &RefCell<CvarRegistry>,
+        console: &Console,
+    ) -> Response {
+        let cvars = cvars.borrow();
+        rcon_state.exec(request, remote(), Duration::seconds(secs), &cvars, console)
+    }
+
+    #[test]
+    fn test_rcon_disabled() {
+        let (cvars, console) = test_console();
+        match exec(
+            &mut Rcon::new(),
+            &rcon("", "sv_gravity 100"),
+            0,
+            &cvars,
+            &console,
+        ) {
+            Response::Reject(_) => (),
+            r => panic!("unexpected response {:?}", r),
+        }
+        assert_eq!(cvars.borrow().get("sv_gravity").unwrap(), "800");
+    }
+
+    #[test]
+    fn test_rcon_bad_password() {
+        let (cvars, console) = test_console();
+        cvars.borrow().set("rcon_password", "hunter2").unwrap();
+        match exec(
+            &mut Rcon::new(),
+            &rcon("hunter3", "sv_gravity 100"),
+            0,
+            &cvars,
+            &console,
+        ) {
+            Response::Reject(r) => assert_eq!(r.message, "Bad rcon password"),
+            r => panic!("unexpected response {:?}", r),
+        }
+        assert_eq!(cvars.borrow().get("sv_gravity").unwrap(), "800");
+    }
+
+    #[test]
+    fn test_rcon_output() {
+        let (cvars, console) = test_console();
+        cvars.borrow().set("rcon_password", "hunter2").unwrap();
+        let request = rcon("hunter2", "sv_gravity 100; sv_gravity");
+        match exec(&mut Rcon::new(), &request, 0, &cvars, &console) {
+            Response::Rcon(r) => assert_eq!(r.output, "\"sv_gravity\" is \"100\"\n"),
+            r => panic!("unexpected response {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_rcon_lockout() {
+        let (cvars, console) = test_console();
+        cvars.borrow().set("rcon_password", "hunter2").unwrap();
+        let mut rcon_state = Rcon::new();
+        let bad = rcon("hunter3", "sv_gravity 100");
+        let good = rcon("hunter2", "sv_gravity 100");
+
+        for _ in 0..MAX_BAD_PASSWORDS {
+            exec(&mut rcon_state, &bad, 0, &cvars, &console);
+        }
+
+        // the right password is rejected until the lockout expires
+        match exec(&mut rcon_state, &good, LOCKOUT_SECS - 1, &cvars, &console) {
+            Response::Reject(r) => assert!(r.message.starts_with("Too many")),
+            r => panic!("unexpected response {:?}", r),
+        }
+        assert_eq!(cvars.borrow().get("sv_gravity").unwrap(), "800");
+
+        match exec(&mut rcon_state, &good, LOCKOUT_SECS, &cvars, &console) {
+            Response::Rcon(_) => (),
+            r => panic!("unexpected response {:?}", r),
+        }
+        assert_eq!(cvars.borrow().get("sv_gravity").unwrap(), "100");
+    }
+
+    #[test]
+    fn test_rcon_good_password_resets_count() {
+        let (cvars, console) = test_console();
+        cvars.borrow().set("rcon_password", "hunter2").unwrap();
+        let mut rcon_state = Rcon::new();
+        let bad = rcon("hunter3", "sv_gravity");
+        let good = rcon("hunter2", "sv_gravity");
+
+        for _ in 0..MAX_BAD_PASSWORDS - 1 {
+            exec(&mut rcon_state, &bad, 0, &cvars, &console);
+        }
+        exec(&mut rcon_state, &good, 0, &cvars, &console);
+        exec(&mut rcon_state, &bad, 0, &cvars, &console);
+
+        match exec(&mut rcon_state, &good, 0, &cvars, &console) {
+            Response::Rcon(_) => (),
+            r => panic!("unexpected response {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_rcon_bad_password_count_resets() {
+        let (cvars, console) = test_console();
+        cvars.borrow().set("rcon_password", "hunter2").unwrap();
+        let mut rcon_state = Rcon::new();
+        let bad = rcon("hunter3", "sv_gravity");
+        let good = rcon("hunter2", "sv_gravity");
+
+        // typos spread far enough apart don't add up to a lockout
+        for i in 0..MAX_BAD_PASSWORDS as i64 {
+            exec(
+                &mut rcon_state,
+                &bad,
+                i * BAD_PASSWORD_RESET_SECS,
+                &cvars,
+                &console,
+            );
+        }
+
+        match exec(
+            &mut rcon_state,
+            &good,
+            MAX_BAD_PASSWORDS as i64 * BAD_PASSWORD_RESET_SECS,
+            &cvars,
+            &console,
+        ) {
+            Response::Rcon(_) => (),
+            r => panic!("unexpected response {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_rcon_tracked_addresses() {
+        let (cvars, console) = test_console();
+        cvars.borrow().set("rcon_password", "hunter2").unwrap();
+        let mut rcon_state = Rcon::new();
+        let bad = rcon("hunter3", "sv_gravity");
+
+        let flood = |rcon_state: &mut Rcon, count: usize, secs: i64| {
+            for i in 0..count {
+                let remote = SocketAddr::from(([10, 0, (i >> 8) as u8, i as u8], 27001));
+                rcon_state.exec(
+                    &bad,
+                    remote,
+                    Duration::seconds(secs),
+                    &cvars.borrow(),
+                    &console,
+                );
+            }
+        };
+
+        flood(&mut rcon_state, MAX_TRACKED_ADDRESSES + 10, 0);
+        assert_eq!(rcon_state.bad_passwords.len(), MAX_TRACKED_ADDRESSES);
+
+        // expired entries are forgotten to make room
+        exec(
+            &mut rcon_state,
+            &bad,
+            BAD_PASSWORD_RESET_SECS,
+            &cvars,
+            &console,
+        );
+        assert_eq!(rcon_state.bad_passwords.len(), 1);
+    }
+
+    #[test]
+    fn test_password_eq() {
+        assert!(password_eq(b"hunter2", b"hunter2"));
+        assert!(!password_eq(b"hunter2", b"hunter3"));
+        assert!(!password_eq(b"hunter2", b"hunter"));
+    }
+}
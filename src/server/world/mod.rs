@@ -839,19 +839,8 @@ impl World {
                 let size = max - min;
                 match self.models[self.try_get_entity(e_id)?.model_index()?].kind() {
                     &ModelKind::Brush(ref bmodel) => {
-                        let hull_index;
-
-                        // TODO: replace these magic constants
-                        if size[0] < 3.0 {
-                            debug!("Using hull 0");
-                            hull_index = 0;
-                        } else if size[0] <= 32.0 {
-                            debug!("Using hull 1");
-                            hull_index = 1;
-                        } else {
-                            debug!("Using hull 2");
-                            hull_index = 2;
-                        }
+                        let hull_index = bmodel.hull_index_for_size(size);
+                        debug!("Using hull {}", hull_index);
 
                         let hull = bmodel.hull(hull_index).unwrap();
 
@@ -0,0 +1,615 @@
+//! Demo playback determinism tests.
+//!
+//! Each demo is played through `Client` at a fixed timestep, without a window or audio device. The
+//! `TraceFrame` for every frame is hashed and compared against the golden hashes in
+//! `tests/golden/<demo>.txt`.
+//!
+//! `test_synthetic_demo` plays a small demo and map that are built by this file, so it needs no
+//! game data and runs in CI. The demo signs on, spawns and moves entities, teleports one, lets
+//! another drop out and spawns temp entities, which covers `parse_server_msg` and entity
+//! interpolation. Temp entities themselves aren't part of the trace, so the test only checks that
+//! they parse and spawn without disturbing the rest of the frame.
+//!
+//! `test_pak_demos` plays the demos from a fixture PAK instead. The PAK contains game data that
+//! can't be redistributed, and the golden hashes depend on it, so neither is checked in and the
+//! test is ignored by default. To run it, copy a PAK containing the demos and the maps and sounds
+//! they use (e.g. the shareware `pak0.pak`) to `tests/fixtures/demo.pak` and create the golden
+//! files with
+//!
+//! ```text
+//! RICHTER_BLESS=1 cargo test --test demo_determinism -- --ignored
+//! ```
+//!
+//! before making changes, then run it again without `RICHTER_BLESS` afterwards. Either test fails
+//! if its golden files are missing. After an intentional change to client behavior, bless the
+//! golden files again.
+
+use std::{
+    cell::RefCell,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use richter::{
+    client::{self, Client},
+    common::{
+        console::{CmdRegistry, Console, CvarRegistry},
+        net::{
+            GameType, ItemFlags, PointEntityKind, ServerCmd, SignOnStage, TempEntity, UpdateFlags,
+            FAST_UPDATE_FLAG,
+        },
+        vfs::Vfs,
+    },
+};
+
+use cgmath::{Deg, Vector3};
+use chrono::Duration;
+
+const FIXTURE_PAK: &str = "tests/fixtures/demo.pak";
+const GOLDEN_DIR: &str = "tests/golden";
+const PAK_DEMOS: &[&str] = &["demo1.dem", "demo2.dem", "demo3.dem"];
+
+const SYNTHETIC_DEMO: &str = "synthetic.dem";
+const SYNTHETIC_MAP: &str = "maps/synthetic.bsp";
+const SYNTHETIC_SPRITE: &str = "progs/s_synthetic.spr";
+
+// the synthetic map's world is model 1, so the sprite is model 2
+const SPRITE_MODEL_ID: u8 = 2;
+
+// seconds between updates in the synthetic demo
+const SYNTHETIC_UPDATE_SECS: f32 = 0.1;
+const SYNTHETIC_UPDATE_COUNT: usize = 20;
+
+// 72 fps, the default value of host_maxfps
+const FRAME_MICROS: i64 = 1_000_000 / 72;
+
+// demos longer than this are assumed to be stuck
+const MAX_FRAMES: usize = 72 * 60 * 10;
+
+// FNV-1a, since the std hasher isn't guaranteed to be stable between releases
+fn fnv1a(data: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for b in data {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Plays the demo and returns the hash of each frame's trace.
+fn trace_demo(vfs: Rc<Vfs>, demo: &str) -> Vec<u64> {
+    let cvars = Rc::new(RefCell::new(CvarRegistry::new()));
+    client::register_cvars(&cvars.borrow()).unwrap();
+    let cmds = Rc::new(RefCell::new(CmdRegistry::new()));
+    let console = Rc::new(RefCell::new(Console::new(
+        cmds.clone(),
+        cvars.clone(),
+        vfs.clone(),
+    )));
+
+    let mut cl = Client::play_demo(demo, vfs, cvars, cmds, console, None).unwrap();
+
+    let mut hashes = Vec::new();
+    for _ in 0..MAX_FRAMES {
+        cl.frame(Duration::microseconds(FRAME_MICROS)).unwrap();
+
+        if cl.demo_finished() {
+            return hashes;
+        }
+
+        // entities don't exist until the demo has signed on
+        if cl.signon_stage() != SignOnStage::Done {
+            continue;
+        }
+
+        let view_ent = cl.view_ent();
+        let frame = cl.trace(std::iter::once(&view_ent).chain(cl.visible_entity_ids()));
+
+        // serde_json::Value sorts map keys, so the serialized entity map is deterministic
+        let value = serde_json::to_value(&frame).unwrap();
+        hashes.push(fnv1a(value.to_string().as_bytes()));
+    }
+
+    panic!("{} did not finish after {} frames", demo, MAX_FRAMES);
+}
+
+fn golden_path(demo: &str) -> PathBuf {
+    Path::new(GOLDEN_DIR).join(demo).with_extension("txt")
+}
+
+fn read_golden(path: &Path) -> Vec<u64> {
+    let text = fs::read_to_string(path).unwrap_or_else(|e| {
+        panic!(
+            "Couldn't read {} (run with RICHTER_BLESS=1 to create it): {}",
+            path.display(),
+            e
+        )
+    });
+
+    text.lines()
+        .map(|line| u64::from_str_radix(line.trim(), 16).unwrap())
+        .collect()
+}
+
+fn write_golden(path: &Path, hashes: &[u64]) {
+    fs::create_dir_all(GOLDEN_DIR).unwrap();
+    let text: String = hashes.iter().map(|h| format!("{:016x}\n", h)).collect();
+    fs::write(path, text).unwrap();
+}
+
+fn blessing() -> bool {
+    std::env::var_os("RICHTER_BLESS").is_some()
+}
+
+/// Plays the demo twice and compares its hashes against the golden file, or replaces the golden
+/// file if `bless` is set.
+fn check_demo(vfs: Rc<Vfs>, demo: &str, bless: bool) {
+    let hashes = trace_demo(vfs.clone(), demo);
+    assert!(!hashes.is_empty(), "{} produced no frames", demo);
+
+    // playing the same demo twice must produce identical traces
+    assert_eq!(
+        hashes,
+        trace_demo(vfs, demo),
+        "{} playback is nondeterministic",
+        demo
+    );
+
+    let path = golden_path(demo);
+    if bless {
+        write_golden(&path, &hashes);
+        return;
+    }
+
+    let golden = read_golden(&path);
+    if let Some(frame) = hashes.iter().zip(golden.iter()).position(|(a, b)| a != b) {
+        panic!(
+            "{} diverged from {} at frame {}",
+            demo,
+            path.display(),
+            frame
+        );
+    }
+
+    assert_eq!(
+        hashes.len(),
+        golden.len(),
+        "{} frame count differs from {}",
+        demo,
+        path.display()
+    );
+}
+
+/// A game directory holding the synthetic demo and the files it uses, which is deleted when it
+/// goes out of scope.
+struct SyntheticGame {
+    path: PathBuf,
+}
+
+impl SyntheticGame {
+    fn new() -> SyntheticGame {
+        let path =
+            std::env::temp_dir().join(format!("richter-demo-determinism-{}", std::process::id()));
+
+        let game = SyntheticGame { path };
+        game.write(SYNTHETIC_DEMO, build_demo());
+        game.write(SYNTHETIC_MAP, build_map());
+        game.write(SYNTHETIC_SPRITE, build_sprite(8, 8));
+        game.write("sound/misc/null.wav", build_wav(16));
+        game
+    }
+
+    fn write(&self, path: &str, contents: Vec<u8>) {
+        let path = self.path.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+}
+
+impl Drop for SyntheticGame {
+    fn drop(&mut self) {
+        // don't panic while a failed test is already unwinding
+        if let Err(e) = fs::remove_dir_all(&self.path) {
+            eprintln!("Couldn't remove {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+fn put_i32s(data: &mut Vec<u8>, values: &[i32]) {
+    for v in values {
+        data.extend_from_slice(&v.to_le_bytes());
+    }
+}
+
+fn put_i16s(data: &mut Vec<u8>, values: &[i16]) {
+    for v in values {
+        data.extend_from_slice(&v.to_le_bytes());
+    }
+}
+
+fn put_f32s(data: &mut Vec<u8>, values: &[f32]) {
+    for v in values {
+        data.extend_from_slice(&v.to_le_bytes());
+    }
+}
+
+// builds a BSP29 map whose world is a single 256x256 floor face
+fn build_map() -> Vec<u8> {
+    const SECTION_COUNT: usize = 15;
+    const ENTITIES: usize = 0;
+    const PLANES: usize = 1;
+    const TEXTURES: usize = 2;
+    const VERTICES: usize = 3;
+    const RENDER_NODES: usize = 5;
+    const TEXTURE_INFO: usize = 6;
+    const FACES: usize = 7;
+    const LIGHTMAPS: usize = 8;
+    const COLLISION_NODES: usize = 9;
+    const LEAVES: usize = 10;
+    const FACE_LIST: usize = 11;
+    const EDGES: usize = 12;
+    const EDGE_LIST: usize = 13;
+    const MODELS: usize = 14;
+
+    let mut sections = vec![Vec::new(); SECTION_COUNT];
+
+    sections[ENTITIES] = b"{\n\"classname\" \"worldspawn\"\n}\n\0".to_vec();
+
+    // z = 0, axial along z
+    put_f32s(&mut sections[PLANES], &[0.0, 0.0, 1.0, 0.0]);
+    put_i32s(&mut sections[PLANES], &[2]);
+
+    // a single 16x16 texture with all of its mipmaps
+    let textures = &mut sections[TEXTURES];
+    put_i32s(textures, &[1, 8]);
+    textures.extend_from_slice(b"floor\0\0\0\0\0\0\0\0\0\0\0");
+    put_i32s(textures, &[16, 16, 40, 296, 360, 376]);
+    textures.extend(std::iter::repeat(7).take(256 + 64 + 16 + 4));
+
+    put_f32s(
+        &mut sections[VERTICES],
+        &[
+            0.0, 0.0, 0.0, 256.0, 0.0, 0.0, 256.0, 256.0, 0.0, 0.0, 256.0, 0.0,
+        ],
+    );
+
+    // front is leaf 1, back is leaf 0
+    let nodes = &mut sections[RENDER_NODES];
+    put_i32s(nodes, &[0]);
+    put_i16s(nodes, &[-2, -1, 0, 0, 0, 256, 256, 0, 0, 1]);
+
+    put_f32s(
+        &mut sections[TEXTURE_INFO],
+        &[1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+    );
+    put_i32s(&mut sections[TEXTURE_INFO], &[0, 0]);
+
+    // unlit, so the map needs no lightmap
+    let faces = &mut sections[FACES];
+    put_i16s(faces, &[0, 0]);
+    put_i32s(faces, &[0]);
+    put_i16s(faces, &[4, 0]);
+    faces.extend_from_slice(&[255, 255, 255, 255]);
+    put_i32s(faces, &[-1]);
+
+    sections[LIGHTMAPS] = Vec::new();
+
+    put_i32s(&mut sections[COLLISION_NODES], &[0]);
+    put_i16s(&mut sections[COLLISION_NODES], &[-1, -2]);
+
+    let leaves = &mut sections[LEAVES];
+    for (contents, face_count) in &[(-2, 0), (-1, 1)] {
+        put_i32s(leaves, &[*contents, -1]);
+        put_i16s(leaves, &[0, 0, 0, 256, 256, 0, 0, *face_count]);
+        leaves.extend_from_slice(&[0; 4]);
+    }
+
+    put_i16s(&mut sections[FACE_LIST], &[0]);
+
+    // edge 0 can't be referenced backwards, so it's left unused
+    put_i16s(&mut sections[EDGES], &[0, 0, 0, 1, 1, 2, 2, 3, 3, 0]);
+    put_i32s(&mut sections[EDGE_LIST], &[1, 2, 3, 4]);
+
+    let models = &mut sections[MODELS];
+    put_f32s(models, &[0.0, 0.0, 0.0, 256.0, 256.0, 0.0, 0.0, 0.0, 0.0]);
+    put_i32s(models, &[0, 0, 0, 0, 1, 0, 1]);
+
+    let mut data = 29i32.to_le_bytes().to_vec();
+    let mut offset = 4 + SECTION_COUNT * 8;
+    for section in &sections {
+        put_i32s(&mut data, &[offset as i32, section.len() as i32]);
+        offset += section.len();
+    }
+
+    for section in sections {
+        data.extend(section);
+    }
+
+    data
+}
+
+// builds a sprite with a single frame of the given size
+fn build_sprite(width: i32, height: i32) -> Vec<u8> {
+    let mut data = b"IDSP".to_vec();
+    put_i32s(&mut data, &[1, 0]); // version, kind
+    put_f32s(&mut data, &[1.0]); // radius
+
+    // max size, frame count, beam length, sync type, then the frame's kind, origin and size
+    put_i32s(&mut data, &[width, height, 1, 0, 0, 0, 0, 0, width, height]);
+
+    data.resize(data.len() + (width * height) as usize, 0);
+    data
+}
+
+fn build_wav(sample_count: usize) -> Vec<u8> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 11025,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut data = Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut data, spec).unwrap();
+    for _ in 0..sample_count {
+        writer.write_sample(0i16).unwrap();
+    }
+    writer.finalize().unwrap();
+    data.into_inner()
+}
+
+// appends an entity update, which `ServerCmd::serialize` doesn't support
+fn put_update(msg: &mut Vec<u8>, ent_id: u8, model_id: Option<u8>, origin: [f32; 3], yaw: f32) {
+    let mut flags = UpdateFlags::ORIGIN_X | UpdateFlags::ORIGIN_Y | UpdateFlags::ORIGIN_Z;
+    flags |= UpdateFlags::YAW;
+    if model_id.is_some() {
+        flags |= UpdateFlags::MODEL | UpdateFlags::MORE_BITS;
+    }
+
+    msg.push(flags.bits() as u8 | FAST_UPDATE_FLAG);
+    if flags.contains(UpdateFlags::MORE_BITS) {
+        msg.push((flags.bits() >> 8) as u8);
+    }
+
+    msg.push(ent_id);
+    if let Some(m) = model_id {
+        msg.push(m);
+    }
+
+    let coord = |c: f32| (c * 8.0) as i16;
+    put_i16s(msg, &[coord(origin[0])]);
+    put_i16s(msg, &[coord(origin[1])]);
+    msg.push((yaw as i32 * 256 / 360) as u8);
+    put_i16s(msg, &[coord(origin[2])]);
+}
+
+fn put_cmds(msg: &mut Vec<u8>, cmds: &[ServerCmd]) {
+    for cmd in cmds {
+        cmd.serialize(msg).unwrap();
+    }
+}
+
+fn client_data() -> ServerCmd {
+    ServerCmd::ClientData {
+        view_height: None,
+        ideal_pitch: None,
+        punch_pitch: None,
+        velocity_x: None,
+        punch_yaw: None,
+        velocity_y: None,
+        punch_roll: None,
+        velocity_z: None,
+        items: ItemFlags::empty(),
+        on_ground: true,
+        in_water: false,
+        weapon_frame: None,
+        armor: None,
+        weapon: None,
+        health: 100,
+        ammo: 0,
+        ammo_shells: 0,
+        ammo_nails: 0,
+        ammo_rockets: 0,
+        ammo_cells: 0,
+        active_weapon: 0,
+    }
+}
+
+fn spawn_baseline(ent_id: u16, model_id: u8, origin: [f32; 3]) -> ServerCmd {
+    ServerCmd::SpawnBaseline {
+        ent_id,
+        model_id,
+        frame_id: 0,
+        colormap: 0,
+        skin_id: 0,
+        origin: origin.into(),
+        angles: Vector3::new(Deg(0.0), Deg(0.0), Deg(0.0)),
+    }
+}
+
+// offsets of the points on a circle with radius 32
+const CIRCLE: [(f32, f32); 8] = [
+    (32.0, 0.0),
+    (22.625, 22.625),
+    (0.0, 32.0),
+    (-22.625, 22.625),
+    (-32.0, 0.0),
+    (-22.625, -22.625),
+    (0.0, -32.0),
+    (22.625, -22.625),
+];
+
+// builds a demo on the synthetic map. the player (entity 1) walks along the x axis while turning,
+// entity 2 circles the player and is teleported halfway through, and entity 3 appears for a few
+// updates.
+fn build_demo() -> Vec<u8> {
+    // view angles and message for each update
+    let mut messages = Vec::new();
+
+    let mut msg = Vec::new();
+    put_cmds(
+        &mut msg,
+        &[
+            ServerCmd::ServerInfo {
+                protocol_version: 15,
+                max_clients: 1,
+                game_type: GameType::CoOp,
+                message: String::from("synthetic"),
+                model_precache: vec![SYNTHETIC_MAP.to_owned(), SYNTHETIC_SPRITE.to_owned()],
+                sound_precache: Vec::new(),
+            },
+            ServerCmd::SignOnStage {
+                stage: SignOnStage::Prespawn,
+            },
+        ],
+    );
+    messages.push(([0.0; 3], msg));
+
+    let mut msg = Vec::new();
+    put_cmds(
+        &mut msg,
+        &[
+            spawn_baseline(0, 1, [0.0, 0.0, 0.0]),
+            spawn_baseline(1, SPRITE_MODEL_ID, [16.0, 128.0, 24.0]),
+            spawn_baseline(2, SPRITE_MODEL_ID, [128.0, 128.0, 24.0]),
+            ServerCmd::SignOnStage {
+                stage: SignOnStage::ClientInfo,
+            },
+        ],
+    );
+    messages.push(([0.0; 3], msg));
+
+    let mut msg = Vec::new();
+    put_cmds(
+        &mut msg,
+        &[
+            ServerCmd::SetView { ent_id: 1 },
+            ServerCmd::SignOnStage {
+                stage: SignOnStage::Begin,
+            },
+        ],
+    );
+    messages.push(([0.0; 3], msg));
+
+    for i in 0..SYNTHETIC_UPDATE_COUNT {
+        let f = i as f32;
+
+        let mut msg = Vec::new();
+        put_cmds(
+            &mut msg,
+            &[
+                ServerCmd::Time {
+                    time: 1.0 + f * SYNTHETIC_UPDATE_SECS,
+                },
+                client_data(),
+            ],
+        );
+
+        // walk along x. the player's angles come from the demo's view angles instead
+        put_update(&mut msg, 1, None, [16.0 + 10.0 * f, 128.0, 24.0], 0.0);
+
+        // circle the player, then jump to the other side of the map. the circle is a table rather
+        // than trig so the demo doesn't depend on the platform's libm
+        let (x, y) = if i < SYNTHETIC_UPDATE_COUNT / 2 {
+            let (dx, dy) = CIRCLE[i % CIRCLE.len()];
+            (16.0 + 10.0 * f + dx, 128.0 + dy)
+        } else {
+            (32.0, 32.0 + 8.0 * f)
+        };
+        put_update(&mut msg, 2, None, [x, y, 24.0], 45.0 * f);
+
+        if (5..10).contains(&i) {
+            put_update(
+                &mut msg,
+                3,
+                Some(SPRITE_MODEL_ID),
+                [200.0, 200.0 - 4.0 * f, 24.0],
+                90.0,
+            );
+        }
+
+        let temp_entity = match i {
+            4 => Some(PointEntityKind::Explosion),
+            8 => Some(PointEntityKind::Teleport),
+            12 => Some(PointEntityKind::Gunshot),
+            _ => None,
+        };
+        if let Some(kind) = temp_entity {
+            put_cmds(
+                &mut msg,
+                &[ServerCmd::TempEntity {
+                    temp_entity: TempEntity::Point {
+                        kind,
+                        origin: Vector3::new(64.0, 64.0, 8.0),
+                    },
+                }],
+            );
+        }
+
+        // turn through 0 degrees so the yaw wraps around
+        let yaw = (340 + 4 * i as i32).rem_euclid(360) as f32;
+        messages.push(([5.0, yaw, 0.0], msg));
+    }
+
+    // no cd track
+    let mut data = b"-1\n".to_vec();
+    for (view_angles, msg) in messages {
+        put_i32s(&mut data, &[msg.len() as i32]);
+        put_f32s(&mut data, &view_angles);
+        data.extend(msg);
+    }
+
+    data
+}
+
+#[test]
+fn test_synthetic_demo() {
+    let game = SyntheticGame::new();
+
+    let mut vfs = Vfs::new();
+    vfs.add_directory(&game.path).unwrap();
+
+    check_demo(Rc::new(vfs), SYNTHETIC_DEMO, blessing());
+}
+
+#[test]
+#[ignore]
+fn test_pak_demos() {
+    assert!(
+        Path::new(FIXTURE_PAK).exists(),
+        "{} not found, see tests/demo_determinism.rs",
+        FIXTURE_PAK
+    );
+
+    // check before playing anything, since a full run takes a while
+    let bless = blessing();
+    if !bless {
+        let missing: Vec<PathBuf> = PAK_DEMOS
+            .iter()
+            .map(|demo| golden_path(demo))
+            .filter(|path| !path.exists())
+            .collect();
+        assert!(
+            missing.is_empty(),
+            "Missing golden files {:?} (run with RICHTER_BLESS=1 to create them)",
+            missing
+        );
+    }
+
+    let mut vfs = Vfs::new();
+    vfs.add_pakfile(FIXTURE_PAK).unwrap();
+    let vfs = Rc::new(vfs);
+
+    for demo in PAK_DEMOS {
+        check_demo(vfs.clone(), demo, bless);
+    }
+}
+
+#[test]
+fn test_fnv1a() {
+    // reference values from the FNV specification
+    assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+    assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+}